@@ -1,6 +1,5 @@
 use super::args;
 use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
-use std::collections::HashSet;
 use std::io::BufRead;
 use std::io::Write;
 
@@ -121,50 +120,9 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         }
     }
 
-    let genus_list: Vec<String> = all_parts
-        .iter()
-        .filter(|p| p.is_normal)
-        .map(|p| p.genus.clone())
-        .collect::<HashSet<_>>()
-        .into_iter()
-        .collect();
-
-    let species_list: Vec<String> = all_parts
-        .iter()
-        .filter(|p| p.is_normal)
-        .map(|p| p.species.clone())
-        .filter(|s| !s.is_empty())
-        .collect::<HashSet<_>>()
-        .into_iter()
-        .collect();
-
-    let genus_abbr = nwr::libs::abbr::abbr_most(&genus_list, 1, true);
-    let species_abbr = nwr::libs::abbr::abbr_most(&species_list, min_len, true);
-
-    for (i, parts) in all_parts.iter().enumerate() {
-        let fields = &all_fields[i];
+    let abbrs = nwr::libs::abbr::abbr_names(&all_parts, min_len, tight);
+    for (fields, abbr) in all_fields.iter().zip(abbrs) {
         let original_line = fields.join(separator);
-
-        let abbr = if parts.is_normal {
-            let spacer = if tight { "" } else { "_" };
-            let ge = genus_abbr.get(&parts.genus).unwrap_or(&parts.genus);
-            let sp = species_abbr.get(&parts.species).unwrap_or(&parts.species);
-
-            let ge_sp = if parts.species.is_empty() {
-                ge.clone()
-            } else {
-                format!("{ge}{spacer}{sp}")
-            };
-
-            if parts.strain.is_empty() {
-                ge_sp
-            } else {
-                format!("{}_{}", ge_sp, parts.strain)
-            }
-        } else {
-            parts.strain.clone()
-        };
-
         writer.write_fmt(format_args!("{original_line}\t{abbr}\n"))?;
     }
     writer.flush()?;
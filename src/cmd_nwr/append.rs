@@ -17,6 +17,8 @@ pub fn make_subcommand() -> Command {
             "Input TSV file(s) to process. Use 'stdin' for standard input",
         ))
         .arg(args::dir_arg())
+        .arg(args::taxonomy_arg())
+        .arg(args::within_arg())
         .arg(args::rank_arg())
         .arg(args::column_arg())
         .arg(
@@ -25,6 +27,12 @@ pub fn make_subcommand() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Also append taxon IDs for each rank"),
         )
+        .arg(args::gc_arg())
+        .arg(args::host_arg())
+        .arg(args::type_material_arg())
+        .arg(args::resolved_arg(
+            "Append the resolved tax ID of each input term before other fields",
+        ))
         .arg(
             Arg::new("strict")
                 .long("strict")
@@ -39,6 +47,10 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     nwr::libs::io::init_logger();
 
     let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let taxonomy = args
+        .get_one::<String>("taxonomy")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'taxonomy' argument"))?;
+    let within = args.get_one::<String>("within").map(String::as_str);
 
     let column: usize = *args
         .get_one("column")
@@ -60,10 +72,14 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?;
     let is_id = args.get_flag("id");
     let is_strict = args.get_flag("strict");
+    let is_gc = args.get_flag("gc");
+    let is_host = args.get_flag("host");
+    let is_type_material = args.get_flag("type_material");
+    let is_resolved = args.get_flag("resolved");
 
     let mut writer = nwr::libs::io::writer(outfile)?;
 
-    let conn = nwr::connect_txdb(&nwrdir)?;
+    let conn = nwr::connect_taxonomy(&nwrdir, taxonomy)?;
 
     // Cache repeated lookups so that input files with duplicate terms don't
     // trigger redundant SQL queries. Failed lookups are also cached so that
@@ -74,6 +90,8 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     let mut lineage_failed: HashSet<i64> = HashSet::new();
     let mut taxon_cache: HashMap<i64, nwr::Taxon> = HashMap::new();
     let mut taxon_failed: HashSet<i64> = HashSet::new();
+    let mut host_cache: HashMap<i64, String> = HashMap::new();
+    let mut type_material_cache: HashMap<i64, String> = HashMap::new();
 
     for infile in &infiles {
         let reader = nwr::libs::io::reader(infile)?;
@@ -90,6 +108,9 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
                     .split('\t')
                     .map(std::string::ToString::to_string)
                     .collect();
+                if is_resolved {
+                    fields.push("resolved_id".to_string());
+                }
                 if ranks.is_empty() {
                     fields.push("sci_name".to_string());
                     if is_id {
@@ -103,6 +124,16 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
                         }
                     }
                 }
+                if is_gc {
+                    fields.push("gc_id".to_string());
+                    fields.push("mgc_id".to_string());
+                }
+                if is_host {
+                    fields.push("host".to_string());
+                }
+                if is_type_material {
+                    fields.push("type_material".to_string());
+                }
                 let new_line: String = fields.join("\t");
                 writer.write_fmt(format_args!("{new_line}\n"))?;
                 continue;
@@ -135,26 +166,32 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
             }
             let id = match term_cache.get(term.as_str()) {
                 Some(&id) => id,
-                None => match nwr::term_to_tax_id(&conn, term) {
-                    Ok(x) => {
-                        term_cache.insert(term.clone(), x);
-                        x
-                    }
-                    Err(err) => {
-                        if is_strict {
-                            anyhow::bail!(
-                                "{}:{}: Error converting term '{term}': {err}",
-                                infile,
-                                line_idx + 1
-                            );
+                None => {
+                    match nwr::term_to_tax_id(&conn, &nwr::with_ancestor(term, within)) {
+                        Ok(x) => {
+                            term_cache.insert(term.clone(), x);
+                            x
+                        }
+                        Err(err) => {
+                            if is_strict {
+                                anyhow::bail!(
+                                    "{}:{}: Error converting term '{term}': {err}",
+                                    infile,
+                                    line_idx + 1
+                                );
+                            }
+                            warn!("Error converting term '{term}': {err}");
+                            term_failed.insert(term.clone());
+                            continue 'line;
                         }
-                        warn!("Error converting term '{term}': {err}");
-                        term_failed.insert(term.clone());
-                        continue 'line;
                     }
-                },
+                }
             };
 
+            if is_resolved {
+                fields.push(id.to_string());
+            }
+
             if ranks.is_empty() {
                 if taxon_failed.contains(&id) {
                     if is_strict {
@@ -197,6 +234,10 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
                 if is_id {
                     fields.push(id.to_string());
                 }
+                if is_gc {
+                    fields.push(node.gc_id.to_string());
+                    fields.push(node.mgc_id.to_string());
+                }
             } else {
                 if lineage_failed.contains(&id) {
                     if is_strict {
@@ -238,6 +279,29 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
                         fields.push(tax_id.to_string());
                     }
                 }
+                if is_gc {
+                    // The lineage ends with the input taxon itself.
+                    let (gc_id, mgc_id) = lineage
+                        .last()
+                        .map_or((0, 0), |node| (node.gc_id, node.mgc_id));
+                    fields.push(gc_id.to_string());
+                    fields.push(mgc_id.to_string());
+                }
+            }
+
+            if is_host {
+                if let Entry::Vacant(e) = host_cache.entry(id) {
+                    e.insert(nwr::get_hosts(&conn, id)?.join(","));
+                }
+                fields.push(host_cache[&id].clone());
+            }
+            if is_type_material {
+                if let Entry::Vacant(e) = type_material_cache.entry(id) {
+                    e.insert(nwr::format_type_material(&nwr::get_type_material(
+                        &conn, id,
+                    )?));
+                }
+                fields.push(type_material_cache[&id].clone());
             }
 
             let new_line: String = fields.join("\t");
@@ -3,19 +3,22 @@ use clap::{Arg, ArgAction, ArgMatches, Command};
 use log::{debug, info, warn};
 use regex::Regex;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 
-/// Organism names matching this regex are considered incompetent and skipped.
+/// Organism names matching this regex are considered incompetent and skipped,
+/// unless `--keep incompetent`.
 static RE_INCOMPETENT: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r"(?xi)\b(uncultured|unidentified|bacterium|archaeon|metagenome)\b")
         .unwrap()
 });
 
-/// Organism names matching this regex are considered viral and skipped.
+/// Organism names matching this regex are considered viral and skipped,
+/// unless `--keep virus`.
 static RE_VIRUS: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"(?xi)(virus|phage)\b").unwrap());
 
@@ -45,145 +48,494 @@ CREATE TABLE ar (
     family_id          INTEGER
 );
 
+DROP TABLE IF EXISTS skipped;
+
+CREATE TABLE skipped (
+    source             VARCHAR (10),
+    line               INTEGER,
+    assembly_accession VARCHAR (50),
+    tax_id             INTEGER,
+    organism_name      VARCHAR (200),
+    reason             VARCHAR (200)
+);
+
 ";
 
-/// Column indices in NCBI `assembly_summary_refseq.txt` / `assembly_summary_genbank.txt`.
-const COL_ASSEMBLY_ACCESSION: usize = 0;
-const COL_BIOPROJECT: usize = 1;
-const COL_BIOSAMPLE: usize = 2;
-const COL_REFSEQ_CATEGORY: usize = 4;
-const COL_TAX_ID: usize = 5;
-const COL_ORGANISM_NAME: usize = 7;
-const COL_INFRASPECIFIC_NAME: usize = 8;
-const COL_ASSEMBLY_LEVEL: usize = 11;
-const COL_GENOME_REP: usize = 13;
-const COL_SEQ_REL_DATE: usize = 14;
-const COL_ASM_NAME: usize = 15;
-const COL_GBRS_PAIRED_ASM: usize = 17;
-const COL_FTP_PATH: usize = 19;
+/// DDL of the extra parts of `ar_all.sqlite`, which holds both summaries.
+static DDL_ALL: &str = r"
+ALTER TABLE ar ADD COLUMN source VARCHAR (10);
 
-/// Create clap subcommand arguments.
-#[must_use]
-pub fn make_subcommand() -> Command {
-    Command::new("ardb")
-        .about("Initializes the assembly database")
-        .after_help(include_str!("../../docs/help/ardb.md"))
-        .arg(args::dir_arg())
-        .arg(
-            Arg::new("genbank")
-                .long("genbank")
-                .action(ArgAction::SetTrue)
-                .help("Create the GenBank assembly database"),
-        )
+DROP TABLE IF EXISTS pair;
+
+CREATE TABLE pair (
+    refseq_accession  VARCHAR (50),
+    genbank_accession VARCHAR (50),
+    paired_asm_comp   VARCHAR (50)
+);
+
+";
+
+/// Indexes of `ar_all.sqlite` besides `AR_INDEXES`.
+pub const ALL_INDEXES: [&str; 4] = [
+    "idx_ar_source",
+    "idx_ar_assembly_accession",
+    "idx_pair_refseq_accession",
+    "idx_pair_genbank_accession",
+];
+
+/// Indexes of `ar` checked before the new database replaces the old one.
+pub const AR_INDEXES: [&str; 7] = [
+    "idx_ar_tax_id",
+    "idx_ar_species",
+    "idx_ar_species_id",
+    "idx_ar_genus",
+    "idx_ar_genus_id",
+    "idx_ar_family",
+    "idx_ar_family_id",
+];
+
+/// Summary columns stored in the first columns of `ar`: the header name and
+/// the column name. The other summary columns follow the lineage columns,
+/// under their header names.
+const CORE_COLUMNS: [(&str, &str); 13] = [
+    ("taxid", "tax_id"),
+    ("organism_name", "organism_name"),
+    ("infraspecific_name", "infraspecific_name"),
+    ("bioproject", "bioproject"),
+    ("biosample", "biosample"),
+    ("assembly_accession", "assembly_accession"),
+    ("refseq_category", "refseq_category"),
+    ("assembly_level", "assembly_level"),
+    ("genome_rep", "genome_rep"),
+    ("seq_rel_date", "seq_rel_date"),
+    ("asm_name", "asm_name"),
+    ("gbrs_paired_asm", "gbrs_paired_asm"),
+    ("ftp_path", "ftp_path"),
+];
+
+/// Columns of `ar` appended from the taxonomy.
+const LINEAGE_COLUMNS: [&str; 6] = [
+    "species",
+    "species_id",
+    "genus",
+    "genus_id",
+    "family",
+    "family_id",
+];
+
+/// The header of summaries written before NCBI added the quality columns,
+/// used when a summary has no header line.
+const LEGACY_HEADER: [&str; 23] = [
+    "assembly_accession",
+    "bioproject",
+    "biosample",
+    "wgs_master",
+    "refseq_category",
+    "taxid",
+    "species_taxid",
+    "organism_name",
+    "infraspecific_name",
+    "isolate",
+    "version_status",
+    "assembly_level",
+    "release_type",
+    "genome_rep",
+    "seq_rel_date",
+    "asm_name",
+    "submitter",
+    "gbrs_paired_asm",
+    "paired_asm_comp",
+    "ftp_path",
+    "excluded_from_refseq",
+    "relation_to_type_material",
+    "asm_not_live_date",
+];
+
+/// SQL type of a summary column; values that don't parse are stored as NULL.
+fn column_type(name: &str) -> &'static str {
+    match name {
+        "taxid"
+        | "species_taxid"
+        | "genome_size"
+        | "genome_size_ungapped"
+        | "replicon_count"
+        | "scaffold_count"
+        | "contig_count"
+        | "scaffold_n50"
+        | "contig_n50"
+        | "total_gene_count"
+        | "protein_coding_gene_count"
+        | "non_coding_gene_count" => "INTEGER",
+        "gc_percent" | "checkm_completeness" | "checkm_contamination" => "REAL",
+        "seq_rel_date" | "annotation_date" | "asm_not_live_date" => "DATE",
+        _ => "TEXT",
+    }
 }
 
-/// Command implementation.
-pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
-    nwr::libs::io::init_logger();
+/// Where the columns of `ar` are in the lines of a summary.
+#[derive(Debug)]
+struct Layout {
+    /// Field index of each core column, in `CORE_COLUMNS` order
+    core: Vec<usize>,
+    /// Column name, SQL type and field index of the other summary columns
+    extra: Vec<(String, &'static str, usize)>,
+    /// Lines with fewer fields are skipped
+    min_fields: usize,
+}
 
-    let nwrdir = nwr::get_nwr_dir(args, "dir")?;
-    let is_genbank = args.get_flag("genbank");
-    let tx_conn = nwr::connect_txdb(&nwrdir)?;
+impl Layout {
+    /// Map the columns of a summary by the names in its header.
+    fn new(header: &[String]) -> anyhow::Result<Self> {
+        let index_of: HashMap<&str, usize> = header
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), i))
+            .collect();
 
-    let file = if is_genbank {
-        nwrdir.join("ar_genbank.sqlite")
-    } else {
-        nwrdir.join("ar_refseq.sqlite")
-    };
-    if file.exists() {
-        std::fs::remove_file(&file)?;
+        let mut core = vec![];
+        for (name, _) in CORE_COLUMNS {
+            let index = index_of.get(name).ok_or_else(|| {
+                anyhow::anyhow!("Column {name} is missing from the summary header")
+            })?;
+            core.push(*index);
+        }
+
+        let mut extra = vec![];
+        for (i, name) in header.iter().enumerate() {
+            if CORE_COLUMNS.iter().any(|(core, _)| core == name) {
+                continue;
+            }
+            if name.is_empty()
+                || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                warn!("Skipping summary column '{name}': not a valid column name");
+                continue;
+            }
+            extra.push((name.clone(), column_type(name), i));
+        }
+
+        let min_fields = core.iter().max().map_or(0, |i| i + 1);
+        Ok(Self {
+            core,
+            extra,
+            min_fields,
+        })
     }
 
-    info!("==> Opening database");
-    let conn = rusqlite::Connection::open(file)?;
-    nwr::libs::db::apply_import_pragmas(&conn)?;
+    /// The field of core column `i`.
+    fn field<'a>(&self, fields: &[&'a str], i: usize) -> &'a str {
+        fields[self.core[i]]
+    }
+}
 
-    info!("==> Create tables");
-    conn.execute_batch(DDL_AR)?;
+/// Column names of the summary header line, `#assembly_accession` and the
+/// others separated by tabs, or `None` for other comment lines.
+fn parse_header(line: &str) -> Option<Vec<String>> {
+    let line = line.trim_start_matches('#').trim_start();
+    if !line.starts_with("assembly_accession\t") {
+        return None;
+    }
+    Some(
+        line.split('\t')
+            .map(|name| name.trim().to_string())
+            .collect(),
+    )
+}
 
-    info!("==> Loading...");
-    let summary_file = if is_genbank {
-        File::open(nwrdir.join("assembly_summary_genbank.txt"))?
-    } else {
-        File::open(nwrdir.join("assembly_summary_refseq.txt"))?
-    };
-    let rdr = BufReader::new(summary_file);
-
-    let mut stmt = conn.prepare(
-        "INSERT INTO ar(
-            tax_id, organism_name, infraspecific_name, bioproject, biosample, assembly_accession, refseq_category,
-            assembly_level, genome_rep, seq_rel_date, asm_name, gbrs_paired_asm, ftp_path,
-            species, species_id, genus, genus_id, family, family_id
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+/// Convert a summary field to a value of the column's type.
+fn to_value(field: &str, sql_type: &str) -> rusqlite::types::Value {
+    use rusqlite::types::Value;
+    let is_missing = field.is_empty() || field.eq_ignore_ascii_case("na");
+    match sql_type {
+        "INTEGER" => field.parse::<i64>().map_or(Value::Null, Value::Integer),
+        "REAL" => field.parse::<f64>().map_or(Value::Null, Value::Real),
+        "DATE" if is_missing => Value::Null,
+        "DATE" => Value::Text(field.replace('/', "-")),
+        _ => Value::Text(field.to_string()),
+    }
+}
+
+/// Which rows of a summary are loaded, from the command line.
+struct Filters {
+    keep_incompetent: bool,
+    keep_virus: bool,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    divisions: Vec<String>,
+}
+
+impl Filters {
+    fn new(args: &ArgMatches) -> anyhow::Result<Self> {
+        let keep: Vec<&String> = args
+            .get_many::<String>("keep")
+            .map(Iterator::collect)
+            .unwrap_or_default();
+        let regexes = |arg: &str| -> anyhow::Result<Vec<Regex>> {
+            args.get_many::<String>(arg)
+                .into_iter()
+                .flatten()
+                .map(|pattern| {
+                    regex::RegexBuilder::new(pattern)
+                        .case_insensitive(true)
+                        .build()
+                        .map_err(|e| {
+                            anyhow::anyhow!("Invalid --{arg} regex '{pattern}': {e}")
+                        })
+                })
+                .collect()
+        };
+        Ok(Self {
+            keep_incompetent: keep.iter().any(|k| *k == "incompetent" || *k == "all"),
+            keep_virus: keep.iter().any(|k| *k == "virus" || *k == "all"),
+            include: regexes("include")?,
+            exclude: regexes("exclude")?,
+            divisions: args
+                .get_many::<String>("division")
+                .map(|divisions| divisions.cloned().collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Why a row is skipped by its organism name, or `None` to check its taxon.
+    fn skip_name(&self, organism_name: &str) -> Option<String> {
+        if !self.keep_incompetent && RE_INCOMPETENT.is_match(organism_name) {
+            return Some("incompetent".to_string());
+        }
+        if !self.keep_virus && RE_VIRUS.is_match(organism_name) {
+            return Some("virus".to_string());
+        }
+        if let Some(re) = self.exclude.iter().find(|re| re.is_match(organism_name)) {
+            return Some(format!("exclude: {}", re.as_str()));
+        }
+        if !self.include.is_empty()
+            && !self.include.iter().any(|re| re.is_match(organism_name))
+        {
+            return Some("include".to_string());
+        }
+        None
+    }
+
+    /// Why a row is skipped by the division of its taxon; `None` keeps it.
+    /// Taxa missing from the taxonomy have no division.
+    fn skip_division(&self, division: Option<&str>) -> Option<String> {
+        if self.divisions.is_empty() {
+            return None;
+        }
+        match division {
+            Some(division)
+                if self
+                    .divisions
+                    .iter()
+                    .any(|d| d.eq_ignore_ascii_case(division)) =>
+            {
+                None
+            }
+            Some(division) => Some(format!("division: {division}")),
+            None => Some("division: NA".to_string()),
+        }
+    }
+}
+
+/// Add the other summary columns of `layout` to `ar` and prepare the insert
+/// of all columns.
+fn prepare_insert<'conn>(
+    conn: &'conn rusqlite::Connection,
+    layout: &Layout,
+    is_all: bool,
+) -> anyhow::Result<rusqlite::Statement<'conn>> {
+    let mut columns: Vec<String> = CORE_COLUMNS
+        .iter()
+        .map(|(_, column)| (*column).to_string())
+        .collect();
+    columns.extend(LINEAGE_COLUMNS.iter().map(|column| (*column).to_string()));
+    if is_all {
+        columns.push("source".to_string());
+    }
+
+    // The second summary of `ar_all.sqlite` shares most of its columns
+    let existing: Vec<String> = conn
+        .prepare("SELECT name FROM pragma_table_info('ar')")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    for (column, sql_type, _) in &layout.extra {
+        if !existing.contains(column) {
+            conn.execute(
+                &format!("ALTER TABLE ar ADD COLUMN \"{column}\" {sql_type}"),
+                [],
+            )?;
+        }
+        columns.push(format!("\"{column}\""));
+    }
+
+    let marks: Vec<String> = (1..=columns.len()).map(|i| format!("?{i}")).collect();
+    Ok(conn.prepare(&format!(
+        "INSERT INTO ar({}) VALUES ({})",
+        columns.join(", "),
+        marks.join(", ")
+    ))?)
+}
+
+/// Fill `pair` from `gbrs_paired_asm` of both sources, and index the columns
+/// used to pick one of each pair.
+///
+/// A pair is listed once even when only one side is in `ar`, e.g. a GenBank
+/// assembly whose RefSeq copy was suppressed.
+fn pair_assemblies(conn: &rusqlite::Connection) -> anyhow::Result<()> {
+    let has_comp: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('ar') WHERE name = 'paired_asm_comp'",
+        [],
+        |row| row.get(0),
+    )?;
+    let comp = if has_comp { "paired_asm_comp" } else { "NULL" };
+    conn.execute(
+        &format!(
+            "
+            INSERT INTO pair(refseq_accession, genbank_accession, paired_asm_comp)
+            SELECT refseq_accession, genbank_accession, MAX(paired_asm_comp)
+            FROM (
+                SELECT assembly_accession AS refseq_accession,
+                    gbrs_paired_asm AS genbank_accession,
+                    {comp} AS paired_asm_comp
+                FROM ar
+                WHERE source = 'refseq' AND gbrs_paired_asm LIKE 'GCA\\_%' ESCAPE '\\'
+                UNION ALL
+                SELECT gbrs_paired_asm, assembly_accession, {comp}
+                FROM ar
+                WHERE source = 'genbank' AND gbrs_paired_asm LIKE 'GCF\\_%' ESCAPE '\\'
+            )
+            GROUP BY refseq_accession, genbank_accession
+            "
+        ),
+        [],
     )?;
 
+    conn.execute("CREATE INDEX idx_ar_source ON ar(source);", [])?;
+    conn.execute(
+        "CREATE INDEX idx_ar_assembly_accession ON ar(assembly_accession);",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX idx_pair_refseq_accession ON pair(refseq_accession);",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX idx_pair_genbank_accession ON pair(genbank_accession);",
+        [],
+    )?;
+    Ok(())
+}
+
+/// A summary to load.
+struct Source<'a> {
+    /// `refseq` or `genbank`
+    name: &'a str,
+    path: &'a Path,
+    /// Loading into `ar_all.sqlite`, with the `source` column
+    is_all: bool,
+}
+
+/// Load the rows of a summary that pass `filters` into `ar`, and the others
+/// into `skipped`.
+fn load_summary(
+    conn: &rusqlite::Connection,
+    tx_conn: &rusqlite::Connection,
+    source: &Source,
+    filters: &Filters,
+    lineage_cache: &mut HashMap<i64, Vec<nwr::Taxon>>,
+) -> anyhow::Result<()> {
+    let summary_path = source.path;
+    let rdr = BufReader::new(File::open(summary_path)?);
+
     // Intentionally use explicit SQL BEGIN/COMMIT rather than rusqlite::Transaction.
     conn.execute_batch("BEGIN;")?;
-    let mut lineage_cache: HashMap<i64, Vec<nwr::Taxon>> = HashMap::new();
+    let mut header: Option<Vec<String>> = None;
+    let mut loader: Option<(Layout, rusqlite::Statement)> = None;
     let mut inserted: usize = 0;
+    let mut skipped: BTreeMap<String, usize> = BTreeMap::new();
+    let mut skip_stmt = conn.prepare(
+        "INSERT INTO skipped(source, line, assembly_accession, tax_id, organism_name, reason)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    let mut skip = |line_num: usize,
+                    accession: &str,
+                    tax_id: Option<i64>,
+                    organism_name: &str,
+                    reason: String|
+     -> anyhow::Result<()> {
+        debug!("Skipping line {line_num}: {reason} '{organism_name}'");
+        skip_stmt.execute(rusqlite::params![
+            source.name,
+            line_num,
+            accession,
+            tax_id,
+            organism_name,
+            reason
+        ])?;
+        let reason = reason.split(':').next().unwrap_or_default().to_string();
+        *skipped.entry(reason).or_default() += 1;
+        Ok(())
+    };
     for (i, line) in rdr.lines().enumerate() {
         let line_num = i + 1;
         let line = line?;
         if line.starts_with('#') {
+            if let Some(names) = parse_header(&line) {
+                header = Some(names);
+            }
             continue;
         }
         if line.trim().is_empty() {
             continue;
         }
 
+        // The columns are known once the header is read
+        let (layout, stmt) = match &mut loader {
+            Some(loader) => loader,
+            None => {
+                let names = header.take().unwrap_or_else(|| {
+                    warn!(
+                        "No header line in {}; assuming the 23 legacy columns",
+                        summary_path.display()
+                    );
+                    LEGACY_HEADER
+                        .iter()
+                        .map(|name| (*name).to_string())
+                        .collect()
+                });
+                let layout = Layout::new(&names)?;
+                let stmt = prepare_insert(conn, &layout, source.is_all)?;
+                loader.insert((layout, stmt))
+            }
+        };
+
         let fields: Vec<&str> = line.split('\t').collect();
-        if fields.len() <= COL_FTP_PATH {
-            debug!(
-                "Skipping line {}: insufficient fields ({} <= {})",
+        if fields.len() < layout.min_fields {
+            skip(
                 line_num,
-                fields.len(),
-                COL_FTP_PATH
-            );
+                fields[0],
+                None,
+                "",
+                format!("fields: {} < {}", fields.len(), layout.min_fields),
+            )?;
             continue;
         }
 
-        // Field accesses rely on the `fields.len() <= COL_FTP_PATH` skip above,
-        // which guarantees every COL_* index is in bounds.
-        let tax_id = fields[COL_TAX_ID]
+        // Field accesses of core columns rely on the `min_fields` skip above
+        let tax_id = layout
+            .field(&fields, 0)
             .parse::<i64>()
             .map_err(|e| anyhow::anyhow!("Invalid tax_id at line {line_num}: {e}"))?;
-        let organism_name = fields[COL_ORGANISM_NAME];
-        let infraspecific_name = fields[COL_INFRASPECIFIC_NAME];
-        let bioproject = fields[COL_BIOPROJECT];
-        let biosample = fields[COL_BIOSAMPLE];
-        let assembly_accession = fields[COL_ASSEMBLY_ACCESSION];
-        let refseq_category = fields[COL_REFSEQ_CATEGORY];
-        let assembly_level = fields[COL_ASSEMBLY_LEVEL];
-        let genome_rep = fields[COL_GENOME_REP];
-        let seq_rel_date = fields[COL_SEQ_REL_DATE];
-        let asm_name = fields[COL_ASM_NAME];
-        let gbrs_paired_asm = fields[COL_GBRS_PAIRED_ASM];
-        let ftp_path = fields[COL_FTP_PATH];
-
-        // clean NA/na
-        let infraspecific_name = if infraspecific_name.eq_ignore_ascii_case("NA") {
-            ""
-        } else {
-            infraspecific_name
-        };
+        let organism_name = layout.field(&fields, 1);
+        let accession = layout.field(&fields, 5);
 
-        // Skip incompetent strains
-        if RE_INCOMPETENT.is_match(organism_name) {
-            debug!(
-                "Skipping line {line_num}: incompetent organism name '{organism_name}'"
-            );
-            continue;
-        }
-
-        // Skip viral strains
-        if RE_VIRUS.is_match(organism_name) {
-            debug!("Skipping line {line_num}: viral organism name '{organism_name}'");
+        if let Some(reason) = filters.skip_name(organism_name) {
+            skip(line_num, accession, Some(tax_id), organism_name, reason)?;
             continue;
         }
 
         // lineage (cached to avoid repeated SQL queries for shared tax_ids)
         let lineage = lineage_cache.entry(tax_id).or_insert_with(|| {
-            match nwr::get_lineage(&tx_conn, tax_id) {
+            match nwr::get_lineage(tx_conn, tax_id) {
                 Err(err) => {
                     warn!("Errors on get_lineage({tax_id}): {err}");
                     // Use a clearly-marked missing taxon so that find_rank
@@ -202,37 +554,167 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
                 Ok(x) => x,
             }
         });
+        let division = lineage
+            .last()
+            .filter(|taxon| taxon.tax_id != 0)
+            .map(|taxon| taxon.division.as_str());
+        if let Some(reason) = filters.skip_division(division) {
+            skip(line_num, accession, Some(tax_id), organism_name, reason)?;
+            continue;
+        }
         let (species_id, species) = nwr::find_rank(lineage, "species");
         let (genus_id, genus) = nwr::find_rank(lineage, "genus");
         let (family_id, family) = nwr::find_rank(lineage, "family");
 
-        stmt.execute(rusqlite::params![
-            tax_id,
-            organism_name,
-            infraspecific_name,
-            bioproject,
-            biosample,
-            assembly_accession,
-            refseq_category,
-            assembly_level,
-            genome_rep,
-            seq_rel_date.replace('/', "-"),
-            asm_name,
-            gbrs_paired_asm,
-            ftp_path,
-            species,
-            species_id,
-            genus,
-            genus_id,
-            family,
-            family_id,
-        ])?;
+        use rusqlite::types::Value;
+        let mut values: Vec<Value> = vec![Value::Integer(tax_id)];
+        for (i, (name, _)) in CORE_COLUMNS.iter().enumerate().skip(1) {
+            let field = layout.field(&fields, i);
+            let value = match *name {
+                // clean NA/na
+                "infraspecific_name" if field.eq_ignore_ascii_case("NA") => {
+                    Value::Text(String::new())
+                }
+                "seq_rel_date" => Value::Text(field.replace('/', "-")),
+                _ => Value::Text(field.to_string()),
+            };
+            values.push(value);
+        }
+        values.extend([
+            Value::Text(species.to_string()),
+            Value::Integer(species_id),
+            Value::Text(genus.to_string()),
+            Value::Integer(genus_id),
+            Value::Text(family.to_string()),
+            Value::Integer(family_id),
+        ]);
+        if source.is_all {
+            values.push(Value::Text(source.name.to_string()));
+        }
+        for (_, sql_type, index) in &layout.extra {
+            let field = fields.get(*index).copied().unwrap_or_default();
+            values.push(to_value(field, sql_type));
+        }
+        stmt.execute(rusqlite::params_from_iter(values))?;
 
         inserted += 1;
         nwr::libs::io::progress_dot(inserted)?;
     }
     eprintln!();
     conn.execute_batch("COMMIT;")?;
+    drop(loader);
+    drop(skip_stmt);
+    for (reason, count) in &skipped {
+        info!("Skipped {count} rows: {reason}");
+    }
+
+    Ok(())
+}
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("ardb")
+        .about("Initializes the assembly database")
+        .after_help(include_str!("../../docs/help/ardb.md"))
+        .arg(args::dir_arg())
+        .arg(
+            Arg::new("genbank")
+                .long("genbank")
+                .action(ArgAction::SetTrue)
+                .help("Create the GenBank assembly database"),
+        )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("genbank")
+                .help("Create one database of both RefSeq and GenBank, ar_all.sqlite"),
+        )
+        .arg(
+            Arg::new("keep")
+                .long("keep")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(["incompetent", "virus", "all"])
+                .help("Keep organisms the built-in filters skip"),
+        )
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .help("Keep only organism names matching one of these regexes"),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .help("Skip organism names matching this regex"),
+        )
+        .arg(
+            Arg::new("division")
+                .long("division")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .help(
+                    "Keep only taxa of these divisions, e.g. Bacteria, Viruses, Phages",
+                ),
+        )
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
+    let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let _lock = nwr::libs::lock::lock_dir(&nwrdir)?;
+    let is_all = args.get_flag("all");
+    let sources: &[&str] = if is_all {
+        &["refseq", "genbank"]
+    } else if args.get_flag("genbank") {
+        &["genbank"]
+    } else {
+        &["refseq"]
+    };
+    let filters = Filters::new(args)?;
+    let tx_conn = nwr::connect_txdb(&nwrdir)?;
+
+    let file = if is_all {
+        nwrdir.join("ar_all.sqlite")
+    } else {
+        nwrdir.join(format!("ar_{}.sqlite", sources[0]))
+    };
+    let summary_paths: Vec<PathBuf> = sources
+        .iter()
+        .map(|source| nwrdir.join(format!("assembly_summary_{source}.txt")))
+        .collect();
+    for path in &summary_paths {
+        if !path.exists() {
+            anyhow::bail!("{} doesn't exist; run `nwr download`", path.display());
+        }
+    }
+    info!("==> Opening database");
+    let staged = nwr::libs::db::StagedDb::create(&file)?;
+    let conn = staged.conn();
+
+    info!("==> Create tables");
+    conn.execute_batch(DDL_AR)?;
+    if is_all {
+        conn.execute_batch(DDL_ALL)?;
+    }
+
+    let mut lineage_cache: HashMap<i64, Vec<nwr::Taxon>> = HashMap::new();
+    for (source, summary_path) in sources.iter().zip(&summary_paths) {
+        info!("==> Loading {}", summary_path.display());
+        let source = Source {
+            name: source,
+            path: summary_path,
+            is_all,
+        };
+        load_summary(conn, &tx_conn, &source, &filters, &mut lineage_cache)?;
+    }
 
     debug!("Creating indexes for ar");
     conn.execute("CREATE INDEX idx_ar_tax_id ON ar(tax_id);", [])?;
@@ -243,6 +725,32 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     conn.execute("CREATE INDEX idx_ar_family ON ar(family);", [])?;
     conn.execute("CREATE INDEX idx_ar_family_id ON ar(family_id);", [])?;
 
+    let mut tables = vec!["ar", "skipped"];
+    if is_all {
+        info!("==> Pairing RefSeq and GenBank assemblies");
+        pair_assemblies(conn)?;
+        tables.push("pair");
+    }
+
+    info!("==> Recording sources");
+    let mut meta = nwr::libs::db::Meta::new(nwr::libs::db::AR_SCHEMA_VERSION);
+    for path in &summary_paths {
+        meta.source(path)?;
+    }
+    meta.rows(conn, &tables)?;
+    meta.write(conn)?;
+
+    info!("==> Checking {}", file.display());
+    staged.commit(|conn| {
+        let count = nwr::libs::db::check_rows(conn, "ar")?;
+        debug!("ar: {count} rows");
+        nwr::libs::db::check_indexes(conn, &AR_INDEXES)?;
+        if is_all {
+            nwr::libs::db::check_indexes(conn, &ALL_INDEXES)?;
+        }
+        Ok(())
+    })?;
+
     Ok(())
 }
 
@@ -266,4 +774,47 @@ mod tests {
         assert!(RE_VIRUS.is_match("phage"));
         assert!(RE_VIRUS.is_match("PHAGE"));
     }
+
+    #[test]
+    fn test_parse_header() {
+        let header = parse_header("#assembly_accession\tbioproject\tgroup").unwrap();
+        assert_eq!(header, vec!["assembly_accession", "bioproject", "group"]);
+        assert!(
+            parse_header("#   See ftp://ftp.ncbi.nlm.nih.gov/genomes/README").is_none()
+        );
+    }
+
+    #[test]
+    fn test_layout() {
+        let mut header: Vec<String> =
+            LEGACY_HEADER.iter().map(|s| (*s).to_string()).collect();
+        header.swap(0, 5);
+        header.push("contig_n50".to_string());
+        header.push("bad name".to_string());
+        let layout = Layout::new(&header).unwrap();
+        assert_eq!(layout.core[0], 0);
+        assert_eq!(layout.core[5], 5);
+        assert_eq!(layout.min_fields, 20);
+        assert!(layout
+            .extra
+            .contains(&("contig_n50".to_string(), "INTEGER", 23)));
+        assert!(!layout.extra.iter().any(|(name, _, _)| name == "bad name"));
+
+        header.retain(|name| name != "ftp_path");
+        assert!(Layout::new(&header).is_err());
+    }
+
+    #[test]
+    fn test_to_value() {
+        use rusqlite::types::Value;
+        assert_eq!(to_value("52000", "INTEGER"), Value::Integer(52000));
+        assert_eq!(to_value("na", "INTEGER"), Value::Null);
+        assert_eq!(to_value("50.5", "REAL"), Value::Real(50.5));
+        assert_eq!(
+            to_value("2013/09/26", "DATE"),
+            Value::Text("2013-09-26".to_string())
+        );
+        assert_eq!(to_value("na", "DATE"), Value::Null);
+        assert_eq!(to_value("na", "TEXT"), Value::Text("na".to_string()));
+    }
 }
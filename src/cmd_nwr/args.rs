@@ -81,3 +81,63 @@ pub fn infiles_arg(help: &'static str) -> Arg {
         .num_args(1..)
         .index(1)
 }
+
+/// `--gc` flag: also output genetic code and mitochondrial genetic code IDs.
+#[must_use]
+pub fn gc_arg() -> Arg {
+    Arg::new("gc")
+        .long("gc")
+        .action(ArgAction::SetTrue)
+        .help("Also output genetic codes (translation tables)")
+}
+
+/// `--host` flag: also output potential hosts from `new_taxdump`.
+#[must_use]
+pub fn host_arg() -> Arg {
+    Arg::new("host")
+        .long("host")
+        .action(ArgAction::SetTrue)
+        .help("Also output potential hosts (requires `new_taxdump`)")
+}
+
+/// `--type-material` flag: also output type material from `new_taxdump`.
+#[must_use]
+pub fn type_material_arg() -> Arg {
+    Arg::new("type_material")
+        .long("type-material")
+        .action(ArgAction::SetTrue)
+        .help("Also output type material (requires `new_taxdump`)")
+}
+
+/// `--taxonomy` option selecting the taxonomy backend (defaults to `ncbi`).
+#[must_use]
+pub fn taxonomy_arg() -> Arg {
+    Arg::new("taxonomy")
+        .long("taxonomy")
+        .num_args(1)
+        .value_name("TAXONOMY")
+        .default_value("ncbi")
+        .value_parser(["ncbi", "gtdb"])
+        .help("Taxonomy backend; gtdb requires `nwr txdb --gtdb`")
+}
+
+/// `--within` option: an ancestor term that disambiguates homonymous names,
+/// the same as writing every name as `Name@Ancestor`.
+#[must_use]
+pub fn within_arg() -> Arg {
+    Arg::new("within")
+        .long("within")
+        .num_args(1)
+        .value_name("TERM")
+        .help("Resolve names only to taxa under this ancestor term")
+}
+
+/// `--resolved` flag: also output the current tax ID that a (possibly merged)
+/// input term resolves to.
+#[must_use]
+pub fn resolved_arg(help: &'static str) -> Arg {
+    Arg::new("resolved")
+        .long("resolved")
+        .action(ArgAction::SetTrue)
+        .help(help)
+}
@@ -0,0 +1,684 @@
+use super::args;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use log::warn;
+
+use nwr::libs::template::level_code;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+
+/// Assembly levels, from the most to the least contiguous.
+const LEVELS: [&str; 4] = ["Complete Genome", "Chromosome", "Scaffold", "Contig"];
+
+/// Keys of `--by`, to rank the assemblies of a taxon; the default order.
+const RANK_KEYS: [&str; 6] = [
+    "category",
+    "level",
+    "contig_n50",
+    "date",
+    "scaffold_n50",
+    "type",
+];
+
+/// Operators of `--filter`, two-character ones first so they match whole.
+const OPERATORS: [&str; 6] = [">=", "<=", "!=", "=", ">", "<"];
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("assembly")
+        .about("Selects assemblies under taxa and writes an .assembly.tsv")
+        .after_help(include_str!("../../docs/help/assembly.md"))
+        .arg(args::terms_arg("The ancestor(s)"))
+        .arg(args::dir_arg())
+        .arg(args::within_arg())
+        .arg(
+            Arg::new("source")
+                .long("source")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(["refseq", "genbank", "all"])
+                .help("Assembly databases to read. Default: refseq"),
+        )
+        .arg(
+            Arg::new("prefer")
+                .long("prefer")
+                .num_args(1)
+                .default_value("refseq")
+                .value_parser(["refseq", "genbank"])
+                .help(
+                    "Of a RefSeq and GenBank pair, keep the assembly from this source",
+                ),
+        )
+        .arg(
+            Arg::new("level")
+                .long("level")
+                .short('l')
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(LEVELS)
+                .help("Keep these assembly levels"),
+        )
+        .arg(
+            Arg::new("category")
+                .long("category")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(["reference genome", "representative genome", "na"])
+                .help("Keep these RefSeq categories"),
+        )
+        .arg(
+            Arg::new("rep")
+                .long("rep")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(["Full", "Partial"])
+                .help("Keep these genome representations"),
+        )
+        .arg(
+            Arg::new("from")
+                .long("from")
+                .num_args(1)
+                .value_parser(parse_date)
+                .help("Released on or after this date, YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::new("to")
+                .long("to")
+                .num_args(1)
+                .value_parser(parse_date)
+                .help("Released on or before this date, YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::new("filter")
+                .long("filter")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(parse_filter)
+                .help(
+                    "Keep assemblies passing COLUMN OP VALUE, e.g. 'contig_n50>=50000'",
+                ),
+        )
+        .arg(
+            Arg::new("column")
+                .long("column")
+                .short('c')
+                .num_args(1)
+                .action(ArgAction::Append)
+                .help("Other columns of `ar` to write after the standard ones"),
+        )
+        .arg(
+            Arg::new("per")
+                .long("per")
+                .num_args(1)
+                .value_parser(["species", "genus", "family"])
+                .help("Keep the best assemblies of each taxon at this rank"),
+        )
+        .arg(
+            Arg::new("top")
+                .long("top")
+                .num_args(1)
+                .default_value("1")
+                .value_parser(clap::value_parser!(usize))
+                .help("Number of assemblies kept of each taxon with --per"),
+        )
+        .arg(
+            Arg::new("by")
+                .long("by")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(RANK_KEYS)
+                .help("Rank by these keys, in order. Default: category, level, contig_n50, date"),
+        )
+        .arg(
+            Arg::new("min")
+                .long("min")
+                .short('m')
+                .num_args(1)
+                .default_value("3")
+                .value_parser(clap::value_parser!(usize))
+                .help("Minimal length for species abbreviation"),
+        )
+        .arg(args::outfile_arg())
+}
+
+/// An assembly selected from `ar`.
+struct Row {
+    organism_name: String,
+    infraspecific_name: String,
+    biosample: String,
+    accession: String,
+    paired: String,
+    /// `refseq` or `genbank`
+    source: String,
+    ftp_path: String,
+    species: String,
+    genus: String,
+    family: String,
+    level: String,
+    category: String,
+    date: String,
+    type_material: String,
+    contig_n50: Option<i64>,
+    scaffold_n50: Option<i64>,
+    /// Values of `--column`
+    extra: Vec<String>,
+    /// Why it was kept by `--per`
+    reason: String,
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
+    let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let within = args.get_one::<String>("within").map(String::as_str);
+    let terms: Vec<String> = args
+        .get_many::<String>("terms")
+        .ok_or_else(|| anyhow::anyhow!("No terms provided"))?
+        .map(|term| nwr::with_ancestor(term, within))
+        .collect();
+    let sources: Vec<&String> = args
+        .get_many::<String>("source")
+        .map(Iterator::collect)
+        .unwrap_or_default();
+    let columns: Vec<String> = args
+        .get_many::<String>("column")
+        .map(|columns| columns.cloned().collect())
+        .unwrap_or_default();
+    let filters: Vec<(String, String, String)> = args
+        .get_many::<(String, String, String)>("filter")
+        .map(|filters| filters.cloned().collect())
+        .unwrap_or_default();
+    if sources.len() > 1 && sources.iter().any(|s| *s == "all") {
+        anyhow::bail!("--source all already holds RefSeq and GenBank");
+    }
+    let prefer = args
+        .get_one::<String>("prefer")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'prefer' argument"))?;
+    let min_len = *args
+        .get_one::<usize>("min")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'min' argument"))?;
+
+    // Filters on `ar`, as SQL conditions and their parameters
+    let mut conditions: Vec<String> = vec![];
+    let mut params: Vec<String> = vec![];
+    for (arg, column) in [
+        ("level", "assembly_level"),
+        ("category", "refseq_category"),
+        ("rep", "genome_rep"),
+    ] {
+        if let Some(values) = args.get_many::<String>(arg) {
+            let values: Vec<String> = values.cloned().collect();
+            let marks = vec!["?"; values.len()].join(", ");
+            conditions.push(format!("{column} IN ({marks})"));
+            params.extend(values);
+        }
+    }
+    if let Some(date) = args.get_one::<String>("from") {
+        conditions.push("seq_rel_date >= ?".to_string());
+        params.push(date.clone());
+    }
+    if let Some(date) = args.get_one::<String>("to") {
+        conditions.push("seq_rel_date <= ?".to_string());
+        params.push(date.clone());
+    }
+
+    // The taxa under the terms
+    let tx_conn = nwr::connect_txdb(&nwrdir)?;
+    let mut tax_ids: HashSet<i64> = HashSet::new();
+    for id in nwr::terms_to_tax_ids(&tx_conn, &terms)? {
+        tax_ids.extend(nwr::get_all_descendent(&tx_conn, id)?);
+    }
+
+    let query = Query {
+        conditions: &conditions,
+        params: &params,
+        filters: &filters,
+        columns: &columns,
+        prefer,
+    };
+    let mut selected: Vec<Row> = vec![];
+    for source in ["refseq", "genbank", "all"] {
+        let is_wanted = if sources.is_empty() {
+            source == "refseq"
+        } else {
+            sources.iter().any(|s| *s == source)
+        };
+        if is_wanted {
+            selected.extend(select(&nwrdir, source, &query, &tax_ids)?);
+        }
+    }
+    // `ar_all.sqlite` pairs them by `pair` in `select`
+    let mut rows = if sources.iter().any(|s| *s == "all") {
+        selected
+    } else {
+        pick_pairs(selected, prefer)
+    };
+    let per = args.get_one::<String>("per");
+    if let Some(rank) = per {
+        let top = *args
+            .get_one::<usize>("top")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'top' argument"))?;
+        let keys: Vec<&str> = match args.get_many::<String>("by") {
+            Some(keys) => keys.map(String::as_str).collect(),
+            None => RANK_KEYS[..4].to_vec(),
+        };
+        rows = pick_best(rows, rank, &keys, top);
+    }
+
+    let mut all_parts = vec![];
+    for row in &rows {
+        let line = format!("{}\t{}\t{}", strain_name(row), row.species, row.genus);
+        let (_, parts) = nwr::libs::abbr::process_line(&line, (1, 2, 3), "\t", true)
+            .ok_or_else(|| {
+                anyhow::anyhow!("Can't parse the name of {}", row.accession)
+            })?;
+        all_parts.push(parts);
+    }
+    let abbrs = nwr::libs::abbr::abbr_names(&all_parts, min_len, false);
+
+    let mut lines: Vec<(String, &Row)> = abbrs
+        .into_iter()
+        .zip(&rows)
+        .map(|(abbr, row)| {
+            let name = nwr::libs::abbr::clean_name(&format!(
+                "{abbr}_{}",
+                row.accession.replace('.', "_")
+            ));
+            (name, row)
+        })
+        .collect();
+    lines.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut writer = nwr::libs::io::writer(
+        args.get_one::<String>("outfile")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?,
+    )?;
+    let mut header = vec![
+        "#name",
+        "ftp_path",
+        "biosample",
+        "species",
+        "assembly_level",
+    ];
+    header.extend(columns.iter().map(String::as_str));
+    if per.is_some() {
+        header.push("reason");
+    }
+    writer.write_fmt(format_args!("{}\n", header.join("\t")))?;
+    for (name, row) in lines {
+        let mut fields = vec![
+            name.as_str(),
+            &row.ftp_path,
+            &row.biosample,
+            &row.species,
+            &row.level,
+        ];
+        fields.extend(row.extra.iter().map(String::as_str));
+        if per.is_some() {
+            fields.push(&row.reason);
+        }
+        writer.write_fmt(format_args!("{}\n", fields.join("\t")))?;
+    }
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Filters and output columns on `ar`.
+struct Query<'a> {
+    /// SQL conditions of the fixed options
+    conditions: &'a [String],
+    params: &'a [String],
+    /// `--filter`s: column, operator and value
+    filters: &'a [(String, String, String)],
+    /// `--column`s
+    columns: &'a [String],
+    /// `--prefer`, the source kept of a pair in `ar_all.sqlite`
+    prefer: &'a str,
+}
+
+/// Select the assemblies of one database that pass the filters and belong to
+/// one of `tax_ids`.
+fn select(
+    nwrdir: &std::path::Path,
+    source: &str,
+    query: &Query,
+    tax_ids: &HashSet<i64>,
+) -> anyhow::Result<Vec<Row>> {
+    let file = nwrdir.join(format!("ar_{source}.sqlite"));
+    if !file.exists() {
+        let command = match source {
+            "genbank" => "nwr ardb --genbank",
+            "all" => "nwr ardb --all",
+            _ => "nwr ardb",
+        };
+        anyhow::bail!("{} doesn't exist; run `{command}`", file.display());
+    }
+    let conn = rusqlite::Connection::open_with_flags(
+        &file,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?;
+    nwr::libs::db::check_schema_version(&conn, &file, nwr::libs::db::AR_SCHEMA_VERSION)?;
+
+    // Columns of older databases depend on the summary they were built from
+    let known: Vec<String> = conn
+        .prepare("SELECT name FROM pragma_table_info('ar')")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    let filter_columns = query.filters.iter().map(|(column, _, _)| column);
+    for column in query.columns.iter().chain(filter_columns) {
+        if !known.contains(column) {
+            anyhow::bail!(
+                "{} has no column {column}; rebuild it with `nwr ardb` from a newer summary",
+                file.display()
+            );
+        }
+    }
+
+    let mut sql = "
+        SELECT tax_id, organism_name, infraspecific_name, biosample, assembly_accession,
+            gbrs_paired_asm, ftp_path, species, genus, assembly_level"
+        .to_string();
+    // Only `ar_all.sqlite` has the `source` column
+    if source == "all" {
+        sql.push_str(", source");
+    } else {
+        sql.push_str(&format!(", '{source}'"));
+    }
+    sql.push_str(", family, refseq_category, seq_rel_date");
+    // Columns of newer summaries, to rank by
+    for column in ["relation_to_type_material", "contig_n50", "scaffold_n50"] {
+        if known.iter().any(|name| name == column) {
+            sql.push_str(&format!(", {column}"));
+        } else {
+            sql.push_str(", NULL");
+        }
+    }
+    for column in query.columns {
+        sql.push_str(&format!(", \"{column}\""));
+    }
+    // The taxa go into a temporary table, to look rows up by `idx_ar_tax_id`
+    conn.execute_batch("CREATE TEMP TABLE taxa (tax_id INTEGER PRIMARY KEY);")?;
+    {
+        let mut insert = conn.prepare("INSERT INTO temp.taxa(tax_id) VALUES (?1)")?;
+        for tax_id in tax_ids {
+            insert.execute([tax_id])?;
+        }
+    }
+    let mut selection = "
+        SELECT *
+        FROM ar
+        WHERE 1=1
+            AND tax_id IN (SELECT tax_id FROM temp.taxa)"
+        .to_string();
+    let mut params: Vec<rusqlite::types::Value> = query
+        .params
+        .iter()
+        .map(|param| param.clone().into())
+        .collect();
+    for condition in query.conditions {
+        selection.push_str(&format!("\n            AND {condition}"));
+    }
+    for (column, op, value) in query.filters {
+        selection.push_str(&format!("\n            AND \"{column}\" {op} ?"));
+        params.push(filter_value(value));
+    }
+    let sql = if source == "all" {
+        // Of the pairs in `pair` with both sides selected, keep the preferred side
+        let (kept, other) = if query.prefer == "genbank" {
+            ("genbank_accession", "refseq_accession")
+        } else {
+            ("refseq_accession", "genbank_accession")
+        };
+        format!(
+            "
+        WITH sel AS ({selection}
+        )
+        {sql}
+        FROM sel
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM pair
+                INNER JOIN sel AS kept ON kept.assembly_accession = pair.{kept}
+            WHERE pair.{other} = sel.assembly_accession
+                AND kept.ftp_path NOT IN ('', 'na')
+        )"
+        )
+    } else {
+        format!("{sql}\n        FROM ({selection}\n        )")
+    };
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(rusqlite::params_from_iter(params))?;
+
+    let mut selected = vec![];
+    while let Some(row) = rows.next()? {
+        let text = |i: usize| -> rusqlite::Result<String> {
+            Ok(row.get::<_, Option<String>>(i)?.unwrap_or_default())
+        };
+
+        let accession = text(4)?;
+        let ftp_path = text(6)?;
+        if ftp_path.is_empty() || ftp_path == "na" {
+            warn!("{accession} has no ftp_path; skipped");
+            continue;
+        }
+
+        // Taxa missing from the taxonomy have no species in `ar`
+        let organism_name = text(1)?;
+        let mut species = text(7)?;
+        if species.is_empty() || species == "NA" {
+            species.clone_from(&organism_name);
+        }
+        let mut genus = text(8)?;
+        if genus.is_empty() || genus == "NA" {
+            genus = species.split(' ').next().unwrap_or_default().to_string();
+        }
+
+        selected.push(Row {
+            organism_name,
+            infraspecific_name: text(2)?,
+            biosample: text(3)?,
+            accession,
+            paired: text(5)?,
+            ftp_path,
+            species,
+            genus,
+            level: text(9)?,
+            source: text(10)?,
+            family: text(11)?,
+            category: text(12)?,
+            date: text(13)?,
+            type_material: text(14)?,
+            contig_n50: row.get(15)?,
+            scaffold_n50: row.get(16)?,
+            extra: (0..query.columns.len())
+                .map(|i| column_text(row, 17 + i))
+                .collect::<rusqlite::Result<_>>()?,
+            reason: String::new(),
+        });
+    }
+
+    Ok(selected)
+}
+
+/// Keep one assembly of each GCF/GCA pair selected from `ar_refseq.sqlite` and
+/// `ar_genbank.sqlite`, the one from `prefer`. Without a `pair` table across
+/// the two, pairs come from `gbrs_paired_asm`.
+fn pick_pairs(rows: Vec<Row>, prefer: &str) -> Vec<Row> {
+    let preferred: HashSet<String> = rows
+        .iter()
+        .filter(|row| row.source == prefer)
+        .flat_map(|row| [row.accession.clone(), row.paired.clone()])
+        .filter(|accession| accession.starts_with("GC"))
+        .collect();
+    rows.into_iter()
+        .filter(|row| {
+            row.source == prefer
+                || !(preferred.contains(&row.accession)
+                    || (row.paired.starts_with("GC") && preferred.contains(&row.paired)))
+        })
+        .collect()
+}
+
+/// Keep the `top` assemblies of each taxon at `rank`, ranked by `keys`, and
+/// say why in their `reason`.
+fn pick_best(rows: Vec<Row>, rank: &str, keys: &[&str], top: usize) -> Vec<Row> {
+    let mut groups: BTreeMap<String, Vec<Row>> = BTreeMap::new();
+    for row in rows {
+        let taxon = match rank {
+            "species" => &row.species,
+            "genus" => &row.genus,
+            _ => &row.family,
+        };
+        groups.entry(taxon.clone()).or_default().push(row);
+    }
+
+    let mut best = vec![];
+    for (taxon, mut members) in groups {
+        members.sort_by(|a, b| {
+            keys.iter()
+                .fold(Ordering::Equal, |ord, key| {
+                    ord.then_with(|| compare_by(key, a, b))
+                })
+                .then_with(|| a.accession.cmp(&b.accession))
+        });
+        let total = members.len();
+        for (i, mut row) in members.into_iter().take(top).enumerate() {
+            let values: Vec<String> =
+                keys.iter().map(|key| describe(key, &row)).collect();
+            row.reason = format!(
+                "{} of {total} in {rank} {taxon}: {}",
+                i + 1,
+                values.join(", ")
+            );
+            best.push(row);
+        }
+    }
+    best
+}
+
+/// Compare two assemblies by one key of `--by`; the better one is `Less`.
+fn compare_by(key: &str, a: &Row, b: &Row) -> Ordering {
+    let category = |row: &Row| match row.category.as_str() {
+        "reference genome" => 0,
+        "representative genome" => 1,
+        _ => 2,
+    };
+    let is_type = |row: &Row| !row.type_material.is_empty() && row.type_material != "na";
+    // Larger values first, missing ones last
+    let descending = |a: Option<i64>, b: Option<i64>| b.cmp(&a);
+    match key {
+        "category" => category(a).cmp(&category(b)),
+        "level" => level_code(&a.level).cmp(level_code(&b.level)),
+        "contig_n50" => descending(a.contig_n50, b.contig_n50),
+        "scaffold_n50" => descending(a.scaffold_n50, b.scaffold_n50),
+        // Newer releases first
+        "date" => b.date.cmp(&a.date),
+        "type" => is_type(b).cmp(&is_type(a)),
+        _ => Ordering::Equal,
+    }
+}
+
+/// The value of one key of `--by`, for `reason`.
+fn describe(key: &str, row: &Row) -> String {
+    let or_na =
+        |value: Option<i64>| value.map_or_else(|| "na".to_string(), |v| v.to_string());
+    match key {
+        "category" => row.category.clone(),
+        "level" => row.level.clone(),
+        "contig_n50" => format!("contig_n50 {}", or_na(row.contig_n50)),
+        "scaffold_n50" => format!("scaffold_n50 {}", or_na(row.scaffold_n50)),
+        "date" => format!("released {}", row.date),
+        "type" if row.type_material.is_empty() || row.type_material == "na" => {
+            "not type material".to_string()
+        }
+        _ => row.type_material.clone(),
+    }
+}
+
+/// A column of any type as text; NULL is `na`, as in the summaries.
+fn column_text(row: &rusqlite::Row, i: usize) -> rusqlite::Result<String> {
+    use rusqlite::types::ValueRef;
+    Ok(match row.get_ref(i)? {
+        ValueRef::Null => "na".to_string(),
+        ValueRef::Integer(value) => value.to_string(),
+        ValueRef::Real(value) => value.to_string(),
+        ValueRef::Text(value) | ValueRef::Blob(value) => {
+            String::from_utf8_lossy(value).into_owned()
+        }
+    })
+}
+
+/// Numbers compare as numbers with INTEGER and REAL columns, other values as
+/// text.
+fn filter_value(value: &str) -> rusqlite::types::Value {
+    use rusqlite::types::Value;
+    if let Ok(value) = value.parse::<i64>() {
+        Value::Integer(value)
+    } else if let Ok(value) = value.parse::<f64>() {
+        Value::Real(value)
+    } else {
+        Value::Text(value.to_string())
+    }
+}
+
+/// Parse `COLUMN OP VALUE` of `--filter`, e.g. `contig_n50>=50000`.
+fn parse_filter(filter: &str) -> Result<(String, String, String), String> {
+    let (pos, op) = OPERATORS
+        .iter()
+        .filter_map(|op| filter.find(op).map(|pos| (pos, *op)))
+        .min_by_key(|(pos, op)| (*pos, std::cmp::Reverse(op.len())))
+        .ok_or_else(|| format!("expected COLUMN OP VALUE with OP in {OPERATORS:?}"))?;
+    let column = filter[..pos].trim();
+    let value = filter[pos + op.len()..].trim();
+    let value = value
+        .strip_prefix('\'')
+        .and_then(|v| v.strip_suffix('\''))
+        .unwrap_or(value);
+    if column.is_empty()
+        || !column
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!("'{column}' is not a column name"));
+    }
+    Ok((column.to_string(), op.to_string(), value.to_string()))
+}
+
+/// The strain name to abbreviate: the species and the value of
+/// `infraspecific_name`, like `strain=K-12`, or else the organism name.
+fn strain_name(row: &Row) -> String {
+    let strain = row
+        .infraspecific_name
+        .split_once('=')
+        .map_or("", |(_, value)| value)
+        .trim();
+    if strain.is_empty() {
+        if row.organism_name.starts_with(&row.species) {
+            row.organism_name.clone()
+        } else {
+            row.species.clone()
+        }
+    } else if row.species.ends_with(strain) {
+        row.species.clone()
+    } else {
+        format!("{} {strain}", row.species)
+    }
+}
+
+/// Accept `YYYY-MM-DD`, or `YYYY/MM/DD` as in the assembly summaries.
+fn parse_date(date: &str) -> Result<String, String> {
+    let date = date.replace('/', "-");
+    let parts: Vec<&str> = date.split('-').collect();
+    let is_valid = parts.len() == 3
+        && [4, 2, 2].iter().zip(&parts).all(|(len, part)| {
+            part.len() == *len && part.chars().all(|c| c.is_ascii_digit())
+        });
+    if is_valid {
+        Ok(date)
+    } else {
+        Err(format!("expected YYYY-MM-DD, got '{date}'"))
+    }
+}
@@ -0,0 +1,37 @@
+use super::args;
+use clap::{Arg, ArgMatches, Command};
+use log::info;
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("bundle")
+        .about("Packs the downloaded files into a bundle for offline installs")
+        .after_help(include_str!("../../docs/help/bundle.md"))
+        .arg(
+            Arg::new("outfile")
+                .help("Output filename, a .tar.gz. [stdout] for screen")
+                .required(true)
+                .num_args(1)
+                .index(1),
+        )
+        .arg(args::dir_arg())
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
+    let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let _lock = nwr::libs::lock::read_lock(&nwrdir)?;
+    let outfile = args
+        .get_one::<String>("outfile")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?;
+
+    let mut writer = nwr::libs::io::writer(outfile)?;
+    let names = nwr::libs::bundle::write_bundle(&nwrdir, &mut writer)?;
+    writer.finish()?;
+    info!("Bundled {} files into {outfile}", names.len());
+
+    Ok(())
+}
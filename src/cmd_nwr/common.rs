@@ -35,12 +35,18 @@ pub fn make_subcommand() -> Command {
         .after_help(include_str!("../../docs/help/common.md"))
         .arg(args::terms_arg("The NCBI Taxonomy ID or scientific name"))
         .arg(args::dir_arg())
+        .arg(args::taxonomy_arg())
+        .arg(args::within_arg())
         .arg(args::outfile_arg())
 }
 
 /// Command implementation.
 pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let taxonomy = args
+        .get_one::<String>("taxonomy")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'taxonomy' argument"))?;
+    let within = args.get_one::<String>("within").map(String::as_str);
     let terms: Vec<String> = args
         .get_many::<String>("terms")
         .ok_or_else(|| anyhow::anyhow!("No terms provided"))?
@@ -51,9 +57,15 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         args.get_one::<String>("outfile")
             .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?,
     )?;
-    let conn = nwr::connect_txdb(&nwrdir)?;
-
-    let tax_ids = nwr::terms_to_tax_ids(&conn, &terms)?;
+    let conn = nwr::connect_taxonomy(&nwrdir, taxonomy)?;
+
+    let tax_ids = nwr::terms_to_tax_ids(
+        &conn,
+        &terms
+            .iter()
+            .map(|term| nwr::with_ancestor(term, within))
+            .collect::<Vec<_>>(),
+    )?;
 
     let mut tree = phylotree::tree::Tree::new();
     // tax_id to NodeId
@@ -0,0 +1,302 @@
+use super::args;
+use clap::{Arg, ArgMatches, Command};
+use log::{debug, info};
+use nwr::libs::db::StagedDb;
+
+use std::collections::HashMap;
+use std::io::BufRead;
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("custom")
+        .about("Builds a taxonomy database from a table of lineages")
+        .after_help(include_str!("../../docs/help/custom.md"))
+        .arg(
+            Arg::new("infile")
+                .help("Input TSV file of lineages. Use 'stdin' for standard input")
+                .required(true)
+                .num_args(1)
+                .index(1),
+        )
+        .arg(
+            Arg::new("lineage")
+                .long("lineage")
+                .num_args(1)
+                .value_name("COLUMN")
+                .value_parser(
+                    clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+                )
+                .help("Read delimited lineage strings from this column (1-based)"),
+        )
+        .arg(
+            Arg::new("sep")
+                .long("sep")
+                .num_args(1)
+                .default_value(";")
+                .help("Delimiter of the lineage strings"),
+        )
+        .arg(
+            Arg::new("graft")
+                .long("graft")
+                .num_args(1)
+                .value_name("TERM")
+                .help(
+                    "Graft the lineages under this node of the NCBI taxonomy in --dir",
+                ),
+        )
+        .arg(args::dir_arg())
+        .arg(args::outdir_arg())
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
+    let infile = args
+        .get_one::<String>("infile")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'infile' argument"))?;
+    let sep = args
+        .get_one::<String>("sep")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'sep' argument"))?;
+    if sep.is_empty() {
+        anyhow::bail!("--sep must not be empty");
+    }
+    let outdir = std::path::Path::new(
+        args.get_one::<String>("outdir")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'outdir' argument"))?,
+    );
+    let _lock = nwr::libs::lock::lock_dir(outdir)?;
+    let outfile = outdir.join(nwr::txdb_filename("ncbi")?);
+
+    // Each lineage is a list of (rank, name) from the top down
+    let lineages = match args.get_one::<usize>("lineage") {
+        Some(column) => read_lineage_strings(infile, *column, sep)?,
+        None => read_rank_columns(infile)?,
+    };
+
+    // The top of the lineages hangs under the root, or the grafted node
+    let is_graft = args.contains_id("graft");
+    let (staged, top_id, division_id, gc_id, mgc_id) = if let Some(term) =
+        args.get_one::<String>("graft")
+    {
+        let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+        let source = nwrdir.join(nwr::txdb_filename("ncbi")?);
+        if outfile.exists()
+            && std::fs::canonicalize(&source)? == std::fs::canonicalize(&outfile)?
+        {
+            anyhow::bail!("--outdir must differ from --dir when grafting");
+        }
+
+        let parent_id = {
+            let conn = nwr::connect_txdb(&nwrdir)?;
+            nwr::term_to_tax_id(&conn, term)?
+        };
+
+        info!("==> Copying {}", source.display());
+        let staged = StagedDb::copy_from(&source, &outfile)?;
+        let conn = staged.conn();
+
+        // Grafted taxa inherit the division and genetic codes of the parent
+        let (division_id, gc_id, mgc_id): (i64, i64, i64) = conn.query_row(
+            "SELECT division_id, gc_id, mgc_id FROM node WHERE tax_id = ?1",
+            [parent_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        (staged, parent_id, division_id, gc_id, mgc_id)
+    } else {
+        info!("==> Opening database");
+        let staged = StagedDb::create(&outfile)?;
+        let conn = staged.conn();
+
+        info!("==> Create tables");
+        super::txdb::create_tables(conn)?;
+        conn.execute_batch(
+                "
+                INSERT INTO division (id, division) VALUES (0, 'Custom');
+                INSERT INTO node (tax_id, parent_tax_id, rank, division_id, gc_id, gc_inherited, mgc_id, mgc_inherited, comment)
+                VALUES (1, 1, 'no rank', 0, 0, 0, 0, 0, '');
+                INSERT INTO name (tax_id, name, name_class) VALUES (1, 'root', 'scientific name');
+                ",
+            )?;
+        (staged, 1, 0, 0, 0)
+    };
+    let conn = staged.conn();
+
+    // tax_id => (parent_tax_id, rank, name)
+    let mut nodes: HashMap<i64, (i64, String, String)> = HashMap::new();
+    for lineage in &lineages {
+        let mut parent_id = top_id;
+        // Each ID hashes the path from the top of the lineage, so names
+        // repeated under other parents, like "environmental samples", get
+        // their own taxa whatever the order of the rows
+        let mut path = String::new();
+        for (rank, name) in lineage {
+            if !path.is_empty() {
+                path.push('/');
+            }
+            path.push_str(&format!("{rank}:{name}"));
+            let tax_id = nwr::synthetic_tax_id(&path);
+            match nodes.get(&tax_id) {
+                Some((p, r, n)) if *p != parent_id || r != rank || n != name => {
+                    anyhow::bail!("Tax ID collision between {r} {n} and {rank} {name}");
+                }
+                Some(_) => {}
+                None => {
+                    nodes.insert(tax_id, (parent_id, rank.clone(), name.clone()));
+                }
+            }
+            parent_id = tax_id;
+        }
+    }
+
+    info!("==> Loading {} taxa", nodes.len());
+    let mut ids: Vec<&i64> = nodes.keys().collect();
+    ids.sort_unstable();
+    conn.execute_batch("BEGIN;")?;
+    {
+        let mut stmt_exists = conn.prepare("SELECT 1 FROM node WHERE tax_id = ?1")?;
+        let mut stmt_node = conn.prepare(
+            "INSERT INTO node (tax_id, parent_tax_id, rank, division_id, gc_id, gc_inherited, mgc_id, mgc_inherited, comment)
+             VALUES (?1, ?2, ?3, ?4, ?5, 1, ?6, 1, 'custom')",
+        )?;
+        let mut stmt_name = conn.prepare(
+            "INSERT INTO name (tax_id, name, name_class) VALUES (?1, ?2, 'scientific name')",
+        )?;
+
+        for tax_id in ids {
+            if stmt_exists.exists([tax_id])? {
+                anyhow::bail!("Tax ID {tax_id} already exists in the taxonomy");
+            }
+            let (parent_id, rank, name) = &nodes[tax_id];
+            stmt_node.execute(rusqlite::params![
+                tax_id,
+                parent_id,
+                rank,
+                division_id,
+                gc_id,
+                mgc_id
+            ])?;
+            stmt_name.execute(rusqlite::params![tax_id, name])?;
+        }
+    }
+    conn.execute_batch("COMMIT;")?;
+
+    debug!("Creating indexes");
+    if is_graft {
+        // Databases built before the search index have no `name_fts`
+        if nwr::libs::db::table_exists(conn, "name_fts")? {
+            conn.execute("INSERT INTO name_fts(name_fts) VALUES ('rebuild');", [])?;
+        }
+    } else {
+        super::txdb::create_indexes(conn)?;
+    }
+    super::txdb::build_lineage(conn)?;
+    let sources = if infile == "stdin" {
+        vec![]
+    } else {
+        vec![std::path::PathBuf::from(infile)]
+    };
+    super::txdb::write_meta(conn, &sources)?;
+
+    staged.commit(super::txdb::check_taxonomy)?;
+
+    Ok(())
+}
+
+/// Read lineages from a table with one rank per column.
+///
+/// The first line names the ranks; a leading `#` is ignored. Empty cells are
+/// left out of the lineages.
+fn read_rank_columns(infile: &str) -> anyhow::Result<Vec<Vec<(String, String)>>> {
+    let reader = nwr::libs::io::reader(infile)?;
+    let mut ranks: Vec<String> = Vec::new();
+    let mut lineages = Vec::new();
+
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if ranks.is_empty() {
+            ranks = line
+                .trim_start_matches('#')
+                .split('\t')
+                .map(|rank| rank.trim().to_string())
+                .collect();
+            if ranks.iter().any(String::is_empty) {
+                anyhow::bail!("{}:{}: Empty rank in the header", infile, line_idx + 1);
+            }
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() > ranks.len() {
+            anyhow::bail!(
+                "{}:{}: {} columns but {} ranks in the header",
+                infile,
+                line_idx + 1,
+                fields.len(),
+                ranks.len()
+            );
+        }
+        let lineage: Vec<(String, String)> = ranks
+            .iter()
+            .zip(fields.iter())
+            .filter(|(_, name)| !name.trim().is_empty())
+            .map(|(rank, name)| (rank.clone(), name.trim().to_string()))
+            .collect();
+        lineages.push(lineage);
+    }
+
+    Ok(lineages)
+}
+
+/// Read lineages from delimited strings in a column.
+///
+/// Names with GTDB-style prefixes like `p__` get their ranks from the
+/// prefixes; others are `no rank`. Lines starting with `#` are skipped.
+fn read_lineage_strings(
+    infile: &str,
+    column: usize,
+    sep: &str,
+) -> anyhow::Result<Vec<Vec<(String, String)>>> {
+    let reader = nwr::libs::io::reader(infile)?;
+    let mut lineages = Vec::new();
+
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        let cell = fields.get(column - 1).ok_or_else(|| {
+            anyhow::anyhow!(
+                "{}:{}: Column {} out of range (line has {} columns)",
+                infile,
+                line_idx + 1,
+                column,
+                fields.len()
+            )
+        })?;
+
+        let mut lineage = Vec::new();
+        for token in cell.split(sep).map(str::trim) {
+            let (rank, name) = match token.split_once("__") {
+                Some((prefix, name)) => match nwr::gtdb_rank(prefix) {
+                    Some(rank) => (rank, name.trim()),
+                    None => ("no rank", token),
+                },
+                None => ("no rank", token),
+            };
+            if !name.is_empty() {
+                lineage.push((rank.to_string(), name.to_string()));
+            }
+        }
+        lineages.push(lineage);
+    }
+
+    Ok(lineages)
+}
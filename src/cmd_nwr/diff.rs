@@ -0,0 +1,193 @@
+use super::args;
+use clap::{Arg, ArgMatches, Command};
+use log::{info, warn};
+use nwr::libs::diff::{ChangeKind, Snapshot};
+
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("diff")
+        .about("Compares two taxonomy releases")
+        .after_help(include_str!("../../docs/help/diff.md"))
+        .arg(
+            Arg::new("old")
+                .help(
+                    "The old release: taxonomy.sqlite, its directory, or taxdump.tar.gz",
+                )
+                .required(true)
+                .num_args(1)
+                .index(1),
+        )
+        .arg(
+            Arg::new("new")
+                .help("The new release")
+                .required(true)
+                .num_args(1)
+                .index(2),
+        )
+        .arg(
+            Arg::new("subtree")
+                .long("subtree")
+                .num_args(1)
+                .value_name("TERM")
+                .help("Only report taxa under this tax ID or scientific name"),
+        )
+        .arg(
+            Arg::new("file")
+                .long("file")
+                .short('f')
+                .num_args(1)
+                .help("Report the rows of this TSV file affected by the changes"),
+        )
+        .arg(args::column_arg())
+        .arg(args::outfile_arg())
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
+    let old_path = args
+        .get_one::<String>("old")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'old' argument"))?;
+    let new_path = args
+        .get_one::<String>("new")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'new' argument"))?;
+    let column: usize = *args
+        .get_one("column")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'column' argument"))?;
+
+    let outfile = args
+        .get_one::<String>("outfile")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?;
+    let mut writer = nwr::libs::io::writer(outfile)?;
+
+    let old = Snapshot::open(Path::new(old_path))?;
+    let new = Snapshot::open(Path::new(new_path))?;
+    let old_index = old.name_index();
+    let new_index = new.name_index();
+
+    // Taxa under the term in either release
+    let scope: Option<HashSet<i64>> = match args.get_one::<String>("subtree") {
+        Some(term) => {
+            let old_id = old.resolve(term, &old_index);
+            let new_id = new.resolve(term, &new_index);
+            if old_id.is_none() && new_id.is_none() {
+                anyhow::bail!("Can't resolve --subtree '{term}' in either release");
+            }
+            let mut scope = HashSet::new();
+            if let Some(id) = old_id {
+                scope.extend(old.subtree(id));
+            }
+            if let Some(id) = new_id {
+                scope.extend(new.subtree(id));
+            }
+            Some(scope)
+        }
+        None => None,
+    };
+
+    let changes: Vec<nwr::libs::diff::Change> = nwr::libs::diff::diff(&old, &new)
+        .into_iter()
+        .filter(|c| scope.as_ref().is_none_or(|s| s.contains(&c.tax_id)))
+        .collect();
+
+    let mut counts: HashMap<ChangeKind, usize> = HashMap::new();
+    for change in &changes {
+        *counts.entry(change.kind).or_default() += 1;
+    }
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_unstable();
+    for (kind, count) in counts {
+        info!("{kind}: {count}");
+    }
+
+    if let Some(file) = args.get_one::<String>("file") {
+        let mut kinds: HashMap<i64, Vec<ChangeKind>> = HashMap::new();
+        for change in &changes {
+            kinds.entry(change.tax_id).or_default().push(change.kind);
+        }
+
+        let reader = nwr::libs::io::reader(file)?;
+        for (line_idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Lines start with "#"
+            if line.starts_with('#') {
+                writer
+                    .write_fmt(format_args!("{line}\tchange\tnew_tax_id\tnew_name\n"))?;
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            let term = fields.get(column - 1).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{}:{}: Column {} out of range (line has {} columns)",
+                    file,
+                    line_idx + 1,
+                    column,
+                    fields.len()
+                )
+            })?;
+
+            let Some(id) = old.resolve(term, &old_index) else {
+                warn!("Can't resolve '{term}' in the old release");
+                continue;
+            };
+            if scope.as_ref().is_some_and(|s| !s.contains(&id)) {
+                continue;
+            }
+
+            let mut row_kinds: Vec<String> = kinds
+                .get(&id)
+                .map(|v| v.iter().map(ToString::to_string).collect())
+                .unwrap_or_default();
+            let new_id = new.merged.get(&id).copied().unwrap_or(id);
+            let is_present = new.nodes.contains_key(&new_id);
+
+            // Changes above the taxon show up in its lineage
+            if is_present
+                && !kinds.contains_key(&id)
+                && old.lineage(id) != new.lineage(new_id)
+            {
+                row_kinds.push("lineage".to_string());
+            }
+            if row_kinds.is_empty() {
+                continue;
+            }
+
+            let (new_id, new_name) = if is_present {
+                (
+                    new_id.to_string(),
+                    new.names.get(&new_id).map_or("NA", String::as_str),
+                )
+            } else {
+                ("NA".to_string(), "NA")
+            };
+            writer.write_fmt(format_args!(
+                "{line}\t{}\t{new_id}\t{new_name}\n",
+                row_kinds.join(",")
+            ))?;
+        }
+    } else {
+        writer.write_fmt(format_args!("#change\ttax_id\tname\told\tnew\n"))?;
+        for change in &changes {
+            writer.write_fmt(format_args!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                change.kind, change.tax_id, change.name, change.old, change.new
+            ))?;
+        }
+    }
+    writer.flush()?;
+    writer.finish()?;
+
+    Ok(())
+}
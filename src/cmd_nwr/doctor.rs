@@ -0,0 +1,347 @@
+use super::args;
+use clap::ArgMatches;
+use clap::Command;
+use nwr::libs::db::DbKind;
+use nwr::libs::doctor::{format_ids, Finding, Level};
+
+use std::io::Write;
+use std::path::Path;
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("doctor")
+        .about("Checks the nwr directory for problems")
+        .after_help(include_str!("../../docs/help/doctor.md"))
+        .arg(args::dir_arg())
+        .arg(args::outfile_arg())
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
+    let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let _lock = nwr::libs::lock::read_lock(&nwrdir)?;
+    let outfile = args
+        .get_one::<String>("outfile")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?;
+
+    let mut findings: Vec<Finding> = vec![];
+    let paths = nwr::libs::download::get_download_paths(&nwrdir)?;
+
+    // Downloads
+    for (tarball, md5_file) in [
+        (&paths.tarball, &paths.md5_file),
+        (&paths.new_tarball, &paths.new_md5_file),
+    ] {
+        if !tarball.exists() {
+            continue;
+        }
+        let subject = file_name(tarball);
+        if !md5_file.exists() {
+            findings.push(Finding::new(
+                Level::Warn,
+                &subject,
+                &format!("no {} to verify it against", file_name(md5_file)),
+            ));
+            continue;
+        }
+        match nwr::libs::download::check_taxdump_md5(tarball, md5_file) {
+            Ok(()) => findings.push(Finding::new(Level::Ok, &subject, "MD5 matches")),
+            Err(err) => findings.push(Finding::new(
+                Level::Fail,
+                &subject,
+                &format!("{err}; re-run `nwr download`"),
+            )),
+        }
+    }
+
+    // Databases
+    let txdb = nwrdir.join("taxonomy.sqlite");
+    let mut has_database = false;
+    for db in &nwr::libs::db::DATABASES {
+        let path = nwrdir.join(db.name);
+        if !path.exists() {
+            continue;
+        }
+        has_database = true;
+        match db.kind {
+            DbKind::Taxonomy => check_taxonomy(&path, db.rebuild, &mut findings)?,
+            DbKind::Assembly => check_assembly(&path, &txdb, db.rebuild, &mut findings)?,
+            DbKind::Seq => {}
+        }
+    }
+    if !has_database {
+        findings.push(Finding::new(
+            Level::Warn,
+            &nwrdir.display().to_string(),
+            "no databases found; run `nwr txdb`",
+        ));
+    }
+
+    // Leftovers
+    let mut dmp_files = vec![];
+    let mut entries: Vec<_> = std::fs::read_dir(&nwrdir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    entries.sort();
+    for path in entries {
+        let name = file_name(&path);
+        if name.ends_with(".dmp") {
+            dmp_files.push(name);
+        } else if name.ends_with(".sqlite.tmp") {
+            findings.push(Finding::new(
+                Level::Warn,
+                &name,
+                "left by an interrupted build; remove it",
+            ));
+        } else if let Some(db) = name.strip_suffix(".bak") {
+            if !db.ends_with(".sqlite") {
+                continue;
+            }
+            if path.with_file_name(db).exists() {
+                findings.push(Finding::new(
+                    Level::Ok,
+                    &name,
+                    &format!("previous {db}, kept by the last rebuild"),
+                ));
+            } else {
+                findings.push(Finding::new(
+                    Level::Warn,
+                    &name,
+                    &format!(
+                        "{db} is missing, as a rebuild was interrupted; rename it back"
+                    ),
+                ));
+            }
+        }
+    }
+    if !dmp_files.is_empty() && paths.tarball.exists() {
+        findings.push(Finding::new(
+            Level::Warn,
+            &nwrdir.display().to_string(),
+            &format!(
+                "{} extracted .dmp files aren't needed, as `nwr txdb` reads {}: {}",
+                dmp_files.len(),
+                file_name(&paths.tarball),
+                dmp_files.join(", ")
+            ),
+        ));
+    }
+
+    // Report
+    let n_fail = findings.iter().filter(|f| f.level == Level::Fail).count();
+    let n_warn = findings.iter().filter(|f| f.level == Level::Warn).count();
+    let mut writer = nwr::libs::io::writer(outfile)?;
+    for finding in &findings {
+        writer.write_fmt(format_args!("{finding}\n"))?;
+    }
+    writer.write_fmt(format_args!("{n_fail} problem(s), {n_warn} warning(s)\n"))?;
+    writer.flush()?;
+    writer.finish()?;
+
+    if n_fail > 0 {
+        anyhow::bail!("{n_fail} problem(s) found in {}", nwrdir.display());
+    }
+
+    Ok(())
+}
+
+/// Checks shared by all databases; `false` if the rest can't be checked.
+fn check_database(
+    conn: &rusqlite::Connection,
+    subject: &str,
+    path: &Path,
+    version: i64,
+    findings: &mut Vec<Finding>,
+) -> anyhow::Result<bool> {
+    let errors = nwr::libs::doctor::quick_check(conn)?;
+    if !errors.is_empty() {
+        findings.push(Finding::new(
+            Level::Fail,
+            subject,
+            &format!("corrupt: {}", errors.join("; ")),
+        ));
+        return Ok(false);
+    }
+    if let Err(err) = nwr::libs::db::check_schema_version(conn, path, version) {
+        findings.push(Finding::new(Level::Fail, subject, &err.to_string()));
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Check the tree and names of a taxonomy database.
+fn check_taxonomy(
+    path: &Path,
+    import: &str,
+    findings: &mut Vec<Finding>,
+) -> anyhow::Result<()> {
+    let subject = file_name(path);
+    let conn = rusqlite::Connection::open_with_flags(
+        path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?;
+    if !check_database(
+        &conn,
+        &subject,
+        path,
+        nwr::libs::db::TX_SCHEMA_VERSION,
+        findings,
+    )? {
+        return Ok(());
+    }
+    let before = findings.len();
+
+    let has_root: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM node WHERE tax_id = 1 AND parent_tax_id = 1)",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_root {
+        findings.push(Finding::new(
+            Level::Fail,
+            &subject,
+            &format!("root node 1 is missing; re-run `{import}`"),
+        ));
+    }
+
+    let problems = nwr::libs::doctor::tree_problems(&conn)?;
+    if !problems.orphans.is_empty() {
+        findings.push(Finding::new(
+            Level::Fail,
+            &subject,
+            &format!(
+                "{} orphan node(s) whose parent is missing: {}",
+                problems.orphans.len(),
+                format_ids(&problems.orphans)
+            ),
+        ));
+    }
+    if !problems.cycles.is_empty() {
+        findings.push(Finding::new(
+            Level::Fail,
+            &subject,
+            &format!(
+                "{} node(s) in parent cycles: {}",
+                problems.cycles.len(),
+                format_ids(&problems.cycles)
+            ),
+        ));
+    }
+
+    let names = nwr::libs::doctor::names_without_nodes(&conn)?;
+    if !names.is_empty() {
+        findings.push(Finding::new(
+            Level::Fail,
+            &subject,
+            &format!(
+                "{} tax ID(s) in name without a node: {}",
+                names.len(),
+                format_ids(&names)
+            ),
+        ));
+    }
+
+    if let Err(err) = nwr::libs::db::check_indexes(&conn, &super::txdb::TX_INDEXES) {
+        findings.push(Finding::new(
+            Level::Fail,
+            &subject,
+            &format!("{err}; re-run `{import}`"),
+        ));
+    }
+
+    if findings.len() == before {
+        let nodes: i64 =
+            conn.query_row("SELECT COUNT(*) FROM node", [], |row| row.get(0))?;
+        findings.push(Finding::new(
+            Level::Ok,
+            &subject,
+            &format!("{nodes} nodes, all connected to the root"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check an assembly database against the taxonomy database.
+fn check_assembly(
+    path: &Path,
+    txdb: &Path,
+    import: &str,
+    findings: &mut Vec<Finding>,
+) -> anyhow::Result<()> {
+    let subject = file_name(path);
+    let conn = rusqlite::Connection::open_with_flags(
+        path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?;
+    if !check_database(
+        &conn,
+        &subject,
+        path,
+        nwr::libs::db::AR_SCHEMA_VERSION,
+        findings,
+    )? {
+        return Ok(());
+    }
+    let before = findings.len();
+
+    if let Err(err) = nwr::libs::db::check_indexes(&conn, &super::ardb::AR_INDEXES) {
+        findings.push(Finding::new(
+            Level::Fail,
+            &subject,
+            &format!("{err}; re-run `{import}`"),
+        ));
+    }
+
+    if txdb.exists() {
+        let (missing, merged) = nwr::libs::doctor::missing_tax_ids(&conn, txdb)?;
+        if !missing.is_empty() {
+            findings.push(Finding::new(
+                Level::Fail,
+                &subject,
+                &format!(
+                    "{} tax ID(s) gone from {}: {}; update both with `nwr download`",
+                    missing.len(),
+                    file_name(txdb),
+                    format_ids(&missing)
+                ),
+            ));
+        }
+        if !merged.is_empty() {
+            findings.push(Finding::new(
+                Level::Warn,
+                &subject,
+                &format!(
+                    "{} tax ID(s) merged in {}: {}",
+                    merged.len(),
+                    file_name(txdb),
+                    format_ids(&merged)
+                ),
+            ));
+        }
+    }
+
+    if findings.len() == before {
+        let rows: i64 =
+            conn.query_row("SELECT COUNT(*) FROM ar", [], |row| row.get(0))?;
+        findings.push(Finding::new(
+            Level::Ok,
+            &subject,
+            &format!("{rows} assemblies, all with known tax IDs"),
+        ));
+    }
+
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().to_string(),
+    )
+}
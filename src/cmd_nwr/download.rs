@@ -1,11 +1,11 @@
 use super::args;
-use clap::{Arg, ArgMatches, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use log::info;
 
 use nwr::libs::download::{
-    assembly_reports_exist, check_taxdump_md5, download_assembly_reports,
-    download_taxdump, extract_taxdump, format_file_sizes, get_download_paths,
-    taxdump_exists, FtpConnection, FtpConnectionTrait,
+    assembly_reports_exist, check_taxdump_md5, connect, download_assembly_reports,
+    download_new_taxdump, download_taxdump, extract_taxdump, extract_taxdump_entries,
+    format_file_sizes, get_download_paths, taxdump_exists, NEW_TAXDUMP_FILES,
 };
 
 /// Create clap subcommand arguments
@@ -19,22 +19,42 @@ pub fn make_subcommand() -> Command {
             Arg::new("host")
                 .long("host")
                 .num_args(1)
-                .default_value("ftp.ncbi.nih.gov:21")
-                .help("NCBI FTP Host:Port"),
+                .default_value("https://ftp.ncbi.nlm.nih.gov")
+                .help("NCBI host: an https:// or http:// URL, or an FTP host:port"),
         )
         .arg(
             Arg::new("tx")
                 .long("tx")
                 .num_args(1)
                 .default_value("/pub/taxonomy")
-                .help("NCBI FTP Path of taxonomy"),
+                .help("NCBI path of taxonomy"),
         )
         .arg(
             Arg::new("ar")
                 .long("ar")
                 .num_args(1)
                 .default_value("/genomes/ASSEMBLY_REPORTS")
-                .help("NCBI FTP Path of assembly reports"),
+                .help("NCBI path of assembly reports"),
+        )
+        .arg(
+            Arg::new("retries")
+                .long("retries")
+                .num_args(1)
+                .default_value("3")
+                .value_parser(clap::value_parser!(u32))
+                .help("Retries of failed HTTP(S) requests, with increasing waits"),
+        )
+        .arg(
+            Arg::new("extract")
+                .long("extract")
+                .action(ArgAction::SetTrue)
+                .help("Also extract the .dmp files; `nwr txdb` reads the tarballs directly"),
+        )
+        .arg(
+            Arg::new("new_taxdump")
+                .long("new-taxdump")
+                .action(ArgAction::SetTrue)
+                .help("Also download `new_taxdump` (lineages, type material, hosts)"),
         )
 }
 
@@ -43,6 +63,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     nwr::libs::io::init_logger();
 
     let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let _lock = nwr::libs::lock::lock_dir(&nwrdir)?;
     let host = args
         .get_one::<String>("host")
         .ok_or_else(|| anyhow::anyhow!("Missing 'host' argument"))?;
@@ -53,6 +74,13 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         .get_one::<String>("ar")
         .ok_or_else(|| anyhow::anyhow!("Missing 'ar' argument"))?;
 
+    let retries: u32 = *args
+        .get_one("retries")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'retries' argument"))?;
+
+    let is_extract = args.get_flag("extract");
+    let is_new_taxdump = args.get_flag("new_taxdump");
+
     let paths = get_download_paths(&nwrdir)?;
 
     // Download taxdump
@@ -61,10 +89,9 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         info!("Skipping, {} exists", paths.tarball.to_string_lossy());
     } else {
         info!("Connecting...");
-        let mut conn = FtpConnection::connect(host)?;
-        conn.login("ftp", "example@example.com")?;
+        let mut conn = connect(host, retries)?;
         info!("Connected.");
-        download_taxdump(&mut conn, &paths, tx_path)?;
+        download_taxdump(conn.as_mut(), &paths, tx_path)?;
         conn.quit()?;
         info!("End connection.");
     }
@@ -81,8 +108,41 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     }
 
     // Extract
-    info!("==> Extracting...");
-    extract_taxdump(&paths.tarball, &nwrdir)?;
+    if is_extract {
+        info!("==> Extracting...");
+        extract_taxdump(&paths.tarball, &nwrdir)?;
+    }
+
+    // new_taxdump
+    if is_new_taxdump {
+        info!("==> Downloading new_taxdump from {host} ...");
+        if taxdump_exists(&paths.new_tarball) && paths.new_md5_file.exists() {
+            info!("Skipping, {} exists", paths.new_tarball.to_string_lossy());
+        } else {
+            info!("Connecting...");
+            let mut conn = connect(host, retries)?;
+            info!("Connected.");
+            download_new_taxdump(conn.as_mut(), &paths, tx_path)?;
+            conn.quit()?;
+            info!("End connection.");
+        }
+
+        info!("==> Checking...");
+        if let Err(e) = check_taxdump_md5(&paths.new_tarball, &paths.new_md5_file) {
+            let _ = std::fs::remove_file(&paths.new_tarball);
+            let _ = std::fs::remove_file(&paths.new_md5_file);
+            return Err(e);
+        }
+
+        if is_extract {
+            info!("==> Extracting...");
+            for name in
+                extract_taxdump_entries(&paths.new_tarball, &nwrdir, NEW_TAXDUMP_FILES)?
+            {
+                info!("{name}");
+            }
+        }
+    }
 
     // Assembly reports
     info!("==> Downloading from {host} ...");
@@ -94,10 +154,9 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         );
     } else {
         info!("Connecting...");
-        let mut conn = FtpConnection::connect(host)?;
-        conn.login("ftp", "example@example.com")?;
+        let mut conn = connect(host, retries)?;
         info!("Connected.");
-        download_assembly_reports(&mut conn, &paths, ar_path)?;
+        download_assembly_reports(conn.as_mut(), &paths, ar_path)?;
         conn.quit()?;
         info!("End connection.");
     }
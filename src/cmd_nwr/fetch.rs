@@ -0,0 +1,176 @@
+use super::args;
+use clap::{value_parser, Arg, ArgMatches, Command};
+use log::{info, warn};
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use nwr::libs::fetch::{fetch_assembly, read_assemblies, FILE_TYPES};
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("fetch")
+        .about("Downloads the genome files of assemblies and checks their MD5 sums")
+        .after_help(include_str!("../../docs/help/fetch.md"))
+        .arg(
+            Arg::new("infiles")
+                .help("ASSEMBLY/url.tsv or .assembly.tsv files")
+                .required(true)
+                .num_args(1..)
+                .index(1),
+        )
+        .arg(
+            Arg::new("type")
+                .long("type")
+                .short('t')
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_parser(FILE_TYPES.map(|(name, _)| name))
+                .help("File types to download. Default: all of them"),
+        )
+        .arg(
+            Arg::new("outdir")
+                .long("outdir")
+                .num_args(1)
+                .default_value(".")
+                .help("Output directory, e.g. ASSEMBLY"),
+        )
+        .arg(
+            Arg::new("retry_list")
+                .long("retry-list")
+                .num_args(1)
+                .default_value("retry.tsv")
+                .help("Where to write the lines of failed assemblies, under the output directory"),
+        )
+        .arg(
+            Arg::new("parallel")
+                .long("parallel")
+                .short('p')
+                .num_args(1)
+                .default_value("4")
+                .value_parser(value_parser!(usize))
+                .help("Number of assemblies downloaded at once"),
+        )
+        .arg(
+            Arg::new("retries")
+                .long("retries")
+                .num_args(1)
+                .default_value("3")
+                .value_parser(value_parser!(u32))
+                .help("Retries of failed requests, with increasing waits"),
+        )
+        .arg(args::outfile_arg())
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
+    let outdir = Path::new(
+        args.get_one::<String>("outdir")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'outdir' argument"))?,
+    );
+    let retry_list = outdir.join(
+        args.get_one::<String>("retry_list")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'retry_list' argument"))?,
+    );
+    let outfile = args
+        .get_one::<String>("outfile")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?;
+    let parallel = (*args
+        .get_one::<usize>("parallel")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'parallel' argument"))?)
+    .max(1);
+    let retries: u32 = *args
+        .get_one("retries")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'retries' argument"))?;
+
+    let types: Vec<&String> = args
+        .get_many::<String>("type")
+        .map(Iterator::collect)
+        .unwrap_or_default();
+    let suffixes: Vec<&str> = FILE_TYPES
+        .iter()
+        .filter(|(name, _)| types.is_empty() || types.iter().any(|t| t == name))
+        .map(|(_, suffix)| *suffix)
+        .collect();
+
+    let mut assemblies = vec![];
+    for infile in args
+        .get_many::<String>("infiles")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'infiles' argument"))?
+    {
+        assemblies.extend(read_assemblies(infile)?);
+    }
+    std::fs::create_dir_all(outdir)?;
+
+    let conn = nwr::libs::download::HttpConnection::connect("")?
+        .with_retries(retries, Duration::from_secs(1));
+
+    // Workers take the next assembly until none is left
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![]);
+    std::thread::scope(|scope| {
+        for _ in 0..parallel.min(assemblies.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some(asm) = assemblies.get(i) else {
+                    break;
+                };
+                info!("==> {}\t{}", asm.species, asm.name);
+                let result = fetch_assembly(&conn, asm, outdir, &suffixes);
+                if let Err(err) = &result {
+                    warn!("{}: {err:#}", asm.name);
+                }
+                results
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .push((i, result));
+            });
+        }
+    });
+    let mut results = results
+        .into_inner()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    results.sort_by_key(|(i, _)| *i);
+
+    let mut writer = nwr::libs::io::writer(outfile)?;
+    let mut failed = vec![];
+    for (i, result) in results {
+        let asm = &assemblies[i];
+        match result {
+            Ok(files) => {
+                for file in files {
+                    writer.write_fmt(format_args!("{}\t{}\n", asm.name, file))?;
+                }
+            }
+            Err(_) => failed.push(asm.line.as_str()),
+        }
+    }
+    writer.finish()?;
+
+    if failed.is_empty() {
+        if retry_list.exists() {
+            std::fs::remove_file(&retry_list)?;
+        }
+        info!("All {} assemblies passed", assemblies.len());
+        return Ok(());
+    }
+
+    let mut writer = nwr::libs::io::writer(&retry_list.to_string_lossy())?;
+    for line in &failed {
+        writer.write_fmt(format_args!("{line}\n"))?;
+    }
+    writer.finish()?;
+    anyhow::bail!(
+        "{} of {} assemblies failed; run `nwr fetch {} --outdir {}` to retry them",
+        failed.len(),
+        assemblies.len(),
+        retry_list.display(),
+        outdir.display()
+    )
+}
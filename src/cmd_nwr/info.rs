@@ -10,32 +10,56 @@ pub fn make_subcommand() -> Command {
         .after_help(include_str!("../../docs/help/info.md"))
         .arg(args::terms_arg("Taxonomy ID(s) or scientific name(s)"))
         .arg(args::dir_arg())
+        .arg(args::taxonomy_arg())
+        .arg(args::within_arg())
         .arg(
             Arg::new("tsv")
                 .long("tsv")
                 .action(ArgAction::SetTrue)
                 .help("Output the results as TSV"),
         )
+        .arg(args::gc_arg())
+        .arg(args::host_arg())
+        .arg(args::type_material_arg())
+        .arg(args::resolved_arg(
+            "Output the original term next to the resolved taxon",
+        ))
         .arg(args::outfile_arg())
 }
 
 /// Command implementation.
 pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
     let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let taxonomy = args
+        .get_one::<String>("taxonomy")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'taxonomy' argument"))?;
+    let within = args.get_one::<String>("within").map(String::as_str);
     let terms: Vec<String> = args
         .get_many::<String>("terms")
         .ok_or_else(|| anyhow::anyhow!("No terms provided"))?
         .cloned()
         .collect();
     let is_tsv = args.get_flag("tsv");
+    let is_gc = args.get_flag("gc");
+    let is_host = args.get_flag("host");
+    let is_type_material = args.get_flag("type_material");
+    let is_resolved = args.get_flag("resolved");
 
     let mut writer = nwr::libs::io::writer(
         args.get_one::<String>("outfile")
             .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?,
     )?;
-    let conn = nwr::connect_txdb(&nwrdir)?;
+    let conn = nwr::connect_taxonomy(&nwrdir, taxonomy)?;
 
-    let ids = nwr::terms_to_tax_ids(&conn, &terms)?;
+    let ids = nwr::terms_to_tax_ids(
+        &conn,
+        &terms
+            .iter()
+            .map(|term| nwr::with_ancestor(term, within))
+            .collect::<Vec<_>>(),
+    )?;
 
     let nodes = nwr::get_taxon(&conn, &ids)?;
 
@@ -44,10 +68,60 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
             .delimiter(b'\t')
             .from_writer(writer);
 
-        wtr.write_record(["#tax_id", "sci_name", "rank", "division"])?;
-        for node in &nodes {
+        let mut header: Vec<&str> = vec!["tax_id", "sci_name", "rank", "division"];
+        if is_resolved {
+            header.insert(0, "term");
+        }
+        if is_gc {
+            header.extend(["gc_id", "gc_name", "mgc_id", "mgc_name"]);
+        }
+        if is_host {
+            header.push("host");
+        }
+        if is_type_material {
+            header.push("type_material");
+        }
+        let header: Vec<String> = header
+            .iter()
+            .enumerate()
+            .map(|(i, h)| {
+                if i == 0 {
+                    format!("#{h}")
+                } else {
+                    (*h).to_string()
+                }
+            })
+            .collect();
+        wtr.write_record(&header)?;
+        for (term, node) in terms.iter().zip(&nodes) {
             let sci_name = node.scientific_name().unwrap_or("Unknown");
-            wtr.serialize((node.tax_id, sci_name, &node.rank, &node.division))?;
+            let mut record: Vec<String> = vec![
+                node.tax_id.to_string(),
+                sci_name.to_string(),
+                node.rank.clone(),
+                node.division.clone(),
+            ];
+            if is_resolved {
+                record.insert(0, term.clone());
+            }
+            if is_gc {
+                record.extend([
+                    node.gc_id.to_string(),
+                    node.gc_name.clone().unwrap_or_default(),
+                    node.mgc_id.to_string(),
+                    node.mgc_name.clone().unwrap_or_default(),
+                ]);
+            }
+            if is_host {
+                record.push(nwr::get_hosts(&conn, node.tax_id)?.join(","));
+            }
+            if is_type_material {
+                record.push(nwr::format_type_material(&nwr::get_type_material(
+                    &conn,
+                    node.tax_id,
+                )?));
+            }
+            wtr.write_record(&record)?;
         }
         wtr.flush()?;
         let writer = wtr
@@ -55,11 +129,33 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
             .map_err(|e| anyhow::anyhow!("failed to flush TSV writer: {e}"))?;
         writer.finish()?;
     } else {
-        for (i, node) in nodes.iter().enumerate() {
+        for (i, (term, node)) in terms.iter().zip(&nodes).enumerate() {
             if i > 0 {
                 writer.write_all(b"\n")?;
             }
+            if is_resolved {
+                writer.write_fmt(format_args!("Query: {term}\n"))?;
+            }
             writer.write_fmt(format_args!("{node}"))?;
+
+            let materials = nwr::get_type_material(&conn, node.tax_id)?;
+            if !materials.is_empty() {
+                writer.write_fmt(format_args!("Type material:\n"))?;
+                for material in &materials {
+                    writer.write_fmt(format_args!(
+                        "* {} {}\n",
+                        material.material_type, material.identifier
+                    ))?;
+                }
+            }
+
+            let hosts = nwr::get_hosts(&conn, node.tax_id)?;
+            if !hosts.is_empty() {
+                writer.write_fmt(format_args!(
+                    "Potential hosts: {}.\n",
+                    hosts.join(", ")
+                ))?;
+            }
         }
         writer.flush()?;
         writer.finish()?;
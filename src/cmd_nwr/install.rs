@@ -0,0 +1,119 @@
+use super::args;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use log::info;
+
+use std::path::{Path, PathBuf};
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("install")
+        .about("Installs the databases from a bundle or a local mirror")
+        .after_help(include_str!("../../docs/help/install.md"))
+        .arg(
+            Arg::new("bundle")
+                .help(
+                    "A bundle written by `nwr bundle`, or a directory of the same files",
+                )
+                .required(true)
+                .num_args(1)
+                .index(1),
+        )
+        .arg(
+            Arg::new("ardb")
+                .long("ardb")
+                .action(ArgAction::SetTrue)
+                .help("Also build the assembly databases"),
+        )
+        .arg(args::dir_arg())
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
+    let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let _lock = nwr::libs::lock::lock_dir(&nwrdir)?;
+    let bundle = PathBuf::from(
+        args.get_one::<String>("bundle")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'bundle' argument"))?,
+    );
+    let is_ardb = args.get_flag("ardb");
+
+    if !bundle.exists() {
+        anyhow::bail!("{} doesn't exist", bundle.display());
+    }
+    std::fs::create_dir_all(&nwrdir)?;
+
+    // Unpack into a staging directory, so a bad bundle leaves nwrdir untouched
+    let staging = nwrdir.join(".bundle");
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    let result = stage(&bundle, &staging)
+        .and_then(|()| nwr::libs::bundle::verify_bundle(&staging))
+        .and_then(|names| {
+            for name in &names {
+                info!("Installing {name}");
+                std::fs::rename(staging.join(name), nwrdir.join(name))?;
+            }
+            Ok(names)
+        });
+    std::fs::remove_dir_all(&staging)?;
+    let names = result?;
+
+    let dir = nwrdir.to_string_lossy().to_string();
+    info!("==> nwr txdb");
+    super::txdb::execute(
+        &super::txdb::make_subcommand().try_get_matches_from(["txdb", "--dir", &dir])?,
+    )?;
+
+    if is_ardb {
+        if !names
+            .iter()
+            .any(|name| name == "assembly_summary_refseq.txt")
+        {
+            anyhow::bail!("The bundle has no assembly_summary_refseq.txt for --ardb");
+        }
+        info!("==> nwr ardb");
+        super::ardb::execute(
+            &super::ardb::make_subcommand()
+                .try_get_matches_from(["ardb", "--dir", &dir])?,
+        )?;
+        if names
+            .iter()
+            .any(|name| name == "assembly_summary_genbank.txt")
+        {
+            info!("==> nwr ardb --genbank");
+            super::ardb::execute(
+                &super::ardb::make_subcommand().try_get_matches_from([
+                    "ardb",
+                    "--genbank",
+                    "--dir",
+                    &dir,
+                ])?,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Put the bundle files into `staging`, unpacking a tarball or copying a mirror.
+fn stage(bundle: &Path, staging: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(staging)?;
+    if bundle.is_dir() {
+        for name in nwr::libs::bundle::BUNDLE_FILES
+            .iter()
+            .chain(std::iter::once(&nwr::libs::bundle::MANIFEST))
+        {
+            if bundle.join(name).is_file() {
+                std::fs::copy(bundle.join(name), staging.join(name))?;
+            }
+        }
+    } else {
+        info!("Unpacking {}", bundle.display());
+        nwr::libs::download::extract_taxdump(bundle, staging)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use super::args;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use log::warn;
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::Write;
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("lca")
+        .about("Outputs the lowest common ancestor of terms")
+        .after_help(include_str!("../../docs/help/lca.md"))
+        .arg(
+            Arg::new("terms")
+                .help("The NCBI Taxonomy ID or scientific name")
+                .num_args(1..)
+                .index(1),
+        )
+        .arg(args::dir_arg())
+        .arg(args::taxonomy_arg())
+        .arg(args::within_arg())
+        .arg(
+            Arg::new("file")
+                .long("file")
+                .short('f')
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .conflicts_with("terms")
+                .help("Input TSV filename(s). 'stdin' for standard input"),
+        )
+        .arg(args::column_arg())
+        .arg(
+            Arg::new("sep")
+                .long("sep")
+                .num_args(1)
+                .default_value(",")
+                .help("Delimiter of the terms in the column"),
+        )
+        .arg(
+            Arg::new("ignore_invalid")
+                .long("ignore-invalid")
+                .action(ArgAction::SetTrue)
+                .help("Skip terms that fail to resolve instead of raising errors"),
+        )
+        .arg(args::outfile_arg())
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
+    let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let taxonomy = args
+        .get_one::<String>("taxonomy")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'taxonomy' argument"))?;
+    let within = args.get_one::<String>("within").map(String::as_str);
+
+    let column: usize = *args
+        .get_one("column")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'column' argument"))?;
+    let sep = args
+        .get_one::<String>("sep")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'sep' argument"))?;
+    if sep.is_empty() {
+        anyhow::bail!("--sep must not be empty");
+    }
+    let is_ignore = args.get_flag("ignore_invalid");
+
+    let outfile = args
+        .get_one::<String>("outfile")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?;
+    let mut writer = nwr::libs::io::writer(outfile)?;
+
+    let conn = nwr::connect_taxonomy(&nwrdir, taxonomy)?;
+
+    // Lineages are shared by all rows; BLAST hits repeat a lot. Failed
+    // terms are cached as `None`.
+    let mut cache: HashMap<String, Option<Vec<nwr::Taxon>>> = HashMap::new();
+
+    if let Some(terms) = args.get_many::<String>("terms") {
+        let terms: Vec<String> = terms.cloned().collect();
+        cache_lineages(&conn, &terms, within, is_ignore, &mut cache)?;
+        let lca = find_lca(&terms, &cache)
+            .ok_or_else(|| anyhow::anyhow!("No valid terms to compute the LCA"))?;
+        writer.write_fmt(format_args!("{lca}\n"))?;
+    } else {
+        let files: Vec<String> = args
+            .get_many::<String>("file")
+            .map_or_else(|| vec!["stdin".to_string()], |v| v.cloned().collect());
+
+        for file in &files {
+            let reader = nwr::libs::io::reader(file)?;
+
+            for (line_idx, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                // Lines start with "#"
+                if line.starts_with('#') {
+                    writer.write_fmt(format_args!(
+                        "{line}\tlca_id\tlca_name\tlca_rank\n"
+                    ))?;
+                    continue;
+                }
+
+                let fields: Vec<&str> = line.split('\t').collect();
+                let cell = fields.get(column - 1).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "{}:{}: Column {} out of range (line has {} columns)",
+                        file,
+                        line_idx + 1,
+                        column,
+                        fields.len()
+                    )
+                })?;
+                let terms: Vec<String> = cell
+                    .split(sep.as_str())
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .map(std::string::ToString::to_string)
+                    .collect();
+
+                cache_lineages(&conn, &terms, within, is_ignore, &mut cache).map_err(
+                    |err| anyhow::anyhow!("{}:{}: {err}", file, line_idx + 1),
+                )?;
+
+                // Rows without any valid term get the same sentinel as `find_rank`
+                let lca =
+                    find_lca(&terms, &cache).unwrap_or_else(|| "0\tNA\tNA".to_string());
+                writer.write_fmt(format_args!("{line}\t{lca}\n"))?;
+            }
+        }
+    }
+    writer.flush()?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Look up and cache the lineages of terms.
+///
+/// Names are resolved under the `within` ancestor when given. Failed terms are
+/// reported as errors, or skipped with a warning when `is_ignore` is set.
+fn cache_lineages(
+    conn: &rusqlite::Connection,
+    terms: &[String],
+    within: Option<&str>,
+    is_ignore: bool,
+    cache: &mut HashMap<String, Option<Vec<nwr::Taxon>>>,
+) -> anyhow::Result<()> {
+    for term in terms {
+        if cache.contains_key(term) {
+            continue;
+        }
+        let lineage = match nwr::term_to_tax_id(conn, &nwr::with_ancestor(term, within))
+            .and_then(|id| nwr::get_lineage(conn, id))
+        {
+            Ok(lineage) => Some(lineage),
+            Err(err) => {
+                if !is_ignore {
+                    anyhow::bail!("Error converting term '{term}': {err}");
+                }
+                warn!("Error converting term '{term}': {err}");
+                None
+            }
+        };
+        cache.insert(term.clone(), lineage);
+    }
+    Ok(())
+}
+
+/// LCA of cached terms as `tax_id`, scientific name and rank.
+fn find_lca(
+    terms: &[String],
+    cache: &HashMap<String, Option<Vec<nwr::Taxon>>>,
+) -> Option<String> {
+    let lineages: Vec<&[nwr::Taxon]> = terms
+        .iter()
+        .filter_map(|t| cache.get(t).and_then(Option::as_deref))
+        .collect();
+    let taxon = nwr::find_lca(&lineages)?;
+
+    Some(format!(
+        "{}\t{}\t{}",
+        taxon.tax_id,
+        taxon.scientific_name().unwrap_or("NA"),
+        taxon.rank
+    ))
+}
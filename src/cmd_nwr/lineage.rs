@@ -1,5 +1,5 @@
 use super::args;
-use clap::{Arg, ArgMatches, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use std::io::Write;
 
 /// Create clap subcommand arguments.
@@ -16,31 +16,68 @@ pub fn make_subcommand() -> Command {
                 .index(1),
         )
         .arg(args::dir_arg())
+        .arg(args::taxonomy_arg())
+        .arg(args::within_arg())
+        .arg(
+            Arg::new("ranked")
+                .long("ranked")
+                .action(ArgAction::SetTrue)
+                .help("Output only the standard ranks read from `rankedlineage`"),
+        )
+        .arg(args::resolved_arg(
+            "Prepend the original term to each output line",
+        ))
         .arg(args::outfile_arg())
 }
 
 /// Command implementation.
 pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
     let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let taxonomy = args
+        .get_one::<String>("taxonomy")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'taxonomy' argument"))?;
+    let within = args.get_one::<String>("within").map(String::as_str);
     let term = args
         .get_one::<String>("term")
         .ok_or_else(|| anyhow::anyhow!("No term provided"))?;
+    let is_ranked = args.get_flag("ranked");
+    let is_resolved = args.get_flag("resolved");
 
     let mut writer = nwr::libs::io::writer(
         args.get_one::<String>("outfile")
             .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?,
     )?;
-    let conn = nwr::connect_txdb(&nwrdir)?;
+    let conn = nwr::connect_taxonomy(&nwrdir, taxonomy)?;
 
-    let id = nwr::term_to_tax_id(&conn, term)?;
-    let lineage = nwr::get_lineage(&conn, id)?;
+    let id = nwr::term_to_tax_id(&conn, &nwr::with_ancestor(term, within))?;
+    if is_ranked {
+        let ranked = nwr::get_ranked_lineage(&conn, id)?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "No ranked lineage for tax_id {id}; import `new_taxdump` with `nwr txdb`"
+            )
+        })?;
+        // Same order as the full lineage: from the top rank down.
+        for (rank, sci_name) in ranked.iter().rev() {
+            if is_resolved {
+                writer.write_fmt(format_args!("{term}\t"))?;
+            }
+            writer.write_fmt(format_args!("{rank}\t{sci_name}\n"))?;
+        }
+    } else {
+        let lineage = nwr::get_lineage(&conn, id)?;
 
-    for node in &lineage {
-        let sci_name = node.scientific_name().unwrap_or("Unknown");
-        writer.write_fmt(format_args!(
-            "{}\t{}\t{}\n",
-            node.rank, sci_name, node.tax_id
-        ))?;
+        for node in &lineage {
+            let sci_name = node.scientific_name().unwrap_or("Unknown");
+            if is_resolved {
+                writer.write_fmt(format_args!("{term}\t"))?;
+            }
+            writer.write_fmt(format_args!(
+                "{}\t{}\t{}\n",
+                node.rank, sci_name, node.tax_id
+            ))?;
+        }
     }
     writer.flush()?;
     writer.finish()?;
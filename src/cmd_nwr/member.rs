@@ -10,6 +10,8 @@ pub fn make_subcommand() -> Command {
         .after_help(include_str!("../../docs/help/member.md"))
         .arg(args::terms_arg("The ancestor(s)"))
         .arg(args::dir_arg())
+        .arg(args::taxonomy_arg())
+        .arg(args::within_arg())
         .arg(args::rank_arg())
         .arg(
             Arg::new("env")
@@ -23,6 +25,10 @@ pub fn make_subcommand() -> Command {
 /// Command implementation.
 pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let taxonomy = args
+        .get_one::<String>("taxonomy")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'taxonomy' argument"))?;
+    let within = args.get_one::<String>("within").map(String::as_str);
 
     let terms: Vec<String> = args
         .get_many::<String>("terms")
@@ -41,7 +47,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         args.get_one::<String>("outfile")
             .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?,
     )?;
-    let conn = nwr::connect_txdb(&nwrdir)?;
+    let conn = nwr::connect_taxonomy(&nwrdir, taxonomy)?;
 
     let mut tsv_wtr = csv::WriterBuilder::new()
         .delimiter(b'\t')
@@ -50,7 +56,13 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
 
     let rank_set: HashSet<String> = ranks.into_iter().collect();
 
-    let ancestor_ids = nwr::terms_to_tax_ids(&conn, &terms)?;
+    let ancestor_ids = nwr::terms_to_tax_ids(
+        &conn,
+        &terms
+            .iter()
+            .map(|term| nwr::with_ancestor(term, within))
+            .collect::<Vec<_>>(),
+    )?;
 
     // Collect all descendant IDs from every ancestor, deduplicating across
     // overlapping subtrees so each taxon is fetched from the database once.
@@ -0,0 +1,235 @@
+use super::args;
+use clap::{Arg, ArgMatches, Command};
+use log::{info, warn};
+use nwr::libs::db::{table_exists, DbKind, DATABASES};
+
+use std::path::{Path, PathBuf};
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("migrate")
+        .about("Upgrades databases built by older releases")
+        .after_help(include_str!("../../docs/help/migrate.md"))
+        .arg(
+            Arg::new("databases")
+                .help("Other databases to upgrade, e.g. seq.sqlite")
+                .num_args(0..)
+                .index(1),
+        )
+        .arg(args::dir_arg())
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
+    let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let _lock = nwr::libs::lock::lock_dir(&nwrdir)?;
+    let mut databases: Vec<PathBuf> = DATABASES
+        .iter()
+        .map(|db| nwrdir.join(db.name))
+        .filter(|path| path.exists())
+        .collect();
+    if let Some(paths) = args.get_many::<String>("databases") {
+        for path in paths {
+            let path = PathBuf::from(path);
+            if !path.exists() {
+                anyhow::bail!("{} doesn't exist", path.display());
+            }
+            databases.push(path);
+        }
+    }
+    if databases.is_empty() {
+        warn!("No databases found in {}", nwrdir.display());
+    }
+
+    // Keep going, so that one run lists every database to rebuild
+    let mut failed = vec![];
+    for path in &databases {
+        if let Err(err) = migrate(path) {
+            warn!("{err}");
+            failed.push(path.display().to_string());
+        }
+    }
+    if !failed.is_empty() {
+        anyhow::bail!("Can't upgrade {}", failed.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Tables and columns of imported data, which can't be recreated in place.
+const TX_COLUMNS: [(&str, &[&str]); 6] = [
+    (
+        "node",
+        &["gc_id", "gc_inherited", "mgc_id", "mgc_inherited"],
+    ),
+    ("name", &["name_class"]),
+    ("division", &["division"]),
+    ("gencode", &["cde", "starts"]),
+    ("merged", &["new_tax_id"]),
+    ("delnodes", &["tax_id"]),
+];
+const AR_COLUMNS: [(&str, &[&str]); 1] = [(
+    "ar",
+    &[
+        "tax_id",
+        "organism_name",
+        "assembly_accession",
+        "ftp_path",
+        "species_id",
+        "genus_id",
+        "family_id",
+    ],
+)];
+const SEQ_COLUMNS: [(&str, &[&str]); 6] = [
+    ("rank", &["name"]),
+    ("asm", &["rank_id"]),
+    ("seq", &["size", "anno"]),
+    ("rep", &["f1"]),
+    ("rep_seq", &["rep_id"]),
+    ("asm_seq", &["asm_id"]),
+];
+
+/// Upgrade one database in place.
+fn migrate(path: &Path) -> anyhow::Result<()> {
+    let conn = rusqlite::Connection::open(path)?;
+    let version = nwr::libs::db::schema_version(&conn)?;
+
+    let kind = if table_exists(&conn, "node")? {
+        DbKind::Taxonomy
+    } else if table_exists(&conn, "ar")? {
+        DbKind::Assembly
+    } else if table_exists(&conn, "asm_seq")? {
+        DbKind::Seq
+    } else {
+        anyhow::bail!("{} isn't a database of nwr", path.display());
+    };
+    let current = kind.schema_version();
+    let (columns, rebuild): (&[(&str, &[&str])], String) = match kind {
+        DbKind::Taxonomy => (&TX_COLUMNS, taxonomy_import(&conn, path)?),
+        DbKind::Assembly => (&AR_COLUMNS, rebuild_command(path, "nwr ardb")),
+        DbKind::Seq => (&SEQ_COLUMNS, "nwr seqdb --init".to_string()),
+    };
+
+    if version == current {
+        info!("{}: schema version {version}, up to date", path.display());
+        return Ok(());
+    }
+    if version > current {
+        anyhow::bail!(
+            "{}: schema version {version} is newer than {current} of this nwr; upgrade nwr",
+            path.display()
+        );
+    }
+
+    let missing = missing_columns(&conn, columns)?;
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "{}: missing {}; re-run `{rebuild}`",
+            path.display(),
+            missing.join(", ")
+        );
+    }
+
+    info!(
+        "==> Upgrading {} from schema version {version} to {current}",
+        path.display()
+    );
+    // Each step can be re-run, and the version is stamped last, so an
+    // interrupted upgrade is simply run again
+    match kind {
+        DbKind::Taxonomy => upgrade_taxonomy(&conn)?,
+        DbKind::Assembly => {
+            for index in super::ardb::AR_INDEXES {
+                let column = index.trim_start_matches("idx_ar_");
+                conn.execute_batch(&format!(
+                    "CREATE INDEX IF NOT EXISTS {index} ON ar({column});"
+                ))?;
+            }
+        }
+        DbKind::Seq => {}
+    }
+    nwr::libs::db::set_schema_version(&conn, current)?;
+
+    Ok(())
+}
+
+/// The import that rebuilds a taxonomy database.
+fn taxonomy_import(conn: &rusqlite::Connection, path: &Path) -> anyhow::Result<String> {
+    // Old databases may lack `node.comment`
+    let is_custom: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM node WHERE comment = 'custom')",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+    let import = if table_exists(conn, "genome")? {
+        "nwr txdb --gtdb".to_string()
+    } else if is_custom {
+        "nwr custom".to_string()
+    } else {
+        rebuild_command(path, "nwr txdb")
+    };
+    Ok(import)
+}
+
+/// The command that rebuilds the database at `path`, by its file name, or
+/// `default` for databases not kept in the nwr directory.
+fn rebuild_command(path: &Path, default: &str) -> String {
+    DATABASES
+        .iter()
+        .find(|db| path.file_name().is_some_and(|name| name == db.name))
+        .map_or(default, |db| db.rebuild)
+        .to_string()
+}
+
+/// Add the search index, the `new_taxdump` tables, indexes and lineages.
+fn upgrade_taxonomy(conn: &rusqlite::Connection) -> anyhow::Result<()> {
+    if !table_exists(conn, "name_fts")? {
+        info!("Adding the search index");
+        conn.execute_batch(super::txdb::DDL_NAME_FTS)?;
+    }
+    if !table_exists(conn, "rankedlineage")? {
+        info!("Adding the new_taxdump tables");
+        conn.execute_batch(super::txdb::DDL_NEW_TX)?;
+    }
+    info!("Creating indexes");
+    super::txdb::create_indexes(conn)?;
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_typematerial_tax_id ON typematerial(tax_id);",
+    )?;
+    if table_exists(conn, "genome")? {
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_genome_tax_id ON genome(tax_id);",
+        )?;
+    }
+    super::txdb::build_lineage(conn)?;
+    Ok(())
+}
+
+/// `table.column` pairs absent from the database.
+fn missing_columns(
+    conn: &rusqlite::Connection,
+    tables: &[(&str, &[&str])],
+) -> anyhow::Result<Vec<String>> {
+    let mut missing = vec![];
+    for (table, columns) in tables {
+        if !table_exists(conn, table)? {
+            missing.push(format!("table {table}"));
+            continue;
+        }
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+        let present: Vec<String> = stmt
+            .query_map([], |row| row.get(1))?
+            .collect::<Result<_, _>>()?;
+        for column in *columns {
+            if !present.iter().any(|c| c == column) {
+                missing.push(format!("{table}.{column}"));
+            }
+        }
+    }
+    Ok(missing)
+}
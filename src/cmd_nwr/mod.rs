@@ -12,22 +12,46 @@ pub mod append;
 pub mod ardb;
 /// Shared clap argument definitions.
 pub mod args;
+/// Select assemblies under taxa.
+pub mod assembly;
+/// Pack the downloaded files for offline installs.
+pub mod bundle;
 /// Find common ancestors of taxa.
 pub mod common;
+/// Build taxonomy databases from lineage tables.
+pub mod custom;
+/// Compare two taxonomy releases.
+pub mod diff;
+/// Check the nwr directory for problems.
+pub mod doctor;
 /// Download NCBI taxonomy and assembly reports.
 pub mod download;
+/// Download the genome files of assemblies.
+pub mod fetch;
 /// Display taxonomy information for terms.
 pub mod info;
+/// Install the databases from a bundle or a local mirror.
+pub mod install;
 /// Print a knowledge base TSV for assemblies.
 pub mod kb;
+/// Find the lowest common ancestor of taxa.
+pub mod lca;
 /// Output taxonomic lineages.
 pub mod lineage;
 /// List members of a taxonomic group.
 pub mod member;
+/// Upgrade databases built by older releases.
+pub mod migrate;
+/// Append formatted lineage strings to input files.
+pub mod reformat;
 /// Include or exclude rows by taxonomy.
 pub mod restrict;
+/// Search names with fuzzy, prefix or substring matching.
+pub mod search;
 /// Build and populate the sequence metadata database.
 pub mod seqdb;
+/// Show the sources and versions of the databases.
+pub mod status;
 /// Generate phylogenomic pipeline templates.
 pub mod template;
 /// Build the NCBI taxonomy database.
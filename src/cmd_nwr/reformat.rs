@@ -0,0 +1,145 @@
+use super::args;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use log::warn;
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::Write;
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("reformat")
+        .about("Appends formatted lineage strings to a TSV file")
+        .after_help(include_str!("../../docs/help/reformat.md"))
+        .arg(args::infiles_arg(
+            "Input TSV file(s) to process. Use 'stdin' for standard input",
+        ))
+        .arg(args::dir_arg())
+        .arg(args::taxonomy_arg())
+        .arg(args::within_arg())
+        .arg(args::column_arg())
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .short('f')
+                .num_args(1)
+                .default_value(nwr::DEFAULT_LINEAGE_FORMAT)
+                .help("Lineage template with {rank} placeholders"),
+        )
+        .arg(
+            Arg::new("fill").long("fill").num_args(1).help(
+                "Fill-in value for missing ranks; {rank} and {parent} are expanded",
+            ),
+        )
+        .arg(
+            Arg::new("prefix")
+                .long("prefix")
+                .action(ArgAction::SetTrue)
+                .help("Add rank prefixes like p__ to the values"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .action(ArgAction::SetTrue)
+                .help("Treat invalid taxonomy terms as errors instead of skipping them"),
+        )
+        .arg(args::outfile_arg())
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
+    let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let taxonomy = args
+        .get_one::<String>("taxonomy")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'taxonomy' argument"))?;
+    let within = args.get_one::<String>("within").map(String::as_str);
+
+    let column: usize = *args
+        .get_one("column")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'column' argument"))?;
+    let template = args
+        .get_one::<String>("format")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'format' argument"))?;
+    let fill = args.get_one::<String>("fill").map(String::as_str);
+    let is_prefix = args.get_flag("prefix");
+    let is_strict = args.get_flag("strict");
+
+    let infiles: Vec<String> = args
+        .get_many::<String>("infiles")
+        .ok_or_else(|| anyhow::anyhow!("No input files provided"))?
+        .cloned()
+        .collect();
+
+    let outfile = args
+        .get_one::<String>("outfile")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?;
+    let mut writer = nwr::libs::io::writer(outfile)?;
+
+    let conn = nwr::connect_taxonomy(&nwrdir, taxonomy)?;
+
+    // Cache formatted strings by term; failed terms are cached as `None`.
+    let mut cache: HashMap<String, Option<String>> = HashMap::new();
+
+    for infile in &infiles {
+        let reader = nwr::libs::io::reader(infile)?;
+
+        for (line_idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Lines start with "#"
+            if line.starts_with('#') {
+                writer.write_fmt(format_args!("{line}\tlineage\n"))?;
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            let term = *fields.get(column - 1).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{}:{}: Column {} out of range (line has {} columns)",
+                    infile,
+                    line_idx + 1,
+                    column,
+                    fields.len()
+                )
+            })?;
+
+            if !cache.contains_key(term) {
+                let formatted =
+                    match nwr::term_to_tax_id(&conn, &nwr::with_ancestor(term, within))
+                        .and_then(|id| nwr::get_lineage(&conn, id))
+                    {
+                        Ok(lineage) => Some(nwr::format_lineage(
+                            &lineage, template, fill, is_prefix,
+                        )),
+                        Err(err) => {
+                            if is_strict {
+                                anyhow::bail!(
+                                    "{}:{}: Error converting term '{term}': {err}",
+                                    infile,
+                                    line_idx + 1
+                                );
+                            }
+                            warn!("Error converting term '{term}': {err}");
+                            None
+                        }
+                    };
+                cache.insert(term.to_string(), formatted);
+            }
+
+            // Failed terms were reported when first seen; skip their rows
+            if let Some(Some(formatted)) = cache.get(term) {
+                writer.write_fmt(format_args!("{line}\t{formatted}\n"))?;
+            }
+        }
+    }
+    writer.flush()?;
+    writer.finish()?;
+
+    Ok(())
+}
@@ -14,6 +14,8 @@ pub fn make_subcommand() -> Command {
         .after_help(include_str!("../../docs/help/restrict.md"))
         .arg(args::terms_arg("The ancestor(s)"))
         .arg(args::dir_arg())
+        .arg(args::taxonomy_arg())
+        .arg(args::within_arg())
         .arg(
             Arg::new("file")
                 .long("file")
@@ -37,6 +39,9 @@ pub fn make_subcommand() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Treat invalid taxonomy terms as errors instead of skipping them"),
         )
+        .arg(args::resolved_arg(
+            "Append the resolved tax ID of each input term",
+        ))
         .arg(args::outfile_arg())
 }
 
@@ -45,6 +50,10 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     nwr::libs::io::init_logger();
 
     let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let taxonomy = args
+        .get_one::<String>("taxonomy")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'taxonomy' argument"))?;
+    let within = args.get_one::<String>("within").map(String::as_str);
 
     let column: usize = *args
         .get_one("column")
@@ -67,14 +76,15 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         .get_one::<String>("outfile")
         .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?;
     let is_strict = args.get_flag("strict");
+    let is_resolved = args.get_flag("resolved");
 
     let mut writer = nwr::libs::io::writer(outfile)?;
 
-    let conn = nwr::connect_txdb(&nwrdir)?;
+    let conn = nwr::connect_taxonomy(&nwrdir, taxonomy)?;
 
     let mut id_set = HashSet::new();
     for term in &terms {
-        let id = nwr::term_to_tax_id(&conn, term)?;
+        let id = nwr::term_to_tax_id(&conn, &nwr::with_ancestor(term, within))?;
         let descendents = nwr::get_all_descendent(&conn, id)?;
         id_set.extend(descendents);
     }
@@ -95,7 +105,11 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
 
             // Always output lines start with "#"
             if line.starts_with('#') {
-                writer.write_fmt(format_args!("{line}\n"))?;
+                if is_resolved {
+                    writer.write_fmt(format_args!("{line}\tresolved_id\n"))?;
+                } else {
+                    writer.write_fmt(format_args!("{line}\n"))?;
+                }
                 continue;
             }
 
@@ -122,28 +136,34 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
             }
             let id = match term_cache.get(*term) {
                 Some(&id) => id,
-                None => match nwr::term_to_tax_id(&conn, term) {
-                    Ok(x) => {
-                        term_cache.insert((*term).to_string(), x);
-                        x
-                    }
-                    Err(err) => {
-                        if is_strict {
-                            anyhow::bail!(
-                                "{}:{}: Error converting term '{term}': {err}",
-                                infile,
-                                line_idx + 1
-                            );
+                None => {
+                    match nwr::term_to_tax_id(&conn, &nwr::with_ancestor(term, within)) {
+                        Ok(x) => {
+                            term_cache.insert((*term).to_string(), x);
+                            x
+                        }
+                        Err(err) => {
+                            if is_strict {
+                                anyhow::bail!(
+                                    "{}:{}: Error converting term '{term}': {err}",
+                                    infile,
+                                    line_idx + 1
+                                );
+                            }
+                            warn!("Error converting term '{term}': {err}");
+                            term_failed.insert((*term).to_string());
+                            continue;
                         }
-                        warn!("Error converting term '{term}': {err}");
-                        term_failed.insert((*term).to_string());
-                        continue;
                     }
-                },
+                }
             };
 
             if is_exclude ^ id_set.contains(&id) {
-                writer.write_fmt(format_args!("{line}\n"))?;
+                if is_resolved {
+                    writer.write_fmt(format_args!("{line}\t{id}\n"))?;
+                } else {
+                    writer.write_fmt(format_args!("{line}\n"))?;
+                }
             }
         }
     }
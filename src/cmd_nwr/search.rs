@@ -0,0 +1,93 @@
+use super::args;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::io::Write;
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("search")
+        .about("Searches scientific names with fuzzy, prefix or substring matching")
+        .after_help(include_str!("../../docs/help/search.md"))
+        .arg(args::terms_arg("The name(s) to search for"))
+        .arg(args::dir_arg())
+        .arg(args::taxonomy_arg())
+        .arg(
+            Arg::new("prefix")
+                .long("prefix")
+                .action(ArgAction::SetTrue)
+                .help("Match names starting with the term"),
+        )
+        .arg(
+            Arg::new("substring")
+                .long("substring")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("prefix")
+                .help("Match names containing the term"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .short('n')
+                .num_args(1)
+                .default_value("10")
+                .value_parser(clap::value_parser!(usize))
+                .help("Maximum number of matches per term"),
+        )
+        .arg(args::outfile_arg())
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let taxonomy = args
+        .get_one::<String>("taxonomy")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'taxonomy' argument"))?;
+    let terms: Vec<String> = args
+        .get_many::<String>("terms")
+        .ok_or_else(|| anyhow::anyhow!("No terms provided"))?
+        .cloned()
+        .collect();
+
+    let mode = if args.get_flag("prefix") {
+        nwr::SearchMode::Prefix
+    } else if args.get_flag("substring") {
+        nwr::SearchMode::Substring
+    } else {
+        nwr::SearchMode::Fuzzy
+    };
+    let limit: usize = *args
+        .get_one("limit")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'limit' argument"))?;
+
+    let mut writer = nwr::libs::io::writer(
+        args.get_one::<String>("outfile")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?,
+    )?;
+    let conn = nwr::connect_taxonomy(&nwrdir, taxonomy)?;
+
+    writer.write_fmt(format_args!(
+        "#term\tname\tname_class\ttax_id\tsci_name\trank\tdistance\n"
+    ))?;
+    for term in &terms {
+        let matches = nwr::search_names(&conn, term, mode, limit)?;
+        let ids: Vec<i64> = matches.iter().map(|m| m.tax_id).collect();
+        let taxa = nwr::get_taxon(&conn, &ids)?;
+
+        for (m, taxon) in matches.iter().zip(taxa.iter()) {
+            writer.write_fmt(format_args!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                term,
+                m.name,
+                m.name_class,
+                m.tax_id,
+                taxon.scientific_name().unwrap_or("Unknown"),
+                taxon.rank,
+                m.distance
+            ))?;
+        }
+    }
+    writer.flush()?;
+    writer.finish()?;
+
+    Ok(())
+}
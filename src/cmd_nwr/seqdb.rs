@@ -4,11 +4,15 @@ use log::info;
 use std::fs::File;
 use std::path::PathBuf;
 
+use nwr::libs::db::{Meta, SEQ_SCHEMA_VERSION};
 use nwr::libs::seqdb::{
     insert_anno, insert_asmseq, insert_clust, insert_rep, insert_size, insert_strain,
     DDL_SEQ, VALID_REP_FIELDS,
 };
 
+/// Tables of `seq.sqlite` whose row counts go to `meta`.
+const SEQ_TABLES: [&str; 6] = ["rank", "asm", "seq", "rep", "rep_seq", "asm_seq"];
+
 /// Resolve an optional file argument.
 ///
 /// If the argument was not provided, returns `None`.
@@ -148,7 +152,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     }
 
     info!("==> Opening database `{}`", db.display());
-    let conn = rusqlite::Connection::open(db)?;
+    let conn = rusqlite::Connection::open(&db)?;
     nwr::libs::db::apply_import_pragmas(&conn)?;
 
     if is_init {
@@ -168,6 +172,9 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
             "seq.sqlite is empty or missing required tables; use --init to create them"
         );
     }
+    if !is_init {
+        nwr::libs::db::check_schema_version(&conn, &db, SEQ_SCHEMA_VERSION)?;
+    }
 
     load_file(
         opt_strain.as_ref(),
@@ -195,5 +202,17 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         insert_rep(&dmp, rep_field, rep_path, &conn)?;
     }
 
+    // Sources accumulate over runs; rows and the build time are the latest
+    let mut meta = Meta::new(SEQ_SCHEMA_VERSION);
+    for path in [&opt_strain, &opt_size, &opt_clust, &opt_anno, &opt_asmseq]
+        .into_iter()
+        .flatten()
+        .chain(opt_rep.as_ref().map(|(_, path)| path))
+    {
+        meta.source(path)?;
+    }
+    meta.rows(&conn, &SEQ_TABLES)?;
+    meta.write(&conn)?;
+
     Ok(())
 }
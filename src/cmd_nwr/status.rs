@@ -0,0 +1,74 @@
+use super::args;
+use clap::{Arg, ArgMatches, Command};
+use log::warn;
+
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Create clap subcommand arguments.
+#[must_use]
+pub fn make_subcommand() -> Command {
+    Command::new("status")
+        .about("Shows the sources and versions of the databases")
+        .after_help(include_str!("../../docs/help/status.md"))
+        .arg(
+            Arg::new("databases")
+                .help("Other databases to show, e.g. seq.sqlite")
+                .num_args(0..)
+                .index(1),
+        )
+        .arg(args::dir_arg())
+        .arg(args::outfile_arg())
+}
+
+/// Command implementation.
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    nwr::libs::io::init_logger();
+
+    let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let _lock = nwr::libs::lock::read_lock(&nwrdir)?;
+    let outfile = args
+        .get_one::<String>("outfile")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'outfile' argument"))?;
+
+    let mut databases: Vec<PathBuf> = nwr::libs::db::DATABASES
+        .iter()
+        .map(|db| nwrdir.join(db.name))
+        .filter(|path| path.exists())
+        .collect();
+    if let Some(paths) = args.get_many::<String>("databases") {
+        for path in paths {
+            let path = PathBuf::from(path);
+            if !path.exists() {
+                anyhow::bail!("{} doesn't exist", path.display());
+            }
+            databases.push(path);
+        }
+    }
+    if databases.is_empty() {
+        warn!("No databases found in {}", nwrdir.display());
+    }
+
+    let mut writer = nwr::libs::io::writer(outfile)?;
+    writer.write_fmt(format_args!("#database\tkey\tvalue\n"))?;
+    for path in &databases {
+        let conn = rusqlite::Connection::open_with_flags(
+            path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )?;
+        let entries = nwr::libs::db::read_meta(&conn)?;
+        if entries.is_empty() {
+            warn!(
+                "{} has no metadata; rebuild it to record its sources",
+                path.display()
+            );
+        }
+        for (key, value) in entries {
+            writer.write_fmt(format_args!("{}\t{key}\t{value}\n", path.display()))?;
+        }
+    }
+    writer.flush()?;
+    writer.finish()?;
+
+    Ok(())
+}
@@ -449,13 +449,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
             let species_ = nwr::libs::template::validate_shell_safe(&species_formatted)
                 .map_err(|e| anyhow::anyhow!("{}:{}: {}", infile, line_num + 1, e))?;
 
-            let level = match fields[4] {
-                "Complete Genome" => nwr::libs::template::LEVEL_COMPLETE_GENOME,
-                "Chromosome" => nwr::libs::template::LEVEL_CHROMOSOME,
-                "Scaffold" => nwr::libs::template::LEVEL_SCAFFOLD,
-                "Contig" => nwr::libs::template::LEVEL_CONTIG,
-                _ => nwr::libs::template::LEVEL_OTHER,
-            };
+            let level = nwr::libs::template::level_code(fields[4]);
 
             // Warn once about duplicate strain names across all name-keyed
             // stages (ass, mh, count, pro) so users learn about duplicates
@@ -556,16 +550,9 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         nwr::libs::template::render_shell_script(
             &mut tera,
             &context,
-            include_str!("../../templates/ass_aria2.tera.sh"),
-            "ASSEMBLY",
-            "aria2.sh",
-        )?;
-        nwr::libs::template::render_shell_script(
-            &mut tera,
-            &context,
-            include_str!("../../templates/ass_check.tera.sh"),
+            include_str!("../../templates/ass_fetch.tera.sh"),
             "ASSEMBLY",
-            "check.sh",
+            "fetch.sh",
         )?;
         nwr::libs::template::render_shell_script(
             &mut tera,
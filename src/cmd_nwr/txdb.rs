@@ -1,14 +1,25 @@
 use super::args;
-use clap::{ArgMatches, Command};
-use log::{debug, info};
+use clap::{Arg, ArgMatches, Command};
+use log::{debug, info, warn};
 
+use nwr::libs::db::{Meta, StagedDb};
+use nwr::libs::download::{
+    get_download_paths, read_taxdump_entries, taxdump_entries, NEW_TAXDUMP_FILES,
+};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
+use std::io::{BufRead, Read};
+use std::path::{Path, PathBuf};
 
 /// DDL for the NCBI taxonomy `SQLite` database.
 static DDL_TX: &str = r"
+DROP TABLE IF EXISTS name_fts;
 DROP TABLE IF EXISTS division;
 DROP TABLE IF EXISTS node;
 DROP TABLE IF EXISTS name;
+DROP TABLE IF EXISTS merged;
+DROP TABLE IF EXISTS delnodes;
+DROP TABLE IF EXISTS gencode;
 
 CREATE TABLE division (
     id       INTEGER      NOT NULL
@@ -22,6 +33,10 @@ CREATE TABLE node (
     parent_tax_id INTEGER,
     rank          VARCHAR (25) NOT NULL,
     division_id   INTEGER      NOT NULL,
+    gc_id         INTEGER      NOT NULL,
+    gc_inherited  INTEGER      NOT NULL,
+    mgc_id        INTEGER      NOT NULL,
+    mgc_inherited INTEGER      NOT NULL,
     comment       TEXT,
     FOREIGN KEY (
         division_id
@@ -36,8 +51,93 @@ CREATE TABLE name (
     name       VARCHAR (50) NOT NULL,
     name_class VARCHAR (50) NOT NULL
 );
+
+CREATE TABLE merged (
+    old_tax_id INTEGER NOT NULL
+                       PRIMARY KEY,
+    new_tax_id INTEGER NOT NULL
+);
+
+CREATE TABLE delnodes (
+    tax_id INTEGER NOT NULL
+                   PRIMARY KEY
+);
+
+CREATE TABLE gencode (
+    id           INTEGER       NOT NULL
+                               PRIMARY KEY,
+    abbreviation VARCHAR (50)  NOT NULL,
+    name         VARCHAR (200) NOT NULL,
+    cde          VARCHAR (64)  NOT NULL,
+    starts       VARCHAR (64)  NOT NULL
+);
+";
+
+/// DDL for the trigram index of names, used for case-insensitive, substring and
+/// fuzzy searches.
+pub static DDL_NAME_FTS: &str = r"
+CREATE VIRTUAL TABLE name_fts USING fts5(
+    name,
+    content='name',
+    content_rowid='id',
+    tokenize='trigram'
+);
 ";
 
+/// DDL for the optional tables imported from `new_taxdump`.
+///
+/// The tables are always created so the schema is stable; they stay empty when
+/// the corresponding .dmp files are absent.
+pub static DDL_NEW_TX: &str = r#"
+DROP TABLE IF EXISTS rankedlineage;
+DROP TABLE IF EXISTS fullnamelineage;
+DROP TABLE IF EXISTS taxidlineage;
+DROP TABLE IF EXISTS typematerial;
+DROP TABLE IF EXISTS host;
+
+CREATE TABLE rankedlineage (
+    tax_id       INTEGER       NOT NULL
+                               PRIMARY KEY,
+    tax_name     VARCHAR (200) NOT NULL,
+    species      VARCHAR (200),
+    genus        VARCHAR (200),
+    family       VARCHAR (200),
+    "order"      VARCHAR (200),
+    class        VARCHAR (200),
+    phylum       VARCHAR (200),
+    kingdom      VARCHAR (200),
+    superkingdom VARCHAR (200)
+);
+
+CREATE TABLE fullnamelineage (
+    tax_id   INTEGER       NOT NULL
+                           PRIMARY KEY,
+    tax_name VARCHAR (200) NOT NULL,
+    lineage  TEXT          NOT NULL
+);
+
+CREATE TABLE taxidlineage (
+    tax_id  INTEGER NOT NULL
+                    PRIMARY KEY,
+    lineage TEXT    NOT NULL
+);
+
+CREATE TABLE typematerial (
+    id         INTEGER       NOT NULL
+                             PRIMARY KEY,
+    tax_id     INTEGER       NOT NULL,
+    tax_name   VARCHAR (200) NOT NULL,
+    type       VARCHAR (50)  NOT NULL,
+    identifier VARCHAR (200) NOT NULL
+);
+
+CREATE TABLE host (
+    tax_id INTEGER NOT NULL
+                   PRIMARY KEY,
+    hosts  TEXT    NOT NULL
+);
+"#;
+
 /// Returns `true` if every field in the record is empty or whitespace only.
 ///
 /// The `csv` crate parses a completely blank line as a record with one empty
@@ -47,6 +147,263 @@ fn is_blank_record(record: &csv::StringRecord) -> bool {
     record.iter().all(|f| f.trim().is_empty())
 }
 
+/// Parse an integer column of the .dmp file `name`, reporting the 0-based
+/// record index `i` as a 1-based line number on failure.
+fn parse_dmp_int(
+    field: &str,
+    column: &str,
+    name: &str,
+    i: usize,
+) -> anyhow::Result<i64> {
+    field.trim().parse().map_err(|e| {
+        anyhow::anyhow!("Invalid {} at line {} in {}: {}", column, i + 1, name, e)
+    })
+}
+
+/// Load a .dmp stream with one prepared `insert_sql` statement.
+///
+/// `insert` receives each non-blank record together with its 0-based index;
+/// records with fewer than `min_fields` fields are rejected. Fields are split
+/// on `|` only: NCBI doesn't quote them, and names may contain `"`.
+fn load_records<R, F>(
+    conn: &rusqlite::Connection,
+    dmp: R,
+    name: &str,
+    insert_sql: &str,
+    min_fields: usize,
+    mut insert: F,
+) -> anyhow::Result<()>
+where
+    R: Read,
+    F: FnMut(&mut rusqlite::Statement, &csv::StringRecord, usize) -> anyhow::Result<()>,
+{
+    let mut tsv_rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'|')
+        .quoting(false)
+        .from_reader(dmp);
+
+    let mut stmt = conn.prepare(insert_sql)?;
+
+    // Intentionally use explicit SQL BEGIN/COMMIT rather than rusqlite::Transaction.
+    conn.execute_batch("BEGIN;")?;
+    for (i, result) in tsv_rdr.records().enumerate() {
+        let record = result?;
+        if is_blank_record(&record) {
+            continue;
+        }
+        if record.len() < min_fields {
+            return Err(anyhow::anyhow!(
+                "{name} record has {} fields, expected at least {min_fields}: {:?}",
+                record.len(),
+                record
+            ));
+        }
+        insert(&mut stmt, &record, i)?;
+
+        nwr::libs::io::progress_dot(i)?;
+    }
+    eprintln!();
+    conn.execute_batch("COMMIT;")?;
+
+    Ok(())
+}
+
+/// Parse the tax_id column of a .dmp file.
+fn parse_tax_id(field: &str, name: &str, i: usize) -> anyhow::Result<i64> {
+    parse_dmp_int(field, "tax_id", name, i)
+}
+
+/// DDL for the genome-to-taxon mapping of the GTDB taxonomy.
+static DDL_GTDB: &str = r"
+DROP TABLE IF EXISTS genome;
+
+CREATE TABLE genome (
+    accession VARCHAR (50) NOT NULL
+                           PRIMARY KEY,
+    tax_id    INTEGER      NOT NULL
+);
+";
+
+/// DDL for the precomputed lineages of all nodes.
+///
+/// `lft`/`rgt` are nested-set intervals: descendants of a taxon have `lft`
+/// within its interval. `lineage` holds the tax IDs from the root down to the
+/// taxon itself.
+static DDL_LINEAGE: &str = r"
+DROP TABLE IF EXISTS lineage;
+
+CREATE TABLE lineage (
+    tax_id  INTEGER NOT NULL
+                    PRIMARY KEY,
+    lft     INTEGER NOT NULL,
+    rgt     INTEGER NOT NULL,
+    depth   INTEGER NOT NULL,
+    lineage TEXT    NOT NULL
+);
+";
+
+/// .dmp files of `taxdump` needed to build `taxonomy.sqlite`.
+const REQUIRED_DMP: [&str; 6] = [
+    "division.dmp",
+    "gencode.dmp",
+    "names.dmp",
+    "nodes.dmp",
+    "merged.dmp",
+    "delnodes.dmp",
+];
+
+/// Default GTDB taxonomy files looked up in the nwr directory.
+const GTDB_FILES: [&str; 2] = ["bac120_taxonomy.tsv", "ar53_taxonomy.tsv"];
+
+/// Create the tables of a fresh taxonomy database.
+pub fn create_tables(conn: &rusqlite::Connection) -> anyhow::Result<()> {
+    conn.execute_batch(DDL_TX)?;
+    conn.execute_batch(DDL_NAME_FTS)?;
+    conn.execute_batch(DDL_NEW_TX)?;
+    Ok(())
+}
+
+/// Create the indexes of `name` and `node`, and fill the search index.
+pub fn create_indexes(conn: &rusqlite::Connection) -> anyhow::Result<()> {
+    conn.execute_batch(
+        "
+        CREATE INDEX IF NOT EXISTS idx_name_tax_id ON name(tax_id);
+        CREATE INDEX IF NOT EXISTS idx_name_name ON name(name);
+        CREATE INDEX IF NOT EXISTS idx_node_parent_id ON node(parent_tax_id);
+        INSERT INTO name_fts(name_fts) VALUES ('rebuild');
+        ",
+    )?;
+    Ok(())
+}
+
+/// Indexes of a taxonomy database.
+pub const TX_INDEXES: [&str; 4] = [
+    "idx_name_tax_id",
+    "idx_name_name",
+    "idx_node_parent_id",
+    "idx_lineage_lft",
+];
+
+/// Integrity checks run before a rebuilt taxonomy database replaces the old one.
+///
+/// The tables must have rows, the root must be in `node` and `lineage`, and the
+/// indexes must exist.
+pub fn check_taxonomy(conn: &rusqlite::Connection) -> anyhow::Result<()> {
+    for table in ["node", "name", "lineage"] {
+        let count = nwr::libs::db::check_rows(conn, table)?;
+        debug!("{table}: {count} rows");
+    }
+    let has_root: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM node JOIN lineage USING (tax_id) WHERE tax_id = 1 AND parent_tax_id = 1)",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_root {
+        anyhow::bail!("Root node 1 is missing");
+    }
+    nwr::libs::db::check_indexes(conn, &TX_INDEXES)?;
+    Ok(())
+}
+
+/// Record the sources and row counts of a taxonomy database in `meta`.
+pub fn write_meta(
+    conn: &rusqlite::Connection,
+    sources: &[PathBuf],
+) -> anyhow::Result<()> {
+    info!("==> Recording sources");
+    let mut meta = Meta::new(nwr::libs::db::TX_SCHEMA_VERSION);
+    for source in sources {
+        meta.source(source)?;
+    }
+    let mut tables = vec!["node", "name", "lineage"];
+    for table in ["merged", "delnodes", "genome"] {
+        if nwr::libs::db::table_exists(conn, table)? {
+            tables.push(table);
+        }
+    }
+    meta.rows(conn, &tables)?;
+    meta.write(conn)?;
+    Ok(())
+}
+
+/// Fill the `lineage` table from `node`, replacing any existing one.
+///
+/// Walks the tree from the root once; nodes not connected to the root are left
+/// out with a warning.
+pub fn build_lineage(conn: &rusqlite::Connection) -> anyhow::Result<()> {
+    info!("==> Building lineages");
+    conn.execute_batch(DDL_LINEAGE)?;
+
+    let mut children: HashMap<i64, Vec<i64>> = HashMap::new();
+    let mut n_nodes = 0;
+    {
+        let mut stmt =
+            conn.prepare("SELECT tax_id, parent_tax_id FROM node ORDER BY tax_id")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let tax_id: i64 = row.get(0)?;
+            let parent_id: i64 = row.get(1)?;
+            n_nodes += 1;
+            if tax_id != parent_id {
+                children.entry(parent_id).or_default().push(tax_id);
+            }
+        }
+    }
+
+    let mut stmt = conn.prepare(
+        "INSERT INTO lineage (tax_id, lft, rgt, depth, lineage) VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+
+    // Depth-first walk; each stack entry is (tax_id, lft, index of the next child)
+    let mut stack: Vec<(i64, i64, usize)> = vec![(1, 0, 0)];
+    let mut path: Vec<String> = vec!["1".to_string()];
+    let mut counter: i64 = 1;
+    let mut n_inserted = 0;
+
+    // Intentionally use explicit SQL BEGIN/COMMIT rather than rusqlite::Transaction.
+    conn.execute_batch("BEGIN;")?;
+    while let Some((tax_id, lft, next)) = stack.last_mut() {
+        let child = children.get(tax_id).and_then(|ids| ids.get(*next)).copied();
+        if let Some(child) = child {
+            *next += 1;
+            stack.push((child, counter, 0));
+            path.push(child.to_string());
+            counter += 1;
+            continue;
+        }
+
+        stmt.execute(rusqlite::params![
+            *tax_id,
+            *lft,
+            counter,
+            path.len() - 1,
+            path.join(" ")
+        ])?;
+        counter += 1;
+        stack.pop();
+        path.pop();
+
+        n_inserted += 1;
+        nwr::libs::io::progress_dot(n_inserted)?;
+    }
+    eprintln!();
+    conn.execute_batch("COMMIT;")?;
+    nwr::forget_table_rows();
+
+    if n_inserted < n_nodes {
+        warn!(
+            "{} nodes are not connected to the root",
+            n_nodes - n_inserted
+        );
+    }
+
+    debug!("Creating indexes for lineage");
+    conn.execute("CREATE INDEX idx_lineage_lft ON lineage(lft);", [])?;
+
+    Ok(())
+}
+
 /// Create clap subcommand arguments.
 #[must_use]
 pub fn make_subcommand() -> Command {
@@ -54,6 +411,21 @@ pub fn make_subcommand() -> Command {
         .about("Initializes the taxonomy database")
         .after_help(include_str!("../../docs/help/txdb.md"))
         .arg(args::dir_arg())
+        .arg(
+            Arg::new("tarball")
+                .long("tarball")
+                .num_args(1..)
+                .value_name("FILE")
+                .conflicts_with("gtdb")
+                .help("Read .dmp files from these taxdump tarballs"),
+        )
+        .arg(
+            Arg::new("gtdb")
+                .long("gtdb")
+                .num_args(0..)
+                .value_name("FILE")
+                .help("Build gtdb.sqlite from GTDB taxonomy files instead"),
+        )
 }
 
 /// Command implementation.
@@ -61,171 +433,532 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     nwr::libs::io::init_logger();
 
     let nwrdir = nwr::get_nwr_dir(args, "dir")?;
+    let _lock = nwr::libs::lock::lock_dir(&nwrdir)?;
+
+    if args.contains_id("gtdb") {
+        let files: Vec<PathBuf> = match args.get_many::<String>("gtdb") {
+            Some(files) if files.len() > 0 => files.map(PathBuf::from).collect(),
+            _ => GTDB_FILES
+                .iter()
+                .flat_map(|f| [nwrdir.join(f), nwrdir.join(format!("{f}.gz"))])
+                .filter(|path| path.exists())
+                .collect(),
+        };
+        if files.is_empty() {
+            anyhow::bail!(
+                "No GTDB taxonomy files found in {}; expected {}",
+                nwrdir.display(),
+                GTDB_FILES.join(" or ")
+            );
+        }
+        return import_gtdb(&nwrdir, &files);
+    }
 
     let file = nwrdir.join("taxonomy.sqlite");
-    if file.exists() {
-        std::fs::remove_file(&file)?;
+
+    // Stream from the tarballs when there are any, else read extracted files
+    let tarballs: Vec<PathBuf> = match args.get_many::<String>("tarball") {
+        Some(files) => files.map(PathBuf::from).collect(),
+        None => {
+            let paths = get_download_paths(&nwrdir)?;
+            if paths.tarball.exists() {
+                [paths.tarball, paths.new_tarball]
+                    .into_iter()
+                    .filter(|path| path.exists())
+                    .collect()
+            } else {
+                vec![]
+            }
+        }
+    };
+
+    // Check the sources before touching the existing database
+    let found: HashSet<String> = if tarballs.is_empty() {
+        REQUIRED_DMP
+            .iter()
+            .filter(|name| nwrdir.join(name).exists())
+            .map(|name| (*name).to_string())
+            .collect()
+    } else {
+        let mut found = HashSet::new();
+        for tarball in &tarballs {
+            info!("==> Checking {}", tarball.display());
+            found.extend(taxdump_entries(tarball)?);
+        }
+        found
+    };
+    let missing: Vec<&str> = REQUIRED_DMP
+        .iter()
+        .filter(|name| !found.contains(**name))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        let source = if tarballs.is_empty() {
+            nwrdir.display().to_string()
+        } else {
+            tarballs
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        anyhow::bail!("Missing {} in {source}", missing.join(", "));
     }
 
     info!("==> Opening database");
-    let conn = rusqlite::Connection::open(file)?;
-    nwr::libs::db::apply_import_pragmas(&conn)?;
+    let staged = StagedDb::create(&file)?;
+    let conn = staged.conn();
 
     info!("==> Create tables");
-    conn.execute_batch(DDL_TX)?;
+    create_tables(conn)?;
 
-    // divisions
-    info!("==> Loading division.dmp");
-    {
-        let dmp = File::open(nwrdir.join("division.dmp"))?;
-        let mut tsv_rdr = csv::ReaderBuilder::new()
-            .has_headers(false)
-            .delimiter(b'|')
-            .from_reader(dmp);
-
-        let mut stmt =
-            conn.prepare("INSERT INTO division (id, division) VALUES (?1, ?2)")?;
-
-        // Intentionally use explicit SQL BEGIN/COMMIT rather than rusqlite::Transaction.
-        conn.execute_batch("BEGIN;")?;
-        for (i, result) in tsv_rdr.records().enumerate() {
-            let record = result?;
-            if is_blank_record(&record) {
-                continue;
-            }
-            if record.len() < 3 {
-                return Err(anyhow::anyhow!(
-                    "division.dmp record has {} fields, expected at least 3: {:?}",
-                    record.len(),
-                    record
-                ));
+    let mut loaded: HashSet<String> = HashSet::new();
+    for tarball in &tarballs {
+        info!("==> Reading {}", tarball.display());
+        read_taxdump_entries(tarball, |name, dmp| {
+            // new_taxdump repeats names.dmp, nodes.dmp, etc.; keep the first
+            if !loaded.contains(name) && load_dmp(conn, name, dmp)? {
+                loaded.insert(name.to_string());
             }
-            let id: i64 = record[0].trim().parse().map_err(|e| {
-                anyhow::anyhow!("Invalid id at line {} in division.dmp: {}", i + 1, e)
-            })?;
-            let name: String = record[2].trim().to_string();
-            stmt.execute(rusqlite::params![id, name])?;
+            Ok(())
+        })?;
+    }
+
+    // Extracted files only without tarballs, so that files of another
+    // release are never mixed in
+    let mut sources: Vec<PathBuf> = tarballs.clone();
+    for name in REQUIRED_DMP.iter().chain(NEW_TAXDUMP_FILES) {
+        let path = nwrdir.join(name);
+        if loaded.contains(*name) {
+            continue;
         }
-        conn.execute_batch("COMMIT;")?;
+        if !path.exists() {
+            debug!("Skipping {name}: not found");
+            continue;
+        }
+        if !tarballs.is_empty() {
+            warn!("Ignoring {}: not in the tarballs", path.display());
+            continue;
+        }
+        load_dmp(conn, name, File::open(&path)?)?;
+        sources.push(path);
+    }
+
+    build_lineage(conn)?;
+
+    debug!("Creating indexes for typematerial");
+    conn.execute(
+        "CREATE INDEX idx_typematerial_tax_id ON typematerial(tax_id);",
+        [],
+    )?;
+
+    write_meta(conn, &sources)?;
+
+    info!("==> Checking {}", file.display());
+    staged.commit(check_taxonomy)?;
+
+    Ok(())
+}
 
-        debug!("Done inserting divisions");
+/// Load one .dmp stream by its file name.
+///
+/// Returns `false` for files that aren't imported.
+fn load_dmp<R: Read>(
+    conn: &rusqlite::Connection,
+    name: &str,
+    dmp: R,
+) -> anyhow::Result<bool> {
+    if !REQUIRED_DMP.contains(&name) && !NEW_TAXDUMP_FILES.contains(&name) {
+        return Ok(false);
     }
 
-    // names
-    info!("==> Loading names.dmp");
-    {
-        let dmp = File::open(nwrdir.join("names.dmp"))?;
-        let mut tsv_rdr = csv::ReaderBuilder::new()
-            .has_headers(false)
-            .delimiter(b'|')
-            .from_reader(dmp);
+    info!("==> Loading {name}");
+    match name {
+        "division.dmp" => load_division(conn, dmp)?,
+        "gencode.dmp" => load_gencode(conn, dmp)?,
+        "names.dmp" => load_names(conn, dmp)?,
+        "nodes.dmp" => load_nodes(conn, dmp)?,
+        "merged.dmp" => load_merged(conn, dmp)?,
+        "delnodes.dmp" => load_delnodes(conn, dmp)?,
+        "rankedlineage.dmp" => load_records(
+            conn,
+            dmp,
+            name,
+            r#"INSERT INTO rankedlineage (
+                tax_id, tax_name, species, genus, family, "order", class, phylum, kingdom, superkingdom
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"#,
+            10,
+            |stmt, record, i| {
+                // tax_id, tax_name, species, genus, family, order, class, phylum,
+                // kingdom, superkingdom
+                let tax_id = parse_tax_id(&record[0], "rankedlineage.dmp", i)?;
+                let mut params: Vec<rusqlite::types::Value> = vec![tax_id.into()];
+                for field in record.iter().skip(1).take(9) {
+                    let field = field.trim();
+                    params.push(if field.is_empty() {
+                        rusqlite::types::Value::Null
+                    } else {
+                        field.to_string().into()
+                    });
+                }
+                stmt.execute(rusqlite::params_from_iter(params))?;
+                Ok(())
+            },
+        )?,
+        "fullnamelineage.dmp" => load_records(
+            conn,
+            dmp,
+            name,
+            "INSERT INTO fullnamelineage (tax_id, tax_name, lineage) VALUES (?1, ?2, ?3)",
+            3,
+            |stmt, record, i| {
+                // tax_id, tax_name, lineage
+                let tax_id = parse_tax_id(&record[0], "fullnamelineage.dmp", i)?;
+                stmt.execute(rusqlite::params![
+                    tax_id,
+                    record[1].trim(),
+                    record[2].trim()
+                ])?;
+                Ok(())
+            },
+        )?,
+        "taxidlineage.dmp" => load_records(
+            conn,
+            dmp,
+            name,
+            "INSERT INTO taxidlineage (tax_id, lineage) VALUES (?1, ?2)",
+            2,
+            |stmt, record, i| {
+                // tax_id, lineage
+                let tax_id = parse_tax_id(&record[0], "taxidlineage.dmp", i)?;
+                stmt.execute(rusqlite::params![tax_id, record[1].trim()])?;
+                Ok(())
+            },
+        )?,
+        "typematerial.dmp" => load_records(
+            conn,
+            dmp,
+            name,
+            "INSERT INTO typematerial (tax_id, tax_name, type, identifier) VALUES (?1, ?2, ?3, ?4)",
+            4,
+            |stmt, record, i| {
+                // tax_id, tax_name, type, identifier
+                let tax_id = parse_tax_id(&record[0], "typematerial.dmp", i)?;
+                stmt.execute(rusqlite::params![
+                    tax_id,
+                    record[1].trim(),
+                    record[2].trim(),
+                    record[3].trim()
+                ])?;
+                Ok(())
+            },
+        )?,
+        "host.dmp" => load_records(
+            conn,
+            dmp,
+            name,
+            "INSERT INTO host (tax_id, hosts) VALUES (?1, ?2)",
+            2,
+            |stmt, record, i| {
+                // tax_id, potential_hosts
+                let tax_id = parse_tax_id(&record[0], "host.dmp", i)?;
+                stmt.execute(rusqlite::params![tax_id, record[1].trim()])?;
+                Ok(())
+            },
+        )?,
+        _ => return Ok(false),
+    }
 
-        let mut stmt = conn.prepare(
-            "INSERT INTO name (tax_id, name, name_class) VALUES (?1, ?2, ?3)",
-        )?;
+    Ok(true)
+}
 
-        // Intentionally use explicit SQL BEGIN/COMMIT rather than rusqlite::Transaction.
-        conn.execute_batch("BEGIN;")?;
-        for (i, result) in tsv_rdr.records().enumerate() {
-            let record = result?;
-            if is_blank_record(&record) {
-                continue;
-            }
-            if record.len() < 4 {
-                return Err(anyhow::anyhow!(
-                    "names.dmp record has {} fields, expected at least 4: {:?}",
-                    record.len(),
-                    record
-                ));
-            }
+/// Load division.dmp.
+fn load_division<R: Read>(conn: &rusqlite::Connection, dmp: R) -> anyhow::Result<()> {
+    load_records(
+        conn,
+        dmp,
+        "division.dmp",
+        "INSERT INTO division (id, division) VALUES (?1, ?2)",
+        3,
+        |stmt, record, i| {
+            // id, code, name, comments
+            let id = parse_dmp_int(&record[0], "id", "division.dmp", i)?;
+            stmt.execute(rusqlite::params![id, record[2].trim()])?;
+            Ok(())
+        },
+    )?;
 
+    debug!("Done inserting divisions");
+    Ok(())
+}
+
+/// Load the genetic codes of gencode.dmp.
+fn load_gencode<R: Read>(conn: &rusqlite::Connection, dmp: R) -> anyhow::Result<()> {
+    load_records(
+        conn,
+        dmp,
+        "gencode.dmp",
+        "INSERT INTO gencode (id, abbreviation, name, cde, starts) VALUES (?1, ?2, ?3, ?4, ?5)",
+        5,
+        |stmt, record, i| {
+            // id, abbreviation, name, cde, starts
+            let id = parse_dmp_int(&record[0], "id", "gencode.dmp", i)?;
+            stmt.execute(rusqlite::params![
+                id,
+                record[1].trim(),
+                record[2].trim(),
+                record[3].trim(),
+                record[4].trim()
+            ])?;
+            Ok(())
+        },
+    )?;
+
+    debug!("Done inserting genetic codes");
+    Ok(())
+}
+
+/// Load names.dmp and build the name indexes.
+fn load_names<R: Read>(conn: &rusqlite::Connection, dmp: R) -> anyhow::Result<()> {
+    load_records(
+        conn,
+        dmp,
+        "names.dmp",
+        "INSERT INTO name (tax_id, name, name_class) VALUES (?1, ?2, ?3)",
+        4,
+        |stmt, record, i| {
             // tax_id, name, unique_name, name_class
-            let tax_id: i64 = record[0].trim().parse().map_err(|e| {
-                anyhow::anyhow!("Invalid tax_id at line {} in names.dmp: {}", i + 1, e)
-            })?;
-            let name: String = record[1].trim().to_string();
-            let name_class: String = record[3].trim().to_string();
+            let tax_id = parse_tax_id(&record[0], "names.dmp", i)?;
+            stmt.execute(rusqlite::params![
+                tax_id,
+                record[1].trim(),
+                record[3].trim()
+            ])?;
+            Ok(())
+        },
+    )?;
 
-            stmt.execute(rusqlite::params![tax_id, name, name_class])?;
+    debug!("Creating indexes for name");
+    conn.execute("CREATE INDEX idx_name_tax_id ON name(tax_id);", [])?;
+    conn.execute("CREATE INDEX idx_name_name ON name(name);", [])?;
 
-            nwr::libs::io::progress_dot(i)?;
-        }
-        eprintln!();
-        conn.execute_batch("COMMIT;")?;
+    debug!("Building the trigram index of names");
+    conn.execute("INSERT INTO name_fts(name_fts) VALUES ('rebuild');", [])?;
+    Ok(())
+}
 
-        debug!("Creating indexes for name");
-        conn.execute("CREATE INDEX idx_name_tax_id ON name(tax_id);", [])?;
-        conn.execute("CREATE INDEX idx_name_name ON name(name);", [])?;
-    }
+/// Load nodes.dmp.
+fn load_nodes<R: Read>(conn: &rusqlite::Connection, dmp: R) -> anyhow::Result<()> {
+    load_records(
+        conn,
+        dmp,
+        "nodes.dmp",
+        "INSERT INTO node (
+            tax_id, parent_tax_id, rank, division_id,
+            gc_id, gc_inherited, mgc_id, mgc_inherited, comment
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        13,
+        |stmt, record, i| {
+            // tax_id, parent, rank, code, divid, inherited div, gen_code,
+            // inherited GC, mito, inherited MGC, ...
+            let int = |j: usize, column: &str| {
+                parse_dmp_int(&record[j], column, "nodes.dmp", i)
+            };
+            stmt.execute(rusqlite::params![
+                int(0, "tax_id")?,
+                int(1, "parent_tax_id")?,
+                record[2].trim(),
+                int(4, "division_id")?,
+                int(6, "gc_id")?,
+                int(7, "gc_inherited")?,
+                int(8, "mgc_id")?,
+                int(9, "mgc_inherited")?,
+                record[12].trim()
+            ])?;
+            Ok(())
+        },
+    )?;
 
-    // nodes
-    info!("==> Loading nodes.dmp");
-    {
-        let dmp = File::open(nwrdir.join("nodes.dmp"))?;
-        let mut tsv_rdr = csv::ReaderBuilder::new()
-            .has_headers(false)
-            .delimiter(b'|')
-            .from_reader(dmp);
+    debug!("Creating indexes for node");
+    conn.execute(
+        "CREATE INDEX idx_node_parent_id ON node(parent_tax_id);",
+        [],
+    )?;
+    Ok(())
+}
 
-        let mut stmt = conn.prepare(
-            "INSERT INTO node (tax_id, parent_tax_id, rank, division_id, comment) VALUES (?1, ?2, ?3, ?4, ?5)",
-        )?;
+/// Load the merged tax IDs of merged.dmp.
+fn load_merged<R: Read>(conn: &rusqlite::Connection, dmp: R) -> anyhow::Result<()> {
+    load_records(
+        conn,
+        dmp,
+        "merged.dmp",
+        "INSERT INTO merged (old_tax_id, new_tax_id) VALUES (?1, ?2)",
+        2,
+        |stmt, record, i| {
+            // old_tax_id, new_tax_id
+            let old_tax_id = parse_dmp_int(&record[0], "old_tax_id", "merged.dmp", i)?;
+            let new_tax_id = parse_dmp_int(&record[1], "new_tax_id", "merged.dmp", i)?;
+            stmt.execute(rusqlite::params![old_tax_id, new_tax_id])?;
+            Ok(())
+        },
+    )?;
 
-        // Intentionally use explicit SQL BEGIN/COMMIT rather than rusqlite::Transaction.
-        conn.execute_batch("BEGIN;")?;
-        for (i, result) in tsv_rdr.records().enumerate() {
-            let record = result?;
-            if is_blank_record(&record) {
+    debug!("Done inserting merged tax IDs");
+    Ok(())
+}
+
+/// Load the deleted tax IDs of delnodes.dmp.
+fn load_delnodes<R: Read>(conn: &rusqlite::Connection, dmp: R) -> anyhow::Result<()> {
+    load_records(
+        conn,
+        dmp,
+        "delnodes.dmp",
+        "INSERT INTO delnodes (tax_id) VALUES (?1)",
+        1,
+        |stmt, record, i| {
+            let tax_id = parse_tax_id(&record[0], "delnodes.dmp", i)?;
+            stmt.execute(rusqlite::params![tax_id])?;
+            Ok(())
+        },
+    )?;
+
+    debug!("Done inserting deleted tax IDs");
+    Ok(())
+}
+
+/// Build `gtdb.sqlite` from GTDB taxonomy files.
+///
+/// Each line maps a genome accession to a `d__;p__;...;s__` string. Taxa get
+/// synthetic IDs from [`nwr::gtdb_tax_id`] and are stored with the same
+/// node/name/division schema as `taxonomy.sqlite`; genomes are mapped to
+/// their species in the `genome` table.
+fn import_gtdb(nwrdir: &Path, files: &[PathBuf]) -> anyhow::Result<()> {
+    // tax_id => (parent_tax_id, rank, name, prefixed name)
+    let mut nodes: HashMap<i64, (i64, &'static str, String, String)> = HashMap::new();
+    // accession => species tax_id
+    let mut genomes: Vec<(String, i64)> = Vec::new();
+
+    for file in files {
+        info!("==> Reading {}", file.display());
+        let reader = nwr::libs::io::reader(&file.to_string_lossy())?;
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
                 continue;
             }
-            if record.len() < 13 {
-                return Err(anyhow::anyhow!(
-                    "nodes.dmp record has {} fields, expected at least 13: {:?}",
-                    record.len(),
-                    record
-                ));
+            let (accession, lineage) = line.split_once('\t').ok_or_else(|| {
+                anyhow::anyhow!("Invalid line {} in {}: {line}", i + 1, file.display())
+            })?;
+
+            let mut parent_id = 1;
+            for token in lineage.split(';') {
+                let token = token.trim();
+                let (prefix, name) = token.split_once("__").ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Invalid taxon '{token}' at line {} in {}",
+                        i + 1,
+                        file.display()
+                    )
+                })?;
+                let rank = nwr::gtdb_rank(prefix).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Unknown rank prefix '{prefix}__' at line {} in {}",
+                        i + 1,
+                        file.display()
+                    )
+                })?;
+                // Unassigned ranks like `g__` are left out of the lineage
+                if name.is_empty() {
+                    continue;
+                }
+
+                let tax_id = nwr::gtdb_tax_id(token);
+                match nodes.get(&tax_id) {
+                    Some((_, _, _, existing)) if existing != token => {
+                        anyhow::bail!("Tax ID collision between {existing} and {token}");
+                    }
+                    Some((existing_parent, _, _, _)) => {
+                        if *existing_parent != parent_id {
+                            warn!("{token} has more than one parent; keeping the first one");
+                        }
+                    }
+                    None => {
+                        nodes.insert(
+                            tax_id,
+                            (parent_id, rank, name.to_string(), token.to_string()),
+                        );
+                    }
+                }
+                parent_id = tax_id;
             }
 
-            // tax_id, parent, rank, code, divid, undef, gen_code, undef, mito
-            let tax_id: i64 = record[0].trim().parse().map_err(|e| {
-                anyhow::anyhow!("Invalid tax_id at line {} in nodes.dmp: {}", i + 1, e)
-            })?;
-            let parent_tax_id: i64 = record[1].trim().parse().map_err(|e| {
-                anyhow::anyhow!(
-                    "Invalid parent_tax_id at line {} in nodes.dmp: {}",
-                    i + 1,
-                    e
-                )
-            })?;
-            let rank: String = record[2].trim().to_string();
-            let division_id: i64 = record[4].trim().parse().map_err(|e| {
-                anyhow::anyhow!(
-                    "Invalid division_id at line {} in nodes.dmp: {}",
-                    i + 1,
-                    e
-                )
-            })?;
-            let comments: String = record[12].trim().to_string();
+            genomes.push((nwr::gtdb_accession(accession.trim()).to_string(), parent_id));
+        }
+    }
 
-            stmt.execute(rusqlite::params![
-                tax_id,
-                parent_tax_id,
-                rank,
-                division_id,
-                comments
-            ])?;
+    let file = nwrdir.join(nwr::txdb_filename("gtdb")?);
+    info!("==> Opening database");
+    let staged = StagedDb::create(&file)?;
+    let conn = staged.conn();
+
+    info!("==> Create tables");
+    create_tables(conn)?;
+    conn.execute_batch(DDL_GTDB)?;
 
-            nwr::libs::io::progress_dot(i)?;
+    // NCBI files prokaryotes, archaea included, under the Bacteria division
+    conn.execute(
+        "INSERT INTO division (id, division) VALUES (0, 'Bacteria')",
+        [],
+    )?;
+
+    info!("==> Loading {} taxa", nodes.len());
+    let mut ids: Vec<&i64> = nodes.keys().collect();
+    ids.sort_unstable();
+    conn.execute_batch("BEGIN;")?;
+    {
+        let mut stmt_node = conn.prepare(
+            "INSERT INTO node (tax_id, parent_tax_id, rank, division_id, gc_id, gc_inherited, mgc_id, mgc_inherited, comment)
+             VALUES (?1, ?2, ?3, 0, 0, 0, 0, 0, ?4)",
+        )?;
+        let mut stmt_name = conn.prepare(
+            "INSERT INTO name (tax_id, name, name_class) VALUES (?1, ?2, 'scientific name')",
+        )?;
+
+        stmt_node.execute(rusqlite::params![1, 1, "no rank", ""])?;
+        stmt_name.execute(rusqlite::params![1, "root"])?;
+        for tax_id in ids {
+            let (parent_id, rank, name, token) = &nodes[tax_id];
+            stmt_node.execute(rusqlite::params![tax_id, parent_id, rank, token])?;
+            stmt_name.execute(rusqlite::params![tax_id, name])?;
         }
-        eprintln!();
-        conn.execute_batch("COMMIT;")?;
+    }
+    conn.execute_batch("COMMIT;")?;
 
-        debug!("Creating indexes for node");
-        conn.execute(
-            "CREATE INDEX idx_node_parent_id ON node(parent_tax_id);",
-            [],
+    info!("==> Loading {} genomes", genomes.len());
+    conn.execute_batch("BEGIN;")?;
+    {
+        let mut stmt = conn.prepare(
+            "INSERT OR REPLACE INTO genome (accession, tax_id) VALUES (?1, ?2)",
         )?;
+        for (accession, tax_id) in &genomes {
+            stmt.execute(rusqlite::params![accession, tax_id])?;
+        }
     }
+    conn.execute_batch("COMMIT;")?;
+
+    debug!("Creating indexes");
+    create_indexes(conn)?;
+    conn.execute("CREATE INDEX idx_genome_tax_id ON genome(tax_id);", [])?;
+    build_lineage(conn)?;
+    write_meta(conn, files)?;
+
+    info!("==> Checking {}", file.display());
+    staged.commit(|conn| {
+        check_taxonomy(conn)?;
+        nwr::libs::db::check_rows(conn, "genome")?;
+        nwr::libs::db::check_indexes(conn, &["idx_genome_tax_id"])
+    })?;
 
     Ok(())
 }
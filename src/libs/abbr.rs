@@ -1,5 +1,5 @@
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::LazyLock;
 
 /// Common subspecies designation terms removed by [`clean_subspecies`].
@@ -282,6 +282,59 @@ pub fn process_line(
     ))
 }
 
+/// Abbreviate parsed names together, so that genus and species
+/// abbreviations are unique among them.
+///
+/// Normal names become `G_spe_Strain`, or `Gspe_Strain` when `tight`; the
+/// others keep their cleaned strain part.
+#[must_use]
+pub fn abbr_names(all_parts: &[NameParts], min_len: usize, tight: bool) -> Vec<String> {
+    let genus_list: Vec<String> = all_parts
+        .iter()
+        .filter(|p| p.is_normal)
+        .map(|p| p.genus.clone())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let species_list: Vec<String> = all_parts
+        .iter()
+        .filter(|p| p.is_normal)
+        .map(|p| p.species.clone())
+        .filter(|s| !s.is_empty())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let genus_abbr = abbr_most(&genus_list, 1, true);
+    let species_abbr = abbr_most(&species_list, min_len, true);
+
+    all_parts
+        .iter()
+        .map(|parts| {
+            if parts.is_normal {
+                let spacer = if tight { "" } else { "_" };
+                let ge = genus_abbr.get(&parts.genus).unwrap_or(&parts.genus);
+                let sp = species_abbr.get(&parts.species).unwrap_or(&parts.species);
+
+                let ge_sp = if parts.species.is_empty() {
+                    ge.clone()
+                } else {
+                    format!("{ge}{spacer}{sp}")
+                };
+
+                if parts.strain.is_empty() {
+                    ge_sp
+                } else {
+                    format!("{}_{}", ge_sp, parts.strain)
+                }
+            } else {
+                parts.strain.clone()
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
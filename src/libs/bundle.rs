@@ -0,0 +1,152 @@
+use log::info;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// Files of the nwr directory put into a bundle, when present.
+pub const BUNDLE_FILES: [&str; 10] = [
+    "taxdump.tar.gz",
+    "taxdump.tar.gz.md5",
+    "new_taxdump.tar.gz",
+    "new_taxdump.tar.gz.md5",
+    "assembly_summary_refseq.txt",
+    "assembly_summary_genbank.txt",
+    "bac120_taxonomy.tsv",
+    "bac120_taxonomy.tsv.gz",
+    "ar53_taxonomy.tsv",
+    "ar53_taxonomy.tsv.gz",
+];
+
+/// The checksums of all files of a bundle, in `md5sum` format.
+pub const MANIFEST: &str = "bundle.md5";
+
+/// Write the bundle files present in `dir` as a .tar.gz to `writer`, with a
+/// manifest of their MD5 sums.
+///
+/// Returns the names of the files written.
+pub fn write_bundle<W: Write>(dir: &Path, writer: W) -> anyhow::Result<Vec<String>> {
+    let names: Vec<String> = BUNDLE_FILES
+        .iter()
+        .filter(|name| dir.join(name).is_file())
+        .map(|name| (*name).to_string())
+        .collect();
+    if !names.iter().any(|name| name == "taxdump.tar.gz") {
+        anyhow::bail!("No taxdump.tar.gz in {}; run `nwr download`", dir.display());
+    }
+
+    let gz = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+    let mut builder = tar::Builder::new(gz);
+    let mut manifest = String::new();
+    for name in &names {
+        info!("Adding {name}");
+        let path = dir.join(name);
+        manifest.push_str(&format!(
+            "{}  {name}\n",
+            crate::libs::download::file_md5(&path)?
+        ));
+        builder.append_path_with_name(&path, name)?;
+    }
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST, manifest.as_bytes())?;
+    builder.into_inner()?.finish()?.flush()?;
+
+    Ok(names)
+}
+
+/// Check the files of an unpacked bundle or a local mirror in `dir`.
+///
+/// `taxdump.tar.gz` must match its .md5 file, as must `new_taxdump.tar.gz` when
+/// present; every file listed in the manifest must be there and match.
+/// Returns the bundle files found.
+pub fn verify_bundle(dir: &Path) -> anyhow::Result<Vec<String>> {
+    for (tarball, md5_file) in [
+        ("taxdump.tar.gz", "taxdump.tar.gz.md5"),
+        ("new_taxdump.tar.gz", "new_taxdump.tar.gz.md5"),
+    ] {
+        let is_required = tarball == "taxdump.tar.gz";
+        if !dir.join(tarball).is_file() && !is_required {
+            continue;
+        }
+        for name in [tarball, md5_file] {
+            if !dir.join(name).is_file() {
+                anyhow::bail!("Missing {name} in {}", dir.display());
+            }
+        }
+        info!("Checking {tarball}");
+        crate::libs::download::check_taxdump_md5(
+            &dir.join(tarball),
+            &dir.join(md5_file),
+        )?;
+    }
+
+    let manifest = dir.join(MANIFEST);
+    if manifest.is_file() {
+        info!("Checking the files of {MANIFEST}");
+        let reader = crate::libs::io::reader(&manifest.to_string_lossy())?;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (expected, name) = line.split_once("  ").ok_or_else(|| {
+                anyhow::anyhow!("Malformed line in {MANIFEST}: {line}")
+            })?;
+            let path = dir.join(name);
+            crate::libs::io::validate_tar_entry_path(Path::new(name))?;
+            if !path.is_file() {
+                anyhow::bail!("Missing {name} listed in {MANIFEST}");
+            }
+            let digest = crate::libs::download::file_md5(&path)?;
+            if digest != expected.to_lowercase() {
+                anyhow::bail!(
+                    "MD5 check of {name} failed. Expected: {expected}, Computed: {digest}"
+                );
+            }
+        }
+    }
+
+    Ok(BUNDLE_FILES
+        .iter()
+        .filter(|name| dir.join(name).is_file())
+        .map(|name| (*name).to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_manifest() {
+        let source = tempfile::TempDir::new().unwrap();
+        let tarball = std::fs::read("tests/nwr/taxdump_new.tar.gz").unwrap();
+        std::fs::write(source.path().join("taxdump.tar.gz"), &tarball).unwrap();
+        std::fs::write(
+            source.path().join("taxdump.tar.gz.md5"),
+            format!("{:x}  taxdump.tar.gz\n", md5::compute(&tarball)),
+        )
+        .unwrap();
+        std::fs::write(source.path().join("assembly_summary_refseq.txt"), "#\n")
+            .unwrap();
+
+        let mut bytes = vec![];
+        let names = write_bundle(source.path(), &mut bytes).unwrap();
+        assert_eq!(names.len(), 3);
+
+        let unpacked = tempfile::TempDir::new().unwrap();
+        tar::Archive::new(flate2::read::GzDecoder::new(bytes.as_slice()))
+            .unpack(unpacked.path())
+            .unwrap();
+        assert!(unpacked.path().join(MANIFEST).is_file());
+        assert_eq!(verify_bundle(unpacked.path()).unwrap(), names);
+
+        // A changed file no longer matches the manifest
+        std::fs::write(unpacked.path().join("assembly_summary_refseq.txt"), "##\n")
+            .unwrap();
+        let err = verify_bundle(unpacked.path()).unwrap_err();
+        assert!(err.to_string().contains("assembly_summary_refseq.txt"));
+    }
+}
@@ -1,3 +1,6 @@
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
 /// Apply PRAGMA settings tuned for bulk import into a fresh `SQLite` database.
 ///
 /// Disables journaling and synchronous writes, increases the cache size, and
@@ -15,3 +18,514 @@ pub fn apply_import_pragmas(conn: &rusqlite::Connection) -> anyhow::Result<()> {
     )?;
     Ok(())
 }
+
+/// A database built next to its target and swapped in only once it validates.
+///
+/// The database is written to `{target}.tmp`; [`StagedDb::commit`] runs the
+/// checks, syncs the temporary file to disk, moves an existing target aside to
+/// `{target}.bak` and renames the temporary file into place. The backup is kept
+/// as the previous database until the next commit replaces it, and the
+/// directory is synced to make the renames durable. If the checks fail, or the
+/// staged database is dropped without being committed, the temporary file is
+/// removed and the existing database is left untouched.
+pub struct StagedDb {
+    temp: PathBuf,
+    target: PathBuf,
+    conn: Option<rusqlite::Connection>,
+}
+
+impl StagedDb {
+    /// Start a fresh database for `target`, with the bulk import PRAGMAs applied.
+    pub fn create(target: &Path) -> anyhow::Result<Self> {
+        let temp = sibling(target, "tmp");
+        if temp.exists() {
+            std::fs::remove_file(&temp)?;
+        }
+        Self::open(temp, target)
+    }
+
+    /// Start from a copy of `source`, e.g. to add taxa to an existing database.
+    pub fn copy_from(source: &Path, target: &Path) -> anyhow::Result<Self> {
+        let temp = sibling(target, "tmp");
+        std::fs::copy(source, &temp).with_context(|| {
+            format!("Could not copy {} to {}", source.display(), temp.display())
+        })?;
+        Self::open(temp, target)
+    }
+
+    fn open(temp: PathBuf, target: &Path) -> anyhow::Result<Self> {
+        let conn = rusqlite::Connection::open(&temp)?;
+        let staged = Self {
+            temp,
+            target: target.to_path_buf(),
+            conn: Some(conn),
+        };
+        apply_import_pragmas(staged.conn())?;
+        Ok(staged)
+    }
+
+    /// The connection to the temporary database.
+    pub fn conn(&self) -> &rusqlite::Connection {
+        self.conn.as_ref().expect("connection is open until commit")
+    }
+
+    /// Validate the database with `check`, then replace the target with it.
+    pub fn commit<F>(mut self, check: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(&rusqlite::Connection) -> anyhow::Result<()>,
+    {
+        check(self.conn()).with_context(|| {
+            format!(
+                "{} failed validation; keeping the existing database",
+                self.temp.display()
+            )
+        })?;
+        if let Some(conn) = self.conn.take() {
+            conn.close().map_err(|(_, e)| e)?;
+        }
+
+        // The database was written without journal or syncs
+        std::fs::File::open(&self.temp)?.sync_all()?;
+
+        let backup = sibling(&self.target, "bak");
+        let has_backup = self.target.exists();
+        if has_backup {
+            std::fs::rename(&self.target, &backup).with_context(|| {
+                format!("Could not move {} aside", self.target.display())
+            })?;
+        }
+        if let Err(e) = std::fs::rename(&self.temp, &self.target) {
+            if has_backup {
+                let _ = std::fs::rename(&backup, &self.target);
+            }
+            return Err(e).with_context(|| {
+                format!("Could not commit database to {}", self.target.display())
+            });
+        }
+        sync_dir(&self.target)?;
+        crate::libs::taxonomy::forget_table_rows();
+
+        Ok(())
+    }
+}
+
+impl Drop for StagedDb {
+    fn drop(&mut self) {
+        // Close the connection before removing an uncommitted database
+        drop(self.conn.take());
+        if self.temp.exists() {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// Sync the directory of `path`, so a rename in it survives a crash.
+fn sync_dir(path: &Path) -> anyhow::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    // Directories can't be opened as files on Windows
+    if cfg!(unix) {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// `{path}.{ext}`, keeping the original extension.
+fn sibling(path: &Path, ext: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(ext);
+    PathBuf::from(name)
+}
+
+/// Fail if `table` has no rows; returns the row count.
+pub fn check_rows(conn: &rusqlite::Connection, table: &str) -> anyhow::Result<i64> {
+    let count: i64 =
+        conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+            row.get(0)
+        })?;
+    if count == 0 {
+        anyhow::bail!("Table {table} is empty");
+    }
+    Ok(count)
+}
+
+/// Fail if any of `indexes` doesn't exist.
+pub fn check_indexes(
+    conn: &rusqlite::Connection,
+    indexes: &[&str],
+) -> anyhow::Result<()> {
+    let mut stmt =
+        conn.prepare("SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = ?1")?;
+    let missing: Vec<&str> = indexes
+        .iter()
+        .filter(|name| !stmt.exists([name]).unwrap_or(false))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!("Missing index {}", missing.join(", "));
+    }
+    Ok(())
+}
+
+/// Schema version of `taxonomy.sqlite`, `gtdb.sqlite` and custom taxonomies.
+pub const TX_SCHEMA_VERSION: i64 = 1;
+/// Schema version of `ar_refseq.sqlite`, `ar_genbank.sqlite` and `ar_all.sqlite`.
+pub const AR_SCHEMA_VERSION: i64 = 1;
+/// Schema version of `seq.sqlite`.
+pub const SEQ_SCHEMA_VERSION: i64 = 1;
+
+/// The kinds of nwr databases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DbKind {
+    Taxonomy,
+    Assembly,
+    Seq,
+}
+
+impl DbKind {
+    /// The schema version this nwr builds for the kind.
+    pub fn schema_version(self) -> i64 {
+        match self {
+            DbKind::Taxonomy => TX_SCHEMA_VERSION,
+            DbKind::Assembly => AR_SCHEMA_VERSION,
+            DbKind::Seq => SEQ_SCHEMA_VERSION,
+        }
+    }
+}
+
+/// A database kept in the nwr directory.
+pub struct DbFile {
+    /// File name under the nwr directory.
+    pub name: &'static str,
+    pub kind: DbKind,
+    /// The command that rebuilds it.
+    pub rebuild: &'static str,
+}
+
+/// Databases looked up in the nwr directory by `migrate`, `status` and `doctor`.
+pub const DATABASES: [DbFile; 5] = [
+    DbFile {
+        name: "taxonomy.sqlite",
+        kind: DbKind::Taxonomy,
+        rebuild: "nwr txdb",
+    },
+    DbFile {
+        name: "gtdb.sqlite",
+        kind: DbKind::Taxonomy,
+        rebuild: "nwr txdb --gtdb",
+    },
+    DbFile {
+        name: "ar_refseq.sqlite",
+        kind: DbKind::Assembly,
+        rebuild: "nwr ardb",
+    },
+    DbFile {
+        name: "ar_genbank.sqlite",
+        kind: DbKind::Assembly,
+        rebuild: "nwr ardb --genbank",
+    },
+    DbFile {
+        name: "ar_all.sqlite",
+        kind: DbKind::Assembly,
+        rebuild: "nwr ardb --all",
+    },
+];
+
+/// Returns `true` if the connected database contains `table`.
+///
+/// `table` may be qualified with the name of an attached database, as in
+/// `tx.merged`.
+///
+/// ```
+/// let conn = rusqlite::Connection::open("tests/nwr/taxonomy.sqlite").unwrap();
+///
+/// assert!(nwr::libs::db::table_exists(&conn, "node").unwrap());
+/// assert!(nwr::libs::db::table_exists(&conn, "main.node").unwrap());
+/// assert!(!nwr::libs::db::table_exists(&conn, "ar").unwrap());
+/// ```
+pub fn table_exists(conn: &rusqlite::Connection, table: &str) -> anyhow::Result<bool> {
+    let (schema, table) = table.split_once('.').unwrap_or(("main", table));
+    let exists: bool = conn.query_row(
+        &format!(
+            "SELECT EXISTS (SELECT 1 FROM {schema}.sqlite_master WHERE type = 'table' AND name = ?1)"
+        ),
+        [table],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+/// DDL of the `meta` table, which records where a database came from.
+pub static DDL_META: &str = r"
+CREATE TABLE IF NOT EXISTS meta (
+    key   TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+";
+
+/// Provenance of a database, written to its `meta` table.
+///
+/// Keys are `nwr_version`, `schema_version`, `built`,
+/// `source:{file}:md5`, `source:{file}:modified` and `rows:{table}`;
+/// `nwr migrate` adds `migrated`.
+/// Times are UTC; when `SOURCE_DATE_EPOCH` is set it replaces the build time
+/// and caps the file times, so that rebuilds are reproducible.
+#[derive(Debug, Default)]
+pub struct Meta {
+    schema_version: i64,
+    entries: Vec<(String, String)>,
+}
+
+impl Meta {
+    /// Start with the version of nwr, the schema version and the build time.
+    #[must_use]
+    pub fn new(schema_version: i64) -> Self {
+        let mut meta = Self {
+            schema_version,
+            ..Self::default()
+        };
+        meta.push("nwr_version", env!("CARGO_PKG_VERSION"));
+        meta.push("schema_version", &schema_version.to_string());
+        meta.push("built", &format_timestamp(now()));
+        meta
+    }
+
+    fn push(&mut self, key: &str, value: &str) {
+        self.entries.push((key.to_string(), value.to_string()));
+    }
+
+    /// Record the MD5 and modification time of a source file.
+    pub fn source(&mut self, path: &Path) -> anyhow::Result<()> {
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |n| n.to_string_lossy().to_string(),
+        );
+        let md5 = crate::libs::download::file_md5(path)?;
+        let mut modified = std::fs::metadata(path)?
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        if let Some(epoch) = source_date_epoch() {
+            modified = modified.min(epoch);
+        }
+        self.push(&format!("source:{name}:md5"), &md5);
+        self.push(
+            &format!("source:{name}:modified"),
+            &format_timestamp(modified),
+        );
+        Ok(())
+    }
+
+    /// Record the row counts of `tables`.
+    pub fn rows(
+        &mut self,
+        conn: &rusqlite::Connection,
+        tables: &[&str],
+    ) -> anyhow::Result<()> {
+        for table in tables {
+            let count: i64 =
+                conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                    row.get(0)
+                })?;
+            self.push(&format!("rows:{table}"), &count.to_string());
+        }
+        Ok(())
+    }
+
+    /// Write the entries, replacing existing keys and keeping the others, and
+    /// stamp the schema version.
+    pub fn write(&self, conn: &rusqlite::Connection) -> anyhow::Result<()> {
+        conn.execute_batch(DDL_META)?;
+        let mut stmt =
+            conn.prepare("INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)")?;
+        for (key, value) in &self.entries {
+            stmt.execute([key, value])?;
+        }
+        conn.pragma_update(None, "user_version", self.schema_version)?;
+        Ok(())
+    }
+}
+
+/// `PRAGMA user_version` of a database; 0 for databases built before versioning.
+pub fn schema_version(conn: &rusqlite::Connection) -> anyhow::Result<i64> {
+    let version: i64 =
+        conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    Ok(version)
+}
+
+/// Stamp a migrated database with `version`, in `PRAGMA user_version` and `meta`.
+pub fn set_schema_version(
+    conn: &rusqlite::Connection,
+    version: i64,
+) -> anyhow::Result<()> {
+    conn.execute_batch(DDL_META)?;
+    let mut stmt =
+        conn.prepare("INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)")?;
+    stmt.execute(["schema_version", &version.to_string()])?;
+    stmt.execute(["migrated", &format_timestamp(now())])?;
+    conn.pragma_update(None, "user_version", version)?;
+    Ok(())
+}
+
+/// Fail unless the database at `path` has schema `expected`.
+///
+/// Older databases are pointed to `nwr migrate`; newer ones to a newer nwr.
+pub fn check_schema_version(
+    conn: &rusqlite::Connection,
+    path: &Path,
+    expected: i64,
+) -> anyhow::Result<()> {
+    let version = schema_version(conn)?;
+    if version < expected {
+        anyhow::bail!(
+            "{} has schema version {version}, this nwr needs {expected}; run `nwr migrate --dir {}` to upgrade it",
+            path.display(),
+            path.parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."))
+                .display()
+        );
+    } else if version > expected {
+        anyhow::bail!(
+            "{} has schema version {version}, newer than {expected} of this nwr; upgrade nwr",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// The `meta` table of a database, empty for databases built without one.
+pub fn read_meta(conn: &rusqlite::Connection) -> anyhow::Result<Vec<(String, String)>> {
+    if !table_exists(conn, "meta")? {
+        return Ok(vec![]);
+    }
+    let mut stmt = conn.prepare("SELECT key, value FROM meta ORDER BY key")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Seconds since the Unix epoch, or `SOURCE_DATE_EPOCH` when set.
+fn now() -> u64 {
+    source_date_epoch().unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+    })
+}
+
+/// `SOURCE_DATE_EPOCH`, as used for reproducible builds.
+fn source_date_epoch() -> Option<u64> {
+    std::env::var("SOURCE_DATE_EPOCH").ok()?.trim().parse().ok()
+}
+
+/// Seconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`.
+#[must_use]
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86400).cast_signed();
+    let rem = secs % 86400;
+
+    // Civil date from days since 1970-01-01, after Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn test_staged_db_commit() {
+        let tempdir = tempfile::TempDir::new().unwrap();
+        let target = tempdir.path().join("test.sqlite");
+        let conn = rusqlite::Connection::open(&target).unwrap();
+        conn.execute_batch("CREATE TABLE old (x INTEGER);").unwrap();
+        drop(conn);
+
+        // A reader of the old database keeps reading it across the swap,
+        // which keeps it as the backup
+        let reader = rusqlite::Connection::open(&target).unwrap();
+        let staged = StagedDb::create(&target).unwrap();
+        staged
+            .conn()
+            .execute_batch("CREATE TABLE new (x INTEGER); INSERT INTO new VALUES (1);")
+            .unwrap();
+        staged
+            .commit(|conn| check_rows(conn, "new").map(|_| ()))
+            .unwrap();
+        assert!(reader.prepare("SELECT x FROM old").is_ok());
+
+        let conn = rusqlite::Connection::open(&target).unwrap();
+        assert_eq!(check_rows(&conn, "new").unwrap(), 1);
+        assert!(!sibling(&target, "tmp").exists());
+        let backup = rusqlite::Connection::open(sibling(&target, "bak")).unwrap();
+        assert!(backup.prepare("SELECT x FROM old").is_ok());
+
+        // A failed check leaves the target alone
+        let staged = StagedDb::create(&target).unwrap();
+        staged
+            .conn()
+            .execute_batch("CREATE TABLE new (x INTEGER);")
+            .unwrap();
+        assert!(staged
+            .commit(|conn| check_rows(conn, "new").map(|_| ()))
+            .is_err());
+        assert_eq!(check_rows(&conn, "new").unwrap(), 1);
+        assert!(!sibling(&target, "tmp").exists());
+    }
+
+    #[test]
+    fn test_meta() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        assert!(read_meta(&conn).unwrap().is_empty());
+
+        conn.execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1), (2);")
+            .unwrap();
+        let mut meta = Meta::new(3);
+        meta.rows(&conn, &["t"]).unwrap();
+        meta.write(&conn).unwrap();
+
+        let entries: std::collections::HashMap<String, String> =
+            read_meta(&conn).unwrap().into_iter().collect();
+        assert_eq!(entries["schema_version"], "3");
+        assert_eq!(entries["rows:t"], "2");
+        assert_eq!(entries["nwr_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(schema_version(&conn).unwrap(), 3);
+
+        let path = Path::new("dir/test.sqlite");
+        assert!(check_schema_version(&conn, path, 3).is_ok());
+        let err = check_schema_version(&conn, path, 4)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("run `nwr migrate --dir dir`"));
+        let err = check_schema_version(&conn, path, 2)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("upgrade nwr"));
+    }
+}
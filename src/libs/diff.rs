@@ -0,0 +1,419 @@
+use log::info;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+
+/// The parts of a taxonomy release compared by [`diff`].
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    /// tax_id => (parent_tax_id, rank)
+    pub nodes: HashMap<i64, (i64, String)>,
+    /// tax_id => scientific name
+    pub names: HashMap<i64, String>,
+    /// old_tax_id => new_tax_id
+    pub merged: HashMap<i64, i64>,
+}
+
+impl Snapshot {
+    /// Load a snapshot from a `taxonomy.sqlite` file, a directory holding
+    /// one, or a `taxdump.tar.gz` tarball.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if path.is_dir() {
+            return Self::from_sqlite(&path.join("taxonomy.sqlite"));
+        }
+        let name = path.to_string_lossy().to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Self::from_tarball(path)
+        } else {
+            Self::from_sqlite(path)
+        }
+    }
+
+    /// Load a snapshot from a taxonomy database.
+    pub fn from_sqlite(path: &Path) -> anyhow::Result<Self> {
+        if !path.is_file() {
+            anyhow::bail!("No such taxonomy database: {}", path.display());
+        }
+        info!("==> Reading {}", path.display());
+        let conn = rusqlite::Connection::open_with_flags(
+            path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )?;
+        let mut snapshot = Self::default();
+
+        let mut stmt = conn.prepare("SELECT tax_id, parent_tax_id, rank FROM node")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            snapshot
+                .nodes
+                .insert(row.get(0)?, (row.get(1)?, row.get(2)?));
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT tax_id, name FROM name WHERE name_class = 'scientific name'",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            snapshot.names.insert(row.get(0)?, row.get(1)?);
+        }
+
+        // Databases built before `merged.dmp` was imported have no merges
+        if crate::libs::db::table_exists(&conn, "merged")? {
+            let mut stmt = conn.prepare("SELECT old_tax_id, new_tax_id FROM merged")?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                snapshot.merged.insert(row.get(0)?, row.get(1)?);
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Load a snapshot from the .dmp files of a taxdump tarball, without
+    /// extracting them.
+    pub fn from_tarball(path: &Path) -> anyhow::Result<Self> {
+        info!("==> Reading {}", path.display());
+        let mut snapshot = Self::default();
+        let mut seen: HashSet<String> = HashSet::new();
+
+        crate::libs::download::read_taxdump_entries(path, |name, entry| {
+            match name {
+                "nodes.dmp" => read_dmp(entry, name, 3, |record| {
+                    // tax_id, parent_tax_id, rank
+                    snapshot.nodes.insert(
+                        parse_id(&record[0], name)?,
+                        (parse_id(&record[1], name)?, record[2].trim().to_string()),
+                    );
+                    Ok(())
+                })?,
+                "names.dmp" => read_dmp(entry, name, 4, |record| {
+                    // tax_id, name, unique_name, name_class
+                    if record[3].trim() == "scientific name" {
+                        snapshot.names.insert(
+                            parse_id(&record[0], name)?,
+                            record[1].trim().to_string(),
+                        );
+                    }
+                    Ok(())
+                })?,
+                "merged.dmp" => read_dmp(entry, name, 2, |record| {
+                    snapshot.merged.insert(
+                        parse_id(&record[0], name)?,
+                        parse_id(&record[1], name)?,
+                    );
+                    Ok(())
+                })?,
+                _ => return Ok(()),
+            }
+            seen.insert(name.to_string());
+            Ok(())
+        })?;
+
+        for required in ["nodes.dmp", "names.dmp"] {
+            if !seen.contains(required) {
+                anyhow::bail!("{} not found in {}", required, path.display());
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Tax ID of a term: a tax ID, merged IDs followed, or a scientific name.
+    ///
+    /// `index` comes from [`Snapshot::name_index`]. Names shared by several
+    /// taxa don't resolve.
+    #[must_use]
+    pub fn resolve(&self, term: &str, index: &HashMap<&str, Vec<i64>>) -> Option<i64> {
+        let term = term.trim();
+        if let Ok(id) = term.parse::<i64>() {
+            let id = self.merged.get(&id).copied().unwrap_or(id);
+            return self.nodes.contains_key(&id).then_some(id);
+        }
+        match index.get(term).map(Vec::as_slice) {
+            Some([id]) => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Scientific names mapped to their tax IDs.
+    #[must_use]
+    pub fn name_index(&self) -> HashMap<&str, Vec<i64>> {
+        let mut index: HashMap<&str, Vec<i64>> = HashMap::new();
+        for (id, name) in &self.names {
+            index.entry(name.as_str()).or_default().push(*id);
+        }
+        index
+    }
+
+    /// Tax IDs from the root down to `id`.
+    #[must_use]
+    pub fn lineage(&self, id: i64) -> Vec<i64> {
+        let mut lineage = vec![id];
+        let mut current = id;
+        while let Some((parent, _)) = self.nodes.get(&current) {
+            // The root is its own parent; the length check guards against cycles
+            if *parent == current || lineage.len() > self.nodes.len() {
+                break;
+            }
+            lineage.push(*parent);
+            current = *parent;
+        }
+        lineage.reverse();
+        lineage
+    }
+
+    /// `id` and all of its descendants.
+    #[must_use]
+    pub fn subtree(&self, id: i64) -> HashSet<i64> {
+        let mut children: HashMap<i64, Vec<i64>> = HashMap::new();
+        for (tax_id, (parent, _)) in &self.nodes {
+            if tax_id != parent {
+                children.entry(*parent).or_default().push(*tax_id);
+            }
+        }
+
+        let mut subtree = HashSet::new();
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop() {
+            if subtree.insert(current) {
+                if let Some(ids) = children.get(&current) {
+                    stack.extend(ids);
+                }
+            }
+        }
+        subtree
+    }
+}
+
+/// Feed each non-blank record of a .dmp stream to `f`.
+fn read_dmp<R, F>(
+    reader: R,
+    name: &str,
+    min_fields: usize,
+    mut f: F,
+) -> anyhow::Result<()>
+where
+    R: Read,
+    F: FnMut(&csv::StringRecord) -> anyhow::Result<()>,
+{
+    let mut tsv_rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'|')
+        .quoting(false)
+        .from_reader(reader);
+
+    for result in tsv_rdr.records() {
+        let record = result?;
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        if record.len() < min_fields {
+            anyhow::bail!(
+                "{name} record has {} fields, expected at least {min_fields}: {:?}",
+                record.len(),
+                record
+            );
+        }
+        f(&record)?;
+    }
+    Ok(())
+}
+
+fn parse_id(field: &str, name: &str) -> anyhow::Result<i64> {
+    field
+        .trim()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid tax ID '{}' in {name}: {e}", field.trim()))
+}
+
+/// Kinds of changes between two snapshots, in report order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChangeKind {
+    Added,
+    Deleted,
+    Merged,
+    Renamed,
+    Rank,
+    Reparented,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Added => "added",
+            Self::Deleted => "deleted",
+            Self::Merged => "merged",
+            Self::Renamed => "renamed",
+            Self::Rank => "rank",
+            Self::Reparented => "reparented",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// A change of one taxon, with its old and new values.
+///
+/// `old` and `new` are ranks for added and deleted taxa, the old and new tax
+/// IDs for merges, names for renames, ranks for rank changes and parent tax
+/// IDs for reparented taxa. Missing values are `NA`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub tax_id: i64,
+    pub name: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// Changes from `old` to `new`, sorted by tax ID and kind.
+#[must_use]
+pub fn diff(old: &Snapshot, new: &Snapshot) -> Vec<Change> {
+    let na = || "NA".to_string();
+    let mut changes = Vec::new();
+
+    for (tax_id, (old_parent, old_rank)) in &old.nodes {
+        let name = old.names.get(tax_id).cloned().unwrap_or_else(na);
+        let Some((new_parent, new_rank)) = new.nodes.get(tax_id) else {
+            match new.merged.get(tax_id) {
+                Some(new_id) => changes.push(Change {
+                    kind: ChangeKind::Merged,
+                    tax_id: *tax_id,
+                    name,
+                    old: tax_id.to_string(),
+                    new: new_id.to_string(),
+                }),
+                None => changes.push(Change {
+                    kind: ChangeKind::Deleted,
+                    tax_id: *tax_id,
+                    name,
+                    old: old_rank.clone(),
+                    new: na(),
+                }),
+            }
+            continue;
+        };
+
+        let new_name = new.names.get(tax_id).cloned().unwrap_or_else(na);
+        if new_name != name {
+            changes.push(Change {
+                kind: ChangeKind::Renamed,
+                tax_id: *tax_id,
+                name: new_name.clone(),
+                old: name,
+                new: new_name.clone(),
+            });
+        }
+        if new_rank != old_rank {
+            changes.push(Change {
+                kind: ChangeKind::Rank,
+                tax_id: *tax_id,
+                name: new_name.clone(),
+                old: old_rank.clone(),
+                new: new_rank.clone(),
+            });
+        }
+        if new_parent != old_parent {
+            changes.push(Change {
+                kind: ChangeKind::Reparented,
+                tax_id: *tax_id,
+                name: new_name,
+                old: old_parent.to_string(),
+                new: new_parent.to_string(),
+            });
+        }
+    }
+
+    for (tax_id, (_, rank)) in &new.nodes {
+        if !old.nodes.contains_key(tax_id) {
+            changes.push(Change {
+                kind: ChangeKind::Added,
+                tax_id: *tax_id,
+                name: new.names.get(tax_id).cloned().unwrap_or_else(na),
+                old: na(),
+                new: rank.clone(),
+            });
+        }
+    }
+
+    changes.sort_by_key(|c| (c.tax_id, c.kind));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(nodes: &[(i64, i64, &str, &str)]) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        for (tax_id, parent, rank, name) in nodes {
+            snapshot
+                .nodes
+                .insert(*tax_id, (*parent, (*rank).to_string()));
+            snapshot.names.insert(*tax_id, (*name).to_string());
+        }
+        snapshot
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = snapshot(&[
+            (1, 1, "no rank", "root"),
+            (2, 1, "genus", "Alpha"),
+            (3, 2, "species", "Alpha one"),
+            (4, 2, "species", "Alpha two"),
+            (5, 1, "genus", "Beta"),
+            (6, 5, "species", "Beta one"),
+        ]);
+        let mut new = snapshot(&[
+            (1, 1, "no rank", "root"),
+            (2, 1, "genus", "Alpha"),
+            (3, 2, "species", "Alpha uno"),
+            (5, 1, "subgenus", "Beta"),
+            (6, 2, "species", "Beta one"),
+            (7, 5, "species", "Beta two"),
+        ]);
+        new.merged.insert(4, 3);
+
+        let changes: Vec<(ChangeKind, i64)> = diff(&old, &new)
+            .iter()
+            .map(|c| (c.kind, c.tax_id))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (ChangeKind::Renamed, 3),
+                (ChangeKind::Merged, 4),
+                (ChangeKind::Rank, 5),
+                (ChangeKind::Reparented, 6),
+                (ChangeKind::Added, 7),
+            ]
+        );
+
+        // Without the merge, 4 is gone
+        new.merged.clear();
+        let changes = diff(&old, &new);
+        assert_eq!(changes[1].kind, ChangeKind::Deleted);
+        assert_eq!(changes[1].old, "species");
+    }
+
+    #[test]
+    fn test_lineage_subtree() {
+        let mut snapshot = snapshot(&[
+            (1, 1, "no rank", "root"),
+            (2, 1, "genus", "Alpha"),
+            (3, 2, "species", "Alpha one"),
+            (5, 1, "genus", "Beta"),
+        ]);
+        snapshot.merged.insert(4, 3);
+
+        assert_eq!(snapshot.lineage(3), vec![1, 2, 3]);
+        assert_eq!(snapshot.subtree(2), HashSet::from([2, 3]));
+        assert_eq!(snapshot.subtree(1).len(), 4);
+
+        let index = snapshot.name_index();
+        assert_eq!(snapshot.resolve("Alpha one", &index), Some(3));
+        assert_eq!(snapshot.resolve("4", &index), Some(3));
+        assert_eq!(snapshot.resolve("9", &index), None);
+        assert_eq!(snapshot.resolve("Gamma", &index), None);
+    }
+}
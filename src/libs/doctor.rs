@@ -0,0 +1,222 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+/// How serious a finding of `nwr doctor` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Level::Ok => "OK",
+            Level::Warn => "WARN",
+            Level::Fail => "FAIL",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// One line of the report: a check on a file and its outcome.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub level: Level,
+    pub subject: String,
+    pub message: String,
+}
+
+impl Finding {
+    #[must_use]
+    pub fn new(level: Level, subject: &str, message: &str) -> Self {
+        Self {
+            level,
+            subject: subject.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.level, self.subject, self.message)
+    }
+}
+
+/// Nodes that don't lead to the root.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TreeProblems {
+    /// Nodes whose parent is missing
+    pub orphans: Vec<i64>,
+    /// Nodes on a parent cycle, other than the root pointing to itself
+    pub cycles: Vec<i64>,
+}
+
+/// Walk up from every node of `node` once, finding orphans and cycles.
+pub fn tree_problems(conn: &rusqlite::Connection) -> anyhow::Result<TreeProblems> {
+    let mut parents: HashMap<i64, i64> = HashMap::new();
+    {
+        let mut stmt = conn.prepare("SELECT tax_id, parent_tax_id FROM node")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            parents.insert(row.get(0)?, row.get(1)?);
+        }
+    }
+
+    let mut problems = TreeProblems::default();
+    // Nodes already walked; those on the current path are in `on_path`
+    let mut done: HashSet<i64> = HashSet::new();
+    let mut ids: Vec<i64> = parents.keys().copied().collect();
+    ids.sort_unstable();
+    for id in ids {
+        let mut path: Vec<i64> = vec![];
+        let mut on_path: HashSet<i64> = HashSet::new();
+        let mut cur = id;
+        loop {
+            if done.contains(&cur) {
+                break;
+            }
+            if on_path.contains(&cur) {
+                let start = path.iter().position(|x| *x == cur).unwrap_or(0);
+                problems.cycles.extend(&path[start..]);
+                break;
+            }
+            let Some(&parent) = parents.get(&cur) else {
+                // Only the first node of a path can be missing: the parent of
+                // the previous one
+                if let Some(&child) = path.last() {
+                    problems.orphans.push(child);
+                }
+                break;
+            };
+            path.push(cur);
+            on_path.insert(cur);
+            if parent == cur {
+                break;
+            }
+            cur = parent;
+        }
+        done.extend(path);
+    }
+    problems.orphans.sort_unstable();
+    problems.cycles.sort_unstable();
+
+    Ok(problems)
+}
+
+/// Tax IDs in `name` without a node.
+pub fn names_without_nodes(conn: &rusqlite::Connection) -> anyhow::Result<Vec<i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT tax_id FROM name WHERE tax_id NOT IN (SELECT tax_id FROM node) ORDER BY tax_id",
+    )?;
+    let ids = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<i64>, _>>()?;
+    Ok(ids)
+}
+
+/// Problems reported by `PRAGMA quick_check`; empty when the file is sound.
+pub fn quick_check(conn: &rusqlite::Connection) -> anyhow::Result<Vec<String>> {
+    let mut stmt = conn.prepare("PRAGMA quick_check")?;
+    let messages = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(messages.into_iter().filter(|m| m != "ok").collect())
+}
+
+/// Tax IDs of `ar` gone from the taxonomy database at `txdb`.
+///
+/// Returns the IDs missing altogether, and those merged into other taxa.
+pub fn missing_tax_ids(
+    conn: &rusqlite::Connection,
+    txdb: &Path,
+) -> anyhow::Result<(Vec<i64>, Vec<i64>)> {
+    conn.execute(
+        "ATTACH DATABASE ?1 AS tx",
+        [txdb.to_string_lossy().as_ref()],
+    )?;
+    let has_merged = crate::libs::db::table_exists(conn, "tx.merged")?;
+    let mut missing = vec![];
+    let mut merged = vec![];
+    {
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT tax_id FROM ar WHERE tax_id NOT IN (SELECT tax_id FROM tx.node) ORDER BY tax_id",
+        )?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<i64>, _>>()?;
+        let mut stmt_merged = if has_merged {
+            Some(conn.prepare("SELECT 1 FROM tx.merged WHERE old_tax_id = ?1")?)
+        } else {
+            None
+        };
+        for id in ids {
+            let is_merged = match stmt_merged.as_mut() {
+                Some(stmt) => stmt.exists([id])?,
+                None => false,
+            };
+            if is_merged {
+                merged.push(id);
+            } else {
+                missing.push(id);
+            }
+        }
+    }
+    conn.execute("DETACH DATABASE tx", [])?;
+    Ok((missing, merged))
+}
+
+/// The first few IDs of a list, for the report.
+#[must_use]
+pub fn format_ids(ids: &[i64]) -> String {
+    const SHOWN: usize = 10;
+    let mut s = ids
+        .iter()
+        .take(SHOWN)
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    if ids.len() > SHOWN {
+        s.push_str(&format!(", ... ({} more)", ids.len() - SHOWN));
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_problems() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE node (tax_id INTEGER PRIMARY KEY, parent_tax_id INTEGER);
+            CREATE TABLE name (tax_id INTEGER, name TEXT);
+            -- 1 is the root; 4 hangs under the missing 3; 5 and 6 form a cycle, with 7 below
+            INSERT INTO node VALUES (1, 1), (2, 1), (4, 3), (8, 4), (5, 6), (6, 5), (7, 5);
+            INSERT INTO name VALUES (1, 'root'), (2, 'two'), (9, 'nine'), (9, 'Nine');
+            ",
+        )
+        .unwrap();
+
+        let problems = tree_problems(&conn).unwrap();
+        assert_eq!(problems.orphans, vec![4]);
+        assert_eq!(problems.cycles, vec![5, 6]);
+        assert_eq!(names_without_nodes(&conn).unwrap(), vec![9]);
+        assert!(quick_check(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_format_ids() {
+        assert_eq!(format_ids(&[1, 2]), "1, 2");
+        let ids: Vec<i64> = (1..=12).collect();
+        assert_eq!(
+            format_ids(&ids),
+            "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, ... (2 more)"
+        );
+    }
+}
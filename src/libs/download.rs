@@ -1,7 +1,8 @@
-use log::info;
+use log::{info, warn};
 use std::fs::File;
 use std::io::{self, BufReader};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 /// File paths used by the download operation.
 #[derive(Debug, Clone)]
@@ -14,8 +15,24 @@ pub struct DownloadPaths {
     pub ar_genbank: PathBuf,
     /// Local path for the tarball MD5 checksum file.
     pub md5_file: PathBuf,
+    /// Local path for the `new_taxdump` tarball.
+    pub new_tarball: PathBuf,
+    /// Local path for the `new_taxdump` MD5 checksum file.
+    pub new_md5_file: PathBuf,
 }
 
+/// Extra files of `new_taxdump.tar.gz` that `nwr txdb` can import.
+///
+/// The archive also carries its own names.dmp, nodes.dmp, etc.; those are left
+/// alone so they don't overwrite the files extracted from `taxdump.tar.gz`.
+pub const NEW_TAXDUMP_FILES: &[&str] = &[
+    "rankedlineage.dmp",
+    "fullnamelineage.dmp",
+    "taxidlineage.dmp",
+    "typematerial.dmp",
+    "host.dmp",
+];
+
 /// Build the set of download file paths under `nwrdir`.
 pub fn get_download_paths(nwrdir: &Path) -> anyhow::Result<DownloadPaths> {
     Ok(DownloadPaths {
@@ -23,6 +40,8 @@ pub fn get_download_paths(nwrdir: &Path) -> anyhow::Result<DownloadPaths> {
         ar_refseq: nwrdir.join("assembly_summary_refseq.txt"),
         ar_genbank: nwrdir.join("assembly_summary_genbank.txt"),
         md5_file: nwrdir.join("taxdump.tar.gz.md5"),
+        new_tarball: nwrdir.join("new_taxdump.tar.gz"),
+        new_md5_file: nwrdir.join("new_taxdump.tar.gz.md5"),
     })
 }
 
@@ -40,11 +59,8 @@ pub fn assembly_reports_exist(ar_refseq: &Path, ar_genbank: &Path) -> bool {
 
 /// Verify the MD5 checksum of a downloaded taxdump tarball.
 pub fn check_taxdump_md5(tarball: &Path, md5_file: &Path) -> anyhow::Result<()> {
-    let mut file = File::open(tarball)?;
-    let mut hasher = md5::Context::new();
     info!("Computing MD5 sum...");
-    io::copy(&mut file, &mut hasher)?;
-    let digest = format!("{:x}", hasher.compute());
+    let digest = file_md5(tarball)?;
 
     let ncbi_digest = std::fs::read_to_string(md5_file)?
         .split_whitespace()
@@ -70,6 +86,15 @@ pub fn check_taxdump_md5(tarball: &Path, md5_file: &Path) -> anyhow::Result<()>
     }
 }
 
+/// Lowercase hex MD5 digest of a file.
+pub fn file_md5(path: &Path) -> anyhow::Result<String> {
+    let mut file = File::open(path)
+        .map_err(|e| anyhow::anyhow!("Could not open {}: {}", path.display(), e))?;
+    let mut hasher = md5::Context::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.compute()))
+}
+
 /// Extract a taxdump tarball into `dest_dir`, rejecting traversal-prone entries.
 pub fn extract_taxdump(tarball: &Path, dest_dir: &Path) -> anyhow::Result<()> {
     let tar_gz = File::open(tarball)?;
@@ -90,6 +115,77 @@ pub fn extract_taxdump(tarball: &Path, dest_dir: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Extract only the `wanted` entries of a taxdump tarball into `dest_dir`.
+///
+/// Entries are matched by file name, so a leading directory in the archive is
+/// ignored. Returns the names that were extracted.
+pub fn extract_taxdump_entries(
+    tarball: &Path,
+    dest_dir: &Path,
+    wanted: &[&str],
+) -> anyhow::Result<Vec<String>> {
+    let tar_gz = File::open(tarball)?;
+    let tar = flate2::read::GzDecoder::new(BufReader::new(tar_gz));
+    let mut archive = tar::Archive::new(tar);
+
+    let mut extracted = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        crate::libs::io::validate_tar_entry_path(&path)?;
+
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if !wanted.contains(&name) {
+            continue;
+        }
+        entry.unpack(dest_dir.join(name))?;
+        extracted.push(name.to_string());
+    }
+
+    Ok(extracted)
+}
+
+/// Stream the entries of a taxdump tarball without extracting them.
+///
+/// `f` gets the file name and contents of each regular file; a leading
+/// directory in the archive is ignored.
+pub fn read_taxdump_entries<F>(tarball: &Path, mut f: F) -> anyhow::Result<()>
+where
+    F: FnMut(&str, &mut dyn io::Read) -> anyhow::Result<()>,
+{
+    let tar_gz = File::open(tarball)?;
+    let tar = flate2::read::GzDecoder::new(BufReader::new(tar_gz));
+    let mut archive = tar::Archive::new(tar);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+        crate::libs::io::validate_tar_entry_path(&path)?;
+
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        f(name, &mut entry)?;
+    }
+
+    Ok(())
+}
+
+/// File names of the entries in a taxdump tarball.
+pub fn taxdump_entries(tarball: &Path) -> anyhow::Result<Vec<String>> {
+    let mut names = Vec::new();
+    read_taxdump_entries(tarball, |name, _| {
+        names.push(name.to_string());
+        Ok(())
+    })?;
+    Ok(names)
+}
+
 /// Format human-readable sizes for the downloaded files.
 pub fn format_file_sizes(paths: &DownloadPaths) -> anyhow::Result<Vec<String>> {
     let mut sizes = Vec::new();
@@ -104,7 +200,7 @@ pub fn format_file_sizes(paths: &DownloadPaths) -> anyhow::Result<Vec<String>> {
     Ok(sizes)
 }
 
-/// FTP connection abstraction for testability.
+/// Connection to a download host, over FTP or HTTP(S), for testability.
 #[cfg_attr(test, mockall::automock)]
 pub trait FtpConnectionTrait {
     /// Login to FTP server.
@@ -193,6 +289,284 @@ impl FtpConnectionTrait for FtpConnection {
     }
 }
 
+/// Connect to a download host, logging in to FTP servers anonymously.
+///
+/// `https://` and `http://` URLs use [`HttpConnection`] with `retries`;
+/// `ftp://` URLs and bare `host:port` use FTP.
+pub fn connect(host: &str, retries: u32) -> anyhow::Result<Box<dyn FtpConnectionTrait>> {
+    let lower = host.to_lowercase();
+    if lower.starts_with("https://") || lower.starts_with("http://") {
+        Ok(Box::new(
+            HttpConnection::connect(host)?.with_retries(retries, Duration::from_secs(1)),
+        ))
+    } else {
+        let addr = host
+            .strip_prefix("ftp://")
+            .unwrap_or(host)
+            .trim_end_matches('/');
+        let addr = if addr.contains(':') {
+            addr.to_string()
+        } else {
+            format!("{addr}:21")
+        };
+        let mut conn = FtpConnection::connect(&addr)?;
+        conn.login("ftp", "example@example.com")?;
+        Ok(Box::new(conn))
+    }
+}
+
+/// An HTTP status that makes a request fail.
+#[derive(Debug)]
+struct HttpStatus {
+    code: u16,
+    url: String,
+    /// `Content-Range` of the response, e.g. `bytes */1234` with a 416
+    content_range: Option<String>,
+}
+
+impl std::fmt::Display for HttpStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {} for {}", self.code, self.url)
+    }
+}
+
+impl std::error::Error for HttpStatus {}
+
+impl HttpStatus {
+    /// Server errors, timeouts and rate limits are worth another try.
+    fn is_transient(&self) -> bool {
+        self.code >= 500 || self.code == 408 || self.code == 429
+    }
+}
+
+/// HTTP(S) connection to a mirror such as `https://ftp.ncbi.nlm.nih.gov`.
+///
+/// Directories set by `cwd` are joined to the base URL. Files are written to
+/// `{dest}.part` and renamed when complete; an interrupted download resumes
+/// from there with a `Range` request, in the next attempt or the next run.
+/// Failed requests are retried with exponential backoff, and each download is
+/// checked against its `Content-Length`.
+pub struct HttpConnection {
+    agent: ureq::Agent,
+    base: String,
+    dir: String,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl HttpConnection {
+    /// Prepare requests to `base`; nothing is sent until a file is retrieved.
+    pub fn connect(base: &str) -> anyhow::Result<Self> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(30))
+            .timeout_read(Duration::from_secs(300))
+            .user_agent(&format!("nwr/{}", env!("CARGO_PKG_VERSION")))
+            .build();
+        Ok(Self {
+            agent,
+            base: base.trim_end_matches('/').to_string(),
+            dir: "/".to_string(),
+            retries: 0,
+            backoff: Duration::from_secs(1),
+        })
+    }
+
+    /// Retry failed requests up to `retries` times, waiting `backoff`, then
+    /// twice as long each time.
+    #[must_use]
+    pub fn with_retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.backoff = backoff;
+        self
+    }
+
+    /// Read the whole body of `url`, checked against its `Content-Length`.
+    pub fn read(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        self.retry(url, || {
+            let response = self.get(url, 0)?;
+            let expected = response
+                .header("Content-Length")
+                .and_then(|len| len.parse::<usize>().ok());
+            let mut body = vec![];
+            io::copy(&mut response.into_reader(), &mut body)?;
+            if let Some(expected) = expected {
+                if body.len() != expected {
+                    anyhow::bail!("{url}: received {} of {expected} bytes", body.len());
+                }
+            }
+            Ok(body)
+        })
+    }
+
+    /// Download `url` to `dest` through `{dest}.part`, resuming what is there.
+    pub fn download(&self, url: &str, dest: &Path) -> anyhow::Result<()> {
+        let mut part = dest.as_os_str().to_os_string();
+        part.push(".part");
+        let part = PathBuf::from(part);
+
+        self.retry(url, || self.fetch_to(url, &part))?;
+        std::fs::rename(&part, dest)?;
+        Ok(())
+    }
+
+    fn url(&self, filename: &str) -> String {
+        format!(
+            "{}{}/{}",
+            self.base,
+            self.dir.trim_end_matches('/'),
+            filename
+        )
+    }
+
+    /// Run `f` until it succeeds, fails for good, or runs out of retries.
+    fn retry<T, F>(&self, url: &str, mut f: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> anyhow::Result<T>,
+    {
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Ok(value) => return Ok(value),
+                Err(err) => {
+                    let is_fatal = err
+                        .downcast_ref::<HttpStatus>()
+                        .is_some_and(|status| !status.is_transient());
+                    if is_fatal || attempt >= self.retries {
+                        return Err(err);
+                    }
+                    let delay = self.backoff * 2_u32.pow(attempt);
+                    attempt += 1;
+                    warn!(
+                        "{err}; retrying {url} in {:?} ({attempt}/{})",
+                        delay, self.retries
+                    );
+                    std::thread::sleep(delay);
+                }
+            }
+        }
+    }
+
+    /// Send a GET request, from byte `offset` on when it isn't 0.
+    fn get(&self, url: &str, offset: u64) -> anyhow::Result<ureq::Response> {
+        let mut request = self.agent.get(url);
+        if offset > 0 {
+            request = request.set("Range", &format!("bytes={offset}-"));
+        }
+        match request.call() {
+            Ok(response) => Ok(response),
+            Err(ureq::Error::Status(code, response)) => Err(HttpStatus {
+                code,
+                url: url.to_string(),
+                content_range: response.header("Content-Range").map(str::to_string),
+            }
+            .into()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// One attempt at downloading `url` into `part`, resuming what is there.
+    fn fetch_to(&self, url: &str, part: &Path) -> anyhow::Result<()> {
+        let offset = std::fs::metadata(part).map_or(0, |m| m.len());
+        let response = match self.get(url, offset) {
+            Err(err) if offset > 0 => match err.downcast_ref::<HttpStatus>() {
+                Some(status) if status.code == 416 => {
+                    // Nothing left to send: the partial file is complete, as
+                    // after a crash before the rename, or no prefix of the
+                    // remote one, so start over
+                    let size = status
+                        .content_range
+                        .as_deref()
+                        .and_then(|range| range.strip_prefix("bytes */"))
+                        .and_then(|size| size.trim().parse::<u64>().ok());
+                    if size == Some(offset) {
+                        info!(
+                            "Already complete at {} bytes",
+                            readable(&offset.to_string())
+                        );
+                        return Ok(());
+                    }
+                    std::fs::remove_file(part)?;
+                    return self.fetch_to(url, part);
+                }
+                _ => return Err(err),
+            },
+            result => result?,
+        };
+
+        // Servers ignoring the range send the whole file again
+        let expected_range = format!("bytes {offset}-");
+        let is_resumed = response.status() == 206
+            && response
+                .header("Content-Range")
+                .is_some_and(|range| range.starts_with(&expected_range));
+        let start = if is_resumed { offset } else { 0 };
+        if offset > 0 {
+            if is_resumed {
+                info!("Resuming at {} bytes", readable(&offset.to_string()));
+            } else {
+                info!("Server can't resume; starting over");
+            }
+        }
+        let expected = response
+            .header("Content-Length")
+            .and_then(|len| len.parse::<u64>().ok())
+            .map(|len| start + len);
+
+        let mut file = if is_resumed {
+            std::fs::OpenOptions::new().append(true).open(part)?
+        } else {
+            File::create(part)?
+        };
+        let copied = io::copy(&mut response.into_reader(), &mut file);
+        file.sync_all()?;
+        drop(file);
+        copied?;
+
+        let size = std::fs::metadata(part)?.len();
+        if let Some(expected) = expected {
+            if size != expected {
+                anyhow::bail!("{url}: received {size} of {expected} bytes");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FtpConnectionTrait for HttpConnection {
+    fn login(&mut self, _user: &str, _password: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn cwd(&mut self, path: &str) -> anyhow::Result<()> {
+        self.dir = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("{}/{}", self.dir.trim_end_matches('/'), path)
+        };
+        Ok(())
+    }
+
+    fn pwd(&mut self) -> anyhow::Result<String> {
+        Ok(format!("{}{}", self.base, self.dir))
+    }
+
+    fn simple_retr(
+        &mut self,
+        filename: &str,
+    ) -> anyhow::Result<std::io::Cursor<Vec<u8>>> {
+        let body = self.read(&self.url(filename))?;
+        Ok(std::io::Cursor::new(body))
+    }
+
+    fn retr_to_file(&mut self, filename: &str, dest_path: &Path) -> anyhow::Result<()> {
+        self.download(&self.url(filename), dest_path)
+    }
+
+    fn quit(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
 /// Download the taxdump tarball and its MD5 file from the remote taxonomy path.
 pub fn download_taxdump(
     conn: &mut dyn FtpConnectionTrait,
@@ -213,6 +587,28 @@ pub fn download_taxdump(
     Ok(())
 }
 
+/// Download the `new_taxdump` tarball and its MD5 file.
+///
+/// NCBI keeps them in the `new_taxdump/` subdirectory of the taxonomy path.
+pub fn download_new_taxdump(
+    conn: &mut dyn FtpConnectionTrait,
+    paths: &DownloadPaths,
+    tx_path: &str,
+) -> anyhow::Result<()> {
+    conn.cwd(&format!("{}/new_taxdump", tx_path.trim_end_matches('/')))?;
+    info!("Remote directory: {}", conn.pwd()?);
+
+    info!("Retrieving MD5 file...");
+    let mut file = File::create(&paths.new_md5_file)?;
+    let mut cursor = conn.simple_retr("new_taxdump.tar.gz.md5")?;
+    io::copy(&mut cursor, &mut file)?;
+
+    info!("Retrieving {}...", "new_taxdump.tar.gz");
+    conn.retr_to_file("new_taxdump.tar.gz", &paths.new_tarball)?;
+
+    Ok(())
+}
+
 /// Download `RefSeq` and `GenBank` assembly summary reports.
 pub fn download_assembly_reports(
     conn: &mut dyn FtpConnectionTrait,
@@ -267,6 +663,14 @@ mod tests {
             temp_dir.path().join("assembly_summary_genbank.txt")
         );
         assert_eq!(paths.md5_file, temp_dir.path().join("taxdump.tar.gz.md5"));
+        assert_eq!(
+            paths.new_tarball,
+            temp_dir.path().join("new_taxdump.tar.gz")
+        );
+        assert_eq!(
+            paths.new_md5_file,
+            temp_dir.path().join("new_taxdump.tar.gz.md5")
+        );
     }
 
     #[test]
@@ -414,6 +818,42 @@ mod tests {
         assert!(extracted_file.exists());
     }
 
+    #[test]
+    fn test_extract_taxdump_entries() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use tar::Builder;
+
+        let temp_dir = TempDir::new().unwrap();
+        let tarball_path = temp_dir.path().join("new_taxdump.tar.gz");
+        let extract_dir = temp_dir.path().join("extracted");
+        std::fs::create_dir(&extract_dir).unwrap();
+
+        {
+            let tar_gz = File::create(&tarball_path).unwrap();
+            let enc = GzEncoder::new(tar_gz, Compression::default());
+            let mut tar = Builder::new(enc);
+
+            for name in ["names.dmp", "rankedlineage.dmp", "host.dmp"] {
+                let content = format!("{name} content");
+                let mut header = tar::Header::new_gnu();
+                header.set_path(name).unwrap();
+                header.set_size(content.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                tar.append(&header, content.as_bytes()).unwrap();
+            }
+        }
+
+        let extracted =
+            extract_taxdump_entries(&tarball_path, &extract_dir, NEW_TAXDUMP_FILES)
+                .unwrap();
+        assert_eq!(extracted, vec!["rankedlineage.dmp", "host.dmp"]);
+        assert!(extract_dir.join("rankedlineage.dmp").exists());
+        assert!(extract_dir.join("host.dmp").exists());
+        assert!(!extract_dir.join("names.dmp").exists());
+    }
+
     #[test]
     fn test_extract_taxdump_nonexistent() {
         let temp_dir = TempDir::new().unwrap();
@@ -568,6 +1008,47 @@ mod tests {
         assert!(paths.tarball.exists());
     }
 
+    #[test]
+    fn test_download_new_taxdump_success() {
+        let temp_dir = TempDir::new().unwrap();
+        let paths = get_download_paths(temp_dir.path()).unwrap();
+
+        let mut mock = MockFtpConnectionTrait::new();
+
+        mock.expect_cwd()
+            .with(mockall::predicate::eq("/pub/taxonomy/new_taxdump"))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        mock.expect_pwd()
+            .times(0..=10)
+            .returning(|| Ok("/pub/taxonomy/new_taxdump".to_string()));
+
+        let md5_content = b"abc123  new_taxdump.tar.gz";
+        mock.expect_simple_retr()
+            .with(mockall::predicate::eq("new_taxdump.tar.gz.md5"))
+            .times(1)
+            .returning(move |_| Ok(std::io::Cursor::new(md5_content.to_vec())));
+
+        let tarball_content = create_test_tarball_content();
+        mock.expect_retr_to_file()
+            .with(
+                mockall::predicate::eq("new_taxdump.tar.gz"),
+                mockall::predicate::always(),
+            )
+            .times(1)
+            .returning(move |_, dest_path: &Path| {
+                std::fs::write(dest_path, &tarball_content).unwrap();
+                Ok(())
+            });
+
+        let result = download_new_taxdump(&mut mock, &paths, "/pub/taxonomy/");
+        assert!(result.is_ok());
+
+        assert!(paths.new_md5_file.exists());
+        assert!(paths.new_tarball.exists());
+    }
+
     #[test]
     fn test_download_assembly_reports_success() {
         let temp_dir = TempDir::new().unwrap();
@@ -0,0 +1,237 @@
+use crate::libs::download::{file_md5, HttpConnection};
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+/// File types of an assembly: the name given to `--type` and the suffix of
+/// the file after the assembly's directory name.
+pub const FILE_TYPES: [(&str, &str); 4] = [
+    ("fna", "_genomic.fna.gz"),
+    ("faa", "_protein.faa.gz"),
+    ("gff", "_genomic.gff.gz"),
+    ("report", "_assembly_report.txt"),
+];
+
+/// An assembly to download, as listed in `url.tsv` or an `.assembly.tsv`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    pub name: String,
+    pub url: String,
+    pub species: String,
+    /// The input line, to write back into the retry list
+    pub line: String,
+}
+
+impl Assembly {
+    /// The directory the files go to, `{species}/{name}` as in `ASSEMBLY/`.
+    #[must_use]
+    pub fn dir(&self, outdir: &Path) -> PathBuf {
+        outdir.join(&self.species).join(&self.name)
+    }
+
+    /// The file names of the requested `suffixes`, e.g.
+    /// `GCF_000005845.2_ASM584v2_genomic.fna.gz`.
+    #[must_use]
+    pub fn file_names(&self, suffixes: &[&str]) -> Vec<String> {
+        let base = self
+            .url
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or("");
+        suffixes
+            .iter()
+            .map(|suffix| format!("{base}{suffix}"))
+            .collect()
+    }
+}
+
+/// Read assemblies from `url.tsv` (name, url, species) or an `.assembly.tsv`
+/// (name, url, biosample, species, `assembly_level`).
+///
+/// Species of an `.assembly.tsv` are cleaned as `nwr template` does, so both
+/// inputs lay out the files the same way. `ftp://` URLs are fetched over HTTPS.
+pub fn read_assemblies(infile: &str) -> anyhow::Result<Vec<Assembly>> {
+    let mut assemblies = vec![];
+    let reader = crate::libs::io::reader(infile)?;
+    for (line_num, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        let species = match fields.len() {
+            3 => fields[2].to_string(),
+            n if n >= 5 => crate::libs::abbr::clean_name(fields[3]),
+            n => anyhow::bail!(
+                "{}:{}: Line has {} fields, expected 3 (url.tsv) or at least 5 (.assembly.tsv): {}",
+                infile,
+                line_num + 1,
+                n,
+                line
+            ),
+        };
+        let name = crate::libs::template::validate_shell_safe(fields[0])
+            .map_err(|e| anyhow::anyhow!("{}:{}: {}", infile, line_num + 1, e))?;
+        let species = crate::libs::template::validate_shell_safe(&species)
+            .map_err(|e| anyhow::anyhow!("{}:{}: {}", infile, line_num + 1, e))?;
+        let url = fields[1].trim_end_matches('/');
+        let url = url
+            .strip_prefix("ftp://")
+            .map_or_else(|| url.to_string(), |rest| format!("https://{rest}"));
+
+        assemblies.push(Assembly {
+            name: name.to_string(),
+            url,
+            species: species.to_string(),
+            line,
+        });
+    }
+    Ok(assemblies)
+}
+
+/// Parse `md5checksums.txt` into file names and their MD5 sums.
+///
+/// Lines look like `a1b2...  ./GCF_000005845.2_ASM584v2_genomic.fna.gz`.
+#[must_use]
+pub fn parse_md5checksums(text: &str) -> BTreeMap<String, String> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let md5 = fields.next()?;
+            let name = fields.next_back()?;
+            let name = name.strip_prefix("./").unwrap_or(name);
+            Some((name.to_string(), md5.to_lowercase()))
+        })
+        .collect()
+}
+
+/// Download the files of `asm` with the given `suffixes` into its directory
+/// under `outdir`, and check them against `md5checksums.txt`.
+///
+/// Files that are already there and match are kept. Files the assembly
+/// doesn't have, e.g. proteins of an unannotated genome, are skipped with a
+/// warning. Returns the names of the files checked.
+pub fn fetch_assembly(
+    conn: &HttpConnection,
+    asm: &Assembly,
+    outdir: &Path,
+    suffixes: &[&str],
+) -> anyhow::Result<Vec<String>> {
+    let dir = asm.dir(outdir);
+    std::fs::create_dir_all(&dir)?;
+
+    let md5_path = dir.join("md5checksums.txt");
+    let is_empty = std::fs::metadata(&md5_path).map_or(true, |m| m.len() == 0);
+    if is_empty {
+        fetch_md5checksums(conn, asm, &md5_path)?;
+    }
+    let mut md5_of = parse_md5checksums(&std::fs::read_to_string(&md5_path)?);
+    let mut is_refreshed = is_empty;
+
+    let mut checked = vec![];
+    for file_name in asm.file_names(suffixes) {
+        let Some(expected) = md5_of.get(&file_name) else {
+            warn!("{}: no {file_name}", asm.name);
+            continue;
+        };
+        let path = dir.join(&file_name);
+        if path.is_file() && file_md5(&path)? == *expected {
+            checked.push(file_name);
+            continue;
+        }
+
+        info!("{}: {file_name}", asm.name);
+        conn.download(&format!("{}/{file_name}", asm.url), &path)?;
+        let mut expected = expected.clone();
+        let digest = file_md5(&path)?;
+        if digest != expected && !is_refreshed {
+            // A stale md5checksums.txt from an earlier run, or an assembly
+            // NCBI has since updated in place
+            info!("{}: md5checksums.txt", asm.name);
+            fetch_md5checksums(conn, asm, &md5_path)?;
+            md5_of = parse_md5checksums(&std::fs::read_to_string(&md5_path)?);
+            is_refreshed = true;
+            expected = md5_of.get(&file_name).cloned().unwrap_or_default();
+        }
+        if digest != expected {
+            std::fs::remove_file(&path)?;
+            anyhow::bail!(
+                "MD5 check of {file_name} failed. Expected: {expected}, Computed: {digest}"
+            );
+        }
+        checked.push(file_name);
+    }
+
+    Ok(checked)
+}
+
+/// Download `md5checksums.txt` of `asm` to `path` through a temporary file,
+/// so an interrupted run never leaves a truncated one behind.
+fn fetch_md5checksums(
+    conn: &HttpConnection,
+    asm: &Assembly,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let body = conn.read(&format!("{}/md5checksums.txt", asm.url))?;
+    let part = path.with_extension("txt.part");
+    std::fs::write(&part, body)?;
+    std::fs::rename(&part, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_md5checksums() {
+        let text = "\
+0123456789abcdef0123456789ABCDEF  ./GCF_000005845.2_ASM584v2_genomic.fna.gz
+fedcba9876543210fedcba9876543210  ./GCF_000005845.2_ASM584v2_cds_from_genomic.fna.gz
+
+";
+        let md5_of = parse_md5checksums(text);
+        assert_eq!(md5_of.len(), 2);
+        assert_eq!(
+            md5_of["GCF_000005845.2_ASM584v2_genomic.fna.gz"],
+            "0123456789abcdef0123456789abcdef"
+        );
+    }
+
+    #[test]
+    fn test_read_assemblies() {
+        let tempdir = tempfile::TempDir::new().unwrap();
+        let url_tsv = tempdir.path().join("url.tsv");
+        std::fs::write(
+            &url_tsv,
+            "Es_coli_K_12_MG1655\tftp://ftp.ncbi.nlm.nih.gov/genomes/all/GCF/000/005/845/GCF_000005845.2_ASM584v2\tEscherichia_coli\n",
+        )
+        .unwrap();
+        let assembly_tsv = tempdir.path().join("Ecoli.assembly.tsv");
+        std::fs::write(
+            &assembly_tsv,
+            "#name\tftp_path\tbiosample\tspecies\tassembly_level\nEs_coli_K_12_MG1655\thttps://ftp.ncbi.nlm.nih.gov/genomes/all/GCF/000/005/845/GCF_000005845.2_ASM584v2/\tSAMN02604091\tEscherichia coli\tComplete Genome\n",
+        )
+        .unwrap();
+
+        let from_url = read_assemblies(&url_tsv.to_string_lossy()).unwrap();
+        let from_assembly = read_assemblies(&assembly_tsv.to_string_lossy()).unwrap();
+        assert_eq!(from_url.len(), 1);
+        assert_eq!(from_assembly.len(), 1);
+        assert_eq!(from_url[0].species, from_assembly[0].species);
+        assert!(from_url[0].url.starts_with("https://"));
+
+        let names = from_assembly[0].file_names(&["_genomic.fna.gz"]);
+        assert_eq!(names, vec!["GCF_000005845.2_ASM584v2_genomic.fna.gz"]);
+        assert_eq!(
+            from_url[0].dir(Path::new("ASSEMBLY")),
+            Path::new("ASSEMBLY/Escherichia_coli/Es_coli_K_12_MG1655")
+        );
+
+        std::fs::write(&url_tsv, "a\tb\tc\td\n").unwrap();
+        assert!(read_assemblies(&url_tsv.to_string_lossy()).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use log::info;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// The lock file in the nwr directory.
+pub const LOCK_FILE: &str = ".nwr.lock";
+
+/// Seconds to wait for a lock before giving up; `NWR_LOCK_TIMEOUT` overrides it.
+pub const LOCK_TIMEOUT: u64 = 600;
+
+/// Directories locked by this process, so nested commands, like `nwr install`
+/// running `nwr txdb`, don't wait for themselves.
+static HELD: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// An exclusive lock on a directory, released when dropped.
+///
+/// The lock is advisory: it keeps other nwr commands out, not other programs.
+/// The lock file holds the process ID and command line of its owner, to name
+/// it in the errors of those waiting.
+pub struct DirLock {
+    file: Option<File>,
+    dir: PathBuf,
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.take() {
+            let _ = file.set_len(0);
+            let _ = fs2::FileExt::unlock(&file);
+            HELD.lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get_or_insert_with(HashSet::new)
+                .remove(&self.dir);
+        }
+    }
+}
+
+/// Lock `dir` for a command that writes it, waiting for other nwr commands
+/// that write it, or that hold a [`read_lock`] on it, to finish.
+pub fn lock_dir(dir: &Path) -> anyhow::Result<DirLock> {
+    std::fs::create_dir_all(dir)?;
+    let dir = dir.canonicalize()?;
+    if is_held(&dir) {
+        return Ok(DirLock { file: None, dir });
+    }
+
+    let path = dir.join(LOCK_FILE);
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)?;
+    if !acquire(&file, &path, true)? {
+        anyhow::bail!(
+            "{} is in use by {}; try again when it finishes, or set NWR_LOCK_TIMEOUT to wait longer",
+            dir.display(),
+            holder(&path)
+        );
+    }
+
+    file.set_len(0)?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    file.write_fmt(format_args!(
+        "pid {}: nwr {}\n",
+        std::process::id(),
+        args.join(" ")
+    ))?;
+    file.flush()?;
+
+    HELD.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get_or_insert_with(HashSet::new)
+        .insert(dir.clone());
+    Ok(DirLock {
+        file: Some(file),
+        dir,
+    })
+}
+
+/// Wait until no nwr command is writing `dir`, without holding it.
+///
+/// Enough for commands that only open databases: those are swapped in whole,
+/// so a connection opened afterwards keeps reading a consistent database even
+/// if a rebuild starts later, and that rebuild doesn't wait for the reader.
+/// Commands reading other files take a [`read_lock`] instead. Directories
+/// without a lock file, e.g. read-only copies, are not waited for.
+pub fn wait_for_writers(dir: &Path) -> anyhow::Result<()> {
+    let Ok(dir) = dir.canonicalize() else {
+        return Ok(());
+    };
+    if is_held(&dir) {
+        return Ok(());
+    }
+
+    let path = dir.join(LOCK_FILE);
+    let Ok(file) = File::open(&path) else {
+        return Ok(());
+    };
+    if !acquire(&file, &path, false)? {
+        anyhow::bail!(
+            "{} is being rebuilt by {}; try again when it finishes, or set NWR_LOCK_TIMEOUT to wait longer",
+            dir.display(),
+            holder(&path)
+        );
+    }
+    fs2::FileExt::unlock(&file)?;
+    Ok(())
+}
+
+/// A shared lock on a directory, released when dropped.
+pub struct ReadLock {
+    file: Option<File>,
+}
+
+impl Drop for ReadLock {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.take() {
+            let _ = fs2::FileExt::unlock(&file);
+        }
+    }
+}
+
+/// Lock `dir` for a command that reads files in it which are rewritten in
+/// place, like `taxdump.tar.gz`, waiting for commands writing it to finish.
+/// Writers wait for the lock to be dropped.
+///
+/// Directories where the lock file can't be created, e.g. read-only copies,
+/// are read without a lock.
+pub fn read_lock(dir: &Path) -> anyhow::Result<ReadLock> {
+    let Ok(dir) = dir.canonicalize() else {
+        return Ok(ReadLock { file: None });
+    };
+    if is_held(&dir) {
+        return Ok(ReadLock { file: None });
+    }
+
+    let path = dir.join(LOCK_FILE);
+    let Ok(file) = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+    else {
+        return Ok(ReadLock { file: None });
+    };
+    if !acquire(&file, &path, false)? {
+        anyhow::bail!(
+            "{} is being rebuilt by {}; try again when it finishes, or set NWR_LOCK_TIMEOUT to wait longer",
+            dir.display(),
+            holder(&path)
+        );
+    }
+    Ok(ReadLock { file: Some(file) })
+}
+
+fn is_held(dir: &Path) -> bool {
+    HELD.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .is_some_and(|held| held.contains(dir))
+}
+
+fn timeout() -> Duration {
+    let secs = std::env::var("NWR_LOCK_TIMEOUT")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .unwrap_or(LOCK_TIMEOUT);
+    Duration::from_secs(secs)
+}
+
+/// Who holds the lock, as written in the lock file.
+fn holder(path: &Path) -> String {
+    let owner = std::fs::read_to_string(path).unwrap_or_default();
+    let owner = owner.trim();
+    if owner.is_empty() {
+        "another nwr command".to_string()
+    } else {
+        format!("another nwr command ({owner})")
+    }
+}
+
+/// Try to lock `file` until the timeout passes; `false` if it never came free.
+fn acquire(file: &File, path: &Path, is_exclusive: bool) -> anyhow::Result<bool> {
+    let timeout = timeout();
+    let start = Instant::now();
+    let mut is_waiting = false;
+    loop {
+        let result = if is_exclusive {
+            fs2::FileExt::try_lock_exclusive(file)
+        } else {
+            fs2::FileExt::try_lock_shared(file)
+        };
+        match result {
+            Ok(()) => return Ok(true),
+            Err(err)
+                if err.raw_os_error() == fs2::lock_contended_error().raw_os_error() => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return Ok(false);
+        }
+        if !is_waiting {
+            info!("Waiting for {}", holder(path));
+            is_waiting = true;
+        }
+        std::thread::sleep((timeout - elapsed).min(Duration::from_millis(200)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_dir_nested() {
+        let tempdir = tempfile::TempDir::new().unwrap();
+
+        let lock = lock_dir(tempdir.path()).unwrap();
+        let content = std::fs::read_to_string(tempdir.path().join(LOCK_FILE)).unwrap();
+        assert!(content.starts_with(&format!("pid {}: nwr", std::process::id())));
+
+        // The same process doesn't wait for itself
+        let nested = lock_dir(tempdir.path()).unwrap();
+        wait_for_writers(tempdir.path()).unwrap();
+        drop(nested);
+        assert!(is_held(&tempdir.path().canonicalize().unwrap()));
+
+        drop(lock);
+        assert!(!is_held(&tempdir.path().canonicalize().unwrap()));
+        let content = std::fs::read_to_string(tempdir.path().join(LOCK_FILE)).unwrap();
+        assert!(content.is_empty());
+    }
+
+    #[test]
+    fn test_read_lock_blocks_writers() {
+        let tempdir = tempfile::TempDir::new().unwrap();
+        let reader = read_lock(tempdir.path()).unwrap();
+
+        // Another handle stands in for another process
+        let file = File::open(tempdir.path().join(LOCK_FILE)).unwrap();
+        assert!(fs2::FileExt::try_lock_exclusive(&file).is_err());
+        assert!(fs2::FileExt::try_lock_shared(&file).is_ok());
+        fs2::FileExt::unlock(&file).unwrap();
+
+        drop(reader);
+        assert!(fs2::FileExt::try_lock_exclusive(&file).is_ok());
+    }
+
+    #[test]
+    fn test_wait_for_writers_without_lock_file() {
+        let tempdir = tempfile::TempDir::new().unwrap();
+        wait_for_writers(tempdir.path()).unwrap();
+        wait_for_writers(&tempdir.path().join("missing")).unwrap();
+        assert!(!tempdir.path().join(LOCK_FILE).exists());
+    }
+}
@@ -4,12 +4,22 @@
 
 /// Abbreviation generation for strain/species/genus names.
 pub mod abbr;
+/// Offline bundles of the downloaded files.
+pub mod bundle;
 /// Database primitives shared by import commands.
 pub mod db;
+/// Changes between two taxonomy releases.
+pub mod diff;
+/// Integrity checks of the nwr directory.
+pub mod doctor;
 /// NCBI taxonomy and assembly report downloader.
 pub mod download;
+/// Native downloads of assembly files.
+pub mod fetch;
 /// I/O helpers returning `Result` instead of panicking.
 pub mod io;
+/// Advisory locking of the nwr directory.
+pub mod lock;
 /// Sequence metadata database builder.
 pub mod seqdb;
 /// NCBI taxonomy queries and operations.
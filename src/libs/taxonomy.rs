@@ -1,6 +1,9 @@
+use crate::libs::db::table_exists;
 use anyhow::Context;
-use std::collections::HashMap;
+use log::warn;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::sync::{LazyLock, Mutex, MutexGuard, PoisonError};
 
 /// Chunk size for `SQLite` `IN (...)` placeholder limits in [`get_taxon`].
 const CHUNK_SIZE: usize = 900;
@@ -16,6 +19,18 @@ pub struct Taxon {
     pub rank: String,
     /// NCBI division name.
     pub division: String,
+    /// Genetic code ID (NCBI translation table).
+    pub gc_id: i64,
+    /// Genetic code name, if known.
+    pub gc_name: Option<String>,
+    /// Whether the genetic code is inherited from the parent.
+    pub gc_inherited: bool,
+    /// Mitochondrial genetic code ID.
+    pub mgc_id: i64,
+    /// Mitochondrial genetic code name, if known.
+    pub mgc_name: Option<String>,
+    /// Whether the mitochondrial genetic code is inherited from the parent.
+    pub mgc_inherited: bool,
     /// Map of name classes to their values (scientific names, synonyms, etc.).
     pub names: HashMap<String, Vec<String>>,
     /// Optional NCBI comments for this taxon.
@@ -70,6 +85,22 @@ impl std::fmt::Display for Taxon {
 
         writeln!(f, "Part of the {}.", self.division)?;
 
+        if self.gc_id > 0 {
+            write!(f, "Genetic code: {}", self.gc_id)?;
+            if let Some(ref name) = self.gc_name {
+                write!(f, " ({name})")?;
+            }
+            writeln!(f)?;
+        }
+
+        if self.mgc_id > 0 {
+            write!(f, "Mitochondrial genetic code: {}", self.mgc_id)?;
+            if let Some(ref name) = self.mgc_name {
+                write!(f, " ({name})")?;
+            }
+            writeln!(f)?;
+        }
+
         if let Some(ref comments) = self.comments {
             writeln!(f, "\nComments: {comments}")?;
         }
@@ -105,16 +136,38 @@ pub fn get_nwr_dir(
         .map_or_else(nwr_path, |dir| Ok(Path::new(dir).to_path_buf()))
 }
 
-/// Connect taxonomy.sqlite in this dir
+/// Database file of a taxonomy backend
+///
+/// `ncbi` is the NCBI Taxonomy built from `taxdump`; `gtdb` is the GTDB
+/// taxonomy built by `nwr txdb --gtdb`.
+///
+/// ```
+/// assert_eq!(nwr::txdb_filename("ncbi").unwrap(), "taxonomy.sqlite");
+/// assert_eq!(nwr::txdb_filename("gtdb").unwrap(), "gtdb.sqlite");
+/// assert!(nwr::txdb_filename("silva").is_err());
+/// ```
+pub fn txdb_filename(taxonomy: &str) -> anyhow::Result<&'static str> {
+    match taxonomy {
+        "ncbi" => Ok("taxonomy.sqlite"),
+        "gtdb" => Ok("gtdb.sqlite"),
+        _ => anyhow::bail!("Unknown taxonomy: {taxonomy}"),
+    }
+}
+
+/// Connect the database of a taxonomy backend in this dir
 ///
 /// ```
 /// let path = std::path::PathBuf::from("tests/nwr/");
-/// let conn = nwr::connect_txdb(&path).unwrap();
+/// let conn = nwr::connect_taxonomy(&path, "gtdb").unwrap();
 ///
-/// assert_eq!(conn.path().unwrap().to_str().unwrap(), "tests/nwr/taxonomy.sqlite");
+/// assert_eq!(conn.path().unwrap().to_str().unwrap(), "tests/nwr/gtdb.sqlite");
 /// ```
-pub fn connect_txdb(dir: &Path) -> anyhow::Result<rusqlite::Connection> {
-    let dbfile = dir.join("taxonomy.sqlite");
+pub fn connect_taxonomy(
+    dir: &Path,
+    taxonomy: &str,
+) -> anyhow::Result<rusqlite::Connection> {
+    let dbfile = dir.join(txdb_filename(taxonomy)?);
+    crate::libs::lock::wait_for_writers(dir)?;
     let conn = rusqlite::Connection::open(&dbfile)
         .with_context(|| format!("failed to open {}", dbfile.display()))?;
 
@@ -125,18 +178,203 @@ pub fn connect_txdb(dir: &Path) -> anyhow::Result<rusqlite::Connection> {
         |row| row.get(0),
     )?;
     if table_count < 3 {
+        let command = if taxonomy == "gtdb" {
+            "nwr txdb --gtdb"
+        } else {
+            "nwr txdb"
+        };
         anyhow::bail!(
-            "{} is missing required tables; run `nwr txdb` to initialize it",
+            "{} is missing required tables; run `{command}` to initialize it",
             dbfile.display()
         );
     }
+    crate::libs::db::check_schema_version(
+        &conn,
+        &dbfile,
+        crate::libs::db::TX_SCHEMA_VERSION,
+    )?;
 
     Ok(conn)
 }
 
+/// Connect taxonomy.sqlite in this dir
+///
+/// ```
+/// let path = std::path::PathBuf::from("tests/nwr/");
+/// let conn = nwr::connect_txdb(&path).unwrap();
+///
+/// assert_eq!(conn.path().unwrap().to_str().unwrap(), "tests/nwr/taxonomy.sqlite");
+/// ```
+pub fn connect_txdb(dir: &Path) -> anyhow::Result<rusqlite::Connection> {
+    connect_taxonomy(dir, "ncbi")
+}
+
+/// Answers of [`table_has_rows`], keyed by database file and table.
+static TABLE_HAS_ROWS: LazyLock<Mutex<HashMap<(std::path::PathBuf, String), bool>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The cache of [`table_has_rows`]; a panic elsewhere can't leave it half-written.
+fn table_has_rows_cache(
+) -> MutexGuard<'static, HashMap<(std::path::PathBuf, String), bool>> {
+    TABLE_HAS_ROWS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Forget which tables hold rows, after a database was rebuilt or upgraded.
+///
+/// Called when a [`crate::libs::db::StagedDb`] is committed and when lineages
+/// are built in place by `nwr migrate`, so that later lookups in the same
+/// process see the new tables.
+pub fn forget_table_rows() {
+    table_has_rows_cache().clear();
+}
+
+/// Returns `true` if `table` exists and holds at least one row.
+///
+/// The `new_taxdump` tables are always created by `nwr txdb` but stay empty
+/// when their .dmp files were not available. The answer is checked once per
+/// database file, as the lineage lookups that ask it run for every taxon;
+/// in-memory databases are checked every time.
+fn table_has_rows(conn: &rusqlite::Connection, table: &str) -> anyhow::Result<bool> {
+    let key = conn
+        .path()
+        .filter(|path| !path.as_os_str().is_empty())
+        .map(|path| (path.to_path_buf(), table.to_string()));
+    if let Some(key) = &key {
+        if let Some(&has_rows) = table_has_rows_cache().get(key) {
+            return Ok(has_rows);
+        }
+    }
+
+    let has_rows = table_exists(conn, table)?
+        && conn.query_row(
+            &format!("SELECT EXISTS (SELECT 1 FROM {table})"),
+            [],
+            |row| row.get(0),
+        )?;
+    if let Some(key) = key {
+        table_has_rows_cache().insert(key, has_rows);
+    }
+    Ok(has_rows)
+}
+
+/// Merged tax IDs to their current IDs
+///
+/// Returns a map from each ID in `ids` that NCBI has merged into another taxon
+/// to the surviving tax ID. IDs that were not merged are absent from the map.
+///
+/// ```
+/// let path = std::path::PathBuf::from("tests/nwr/");
+/// let conn = nwr::connect_txdb(&path).unwrap();
+///
+/// let merged = nwr::get_merged(&conn, &[12339, 12340]).unwrap();
+///
+/// assert_eq!(merged.get(&12339), Some(&12340));
+/// assert_eq!(merged.get(&12340), None);
+/// ```
+pub fn get_merged(
+    conn: &rusqlite::Connection,
+    ids: &[i64],
+) -> anyhow::Result<HashMap<i64, i64>> {
+    let mut merged: HashMap<i64, i64> = HashMap::new();
+    if ids.is_empty() || !table_exists(conn, "merged")? {
+        return Ok(merged);
+    }
+
+    for chunk in ids.chunks(CHUNK_SIZE) {
+        let placeholders = (0..chunk.len()).map(|_| "?").collect::<Vec<_>>().join(",");
+        let sql = format!(
+            "
+            SELECT old_tax_id, new_tax_id
+            FROM merged
+            WHERE old_tax_id IN ({placeholders})
+            "
+        );
+
+        let mut stmt = conn.prepare(&sql)?;
+        let mut rows = stmt.query(rusqlite::params_from_iter(chunk.iter()))?;
+        while let Some(row) = rows.next()? {
+            merged.insert(row.get(0)?, row.get(1)?);
+        }
+    }
+
+    Ok(merged)
+}
+
+/// Whether the tax ID has been deleted from NCBI Taxonomy
+///
+/// ```
+/// let path = std::path::PathBuf::from("tests/nwr/");
+/// let conn = nwr::connect_txdb(&path).unwrap();
+///
+/// assert!(nwr::is_deleted(&conn, 12341).unwrap());
+/// assert!(!nwr::is_deleted(&conn, 12340).unwrap());
+/// ```
+pub fn is_deleted(conn: &rusqlite::Connection, id: i64) -> anyhow::Result<bool> {
+    if !table_exists(conn, "delnodes")? {
+        return Ok(false);
+    }
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM delnodes WHERE tax_id = ?1",
+        [id],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Error for a tax ID that is absent from `node`.
+///
+/// Deleted IDs are reported distinctly so users can tell a retired taxon from
+/// a typo.
+fn missing_id_error(conn: &rusqlite::Connection, id: i64) -> anyhow::Error {
+    match is_deleted(conn, id) {
+        Ok(true) => anyhow::anyhow!("Tax ID {id} has been deleted from NCBI Taxonomy"),
+        _ => anyhow::anyhow!("No such ID: {id}"),
+    }
+}
+
+/// Resolve a tax ID to its current ID
+///
+/// IDs merged into another taxon are redirected to the surviving tax ID with a
+/// warning, and deleted IDs are reported as errors. All other IDs, including
+/// unknown ones, are returned unchanged so that callers report them in their
+/// own context.
+///
+/// ```
+/// let path = std::path::PathBuf::from("tests/nwr/");
+/// let conn = nwr::connect_txdb(&path).unwrap();
+///
+/// assert_eq!(nwr::resolve_tax_id(&conn, 12340).unwrap(), 12340);
+/// assert_eq!(nwr::resolve_tax_id(&conn, 12339).unwrap(), 12340);
+/// assert!(nwr::resolve_tax_id(&conn, 12341).is_err());
+/// ```
+pub fn resolve_tax_id(conn: &rusqlite::Connection, id: i64) -> anyhow::Result<i64> {
+    let exists: i64 =
+        conn.query_row("SELECT COUNT(*) FROM node WHERE tax_id = ?1", [id], |row| {
+            row.get(0)
+        })?;
+    if exists > 0 {
+        return Ok(id);
+    }
+
+    if let Some(new_id) = get_merged(conn, &[id])?.get(&id) {
+        warn!("Tax ID {id} has been merged into {new_id}");
+        return Ok(*new_id);
+    }
+
+    if is_deleted(conn, id)? {
+        anyhow::bail!("Tax ID {id} has been deleted from NCBI Taxonomy");
+    }
+
+    Ok(id)
+}
+
 /// Build fallback name candidates when an exact match fails.
 ///
-/// Handles two common NCBI naming quirks:
+/// Underscores typed for spaces (e.g. `Homo_sapiens`) are replaced first, as
+/// names like GTDB's `Bacillus_A` or genome accessions contain them. Then
+/// handles two common NCBI naming quirks:
 /// - `sp` / `sp.` interchange (e.g. "Cladobotryum sp" -> "Cladobotryum sp.")
 /// - Stripping trailing nomenclatural qualifiers (e.g. "X nom inval" -> "X",
 ///   matching the synonym when the scientific name is "X (nom. inval.)")
@@ -148,6 +386,13 @@ pub fn connect_txdb(dir: &Path) -> anyhow::Result<rusqlite::Connection> {
 fn fallback_candidates(name: &str) -> Vec<String> {
     let mut candidates = Vec::new();
 
+    if name.contains('_') {
+        let spaced = name.replace('_', " ");
+        candidates.extend(fallback_candidates(&spaced));
+        candidates.insert(0, spaced);
+        return candidates;
+    }
+
     // sp <-> sp. (only the trailing species-unspecified marker)
     if name.ends_with(" sp") && !name.ends_with(" sp.") {
         candidates.push(format!("{name}."));
@@ -190,6 +435,131 @@ fn fallback_candidates(name: &str) -> Vec<String> {
     candidates
 }
 
+/// A name matching several taxa (homonyms)
+///
+/// Returned by [`get_tax_id`] and the term converters when a name can't be
+/// resolved to a single taxon. Disambiguate with `Name@Ancestor`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmbiguousNameError {
+    pub name: String,
+    /// `(tax_id, rank, lineage)` of each candidate
+    pub candidates: Vec<(i64, String, String)>,
+}
+
+impl std::fmt::Display for AmbiguousNameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Ambiguous name: {} matches {} taxa; use `Name@Ancestor` or `--within` to pick one:",
+            self.name,
+            self.candidates.len()
+        )?;
+        for (tax_id, rank, lineage) in &self.candidates {
+            write!(f, "\n    {tax_id} ({rank}): {lineage}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AmbiguousNameError {}
+
+/// Build an [`AmbiguousNameError`], describing candidates by their lineages.
+fn ambiguous_name_error(
+    conn: &rusqlite::Connection,
+    name: &str,
+    ids: &[i64],
+) -> anyhow::Error {
+    let candidates = ids
+        .iter()
+        .map(|id| {
+            // Lineages only help to tell candidates apart; don't fail on them
+            let lineage = get_lineage(conn, *id).unwrap_or_default();
+            let rank = lineage.last().map(|n| n.rank.clone()).unwrap_or_default();
+            let names: Vec<&str> = lineage
+                .iter()
+                .filter(|n| n.tax_id != 1)
+                .map(|n| n.scientific_name().unwrap_or("Unknown"))
+                .collect();
+            (*id, rank, names.join("; "))
+        })
+        .collect();
+
+    AmbiguousNameError {
+        name: name.to_string(),
+        candidates,
+    }
+    .into()
+}
+
+/// Split a `Name@Ancestor` term into the name and the ancestor term.
+///
+/// The ancestor may itself be a `Name@Ancestor` term.
+fn split_ancestor(term: &str) -> (&str, Option<&str>) {
+    match term.split_once('@') {
+        Some((name, ancestor))
+            if !name.trim().is_empty() && !ancestor.trim().is_empty() =>
+        {
+            (name.trim(), Some(ancestor.trim()))
+        }
+        _ => (term, None),
+    }
+}
+
+/// Add the ancestor of `--within` to a term.
+///
+/// Numeric terms and terms that already have an ancestor are left as they are.
+///
+/// ```
+/// assert_eq!(nwr::with_ancestor("Bacillus", Some("Bacteria")), "Bacillus@Bacteria");
+/// assert_eq!(nwr::with_ancestor("1386", Some("Bacteria")), "1386");
+/// assert_eq!(nwr::with_ancestor("Bacillus@Insecta", Some("Bacteria")), "Bacillus@Insecta");
+/// assert_eq!(nwr::with_ancestor("Bacillus", None), "Bacillus");
+/// ```
+#[must_use]
+pub fn with_ancestor(term: &str, within: Option<&str>) -> String {
+    match within {
+        Some(ancestor) if term.trim().parse::<i64>().is_err() && !term.contains('@') => {
+            format!("{term}@{ancestor}")
+        }
+        _ => term.to_string(),
+    }
+}
+
+/// Candidate tax IDs of names as `(tax_id, is_scientific_name)` pairs
+fn name_candidates(
+    conn: &rusqlite::Connection,
+    names: &[String],
+    name_to_ids: &mut HashMap<String, Vec<(i64, bool)>>,
+) -> anyhow::Result<()> {
+    for chunk in names.chunks(CHUNK_SIZE) {
+        let placeholders = (0..chunk.len()).map(|_| "?").collect::<Vec<_>>().join(",");
+        let sql = format!(
+            "
+            SELECT name, tax_id, MAX(name_class = 'scientific name')
+            FROM name
+            WHERE name_class IN ('scientific name', 'synonym', 'genbank synonym')
+            AND name IN ({placeholders})
+            GROUP BY name, tax_id
+            ORDER BY name, tax_id
+            "
+        );
+
+        let mut stmt = conn.prepare(&sql)?;
+        let mut rows = stmt.query(rusqlite::params_from_iter(chunk.iter()))?;
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+            let tax_id: i64 = row.get(1)?;
+            let is_scientific: bool = row.get(2)?;
+            name_to_ids
+                .entry(name)
+                .or_default()
+                .push((tax_id, is_scientific));
+        }
+    }
+
+    Ok(())
+}
+
 /// Names to Taxonomy IDs
 ///
 /// Resolves names via exact match against the `name` table. When an exact
@@ -198,6 +568,14 @@ fn fallback_candidates(name: &str) -> Vec<String> {
 /// - Stripping trailing nomenclatural qualifiers (e.g. "X nom inval" -> "X",
 ///   matching the synonym when the scientific name is "X (nom. inval.)")
 ///
+/// A name matching several taxa resolves to the only one for which it is the
+/// scientific name; otherwise an [`AmbiguousNameError`] is returned.
+/// `Name@Ancestor` keeps only the taxa under `Ancestor`, which can be an ID
+/// or a name.
+///
+/// In a GTDB database, genome accessions (with or without the `RS_`/`GB_`
+/// prefix) resolve to their species.
+///
 /// ```
 /// let path = std::path::PathBuf::from("tests/nwr/");
 /// let conn = nwr::connect_txdb(&path).unwrap();
@@ -209,6 +587,14 @@ fn fallback_candidates(name: &str) -> Vec<String> {
 /// let tax_ids = nwr::get_tax_id(&conn, &names).unwrap();
 ///
 /// assert_eq!(tax_ids, vec![12340, 12347]);
+///
+/// // A synonym of both 370566 and 370559
+/// let names = vec!["Streptococcus pyogenes prophage".to_string()];
+/// let err = nwr::get_tax_id(&conn, &names).unwrap_err();
+/// assert!(err.is::<nwr::AmbiguousNameError>());
+///
+/// let names = vec!["Streptococcus pyogenes prophage@387086".to_string()];
+/// assert_eq!(nwr::get_tax_id(&conn, &names).unwrap(), vec![370566]);
 /// ```
 pub fn get_tax_id(
     conn: &rusqlite::Connection,
@@ -218,36 +604,22 @@ pub fn get_tax_id(
         return Ok(vec![]);
     }
 
-    let mut name_to_id: HashMap<String, i64> = HashMap::new();
+    let bare_names: Vec<String> = names
+        .iter()
+        .map(|name| split_ancestor(name).0.to_string())
+        .collect();
 
-    // 1. Exact match
-    for chunk in names.chunks(CHUNK_SIZE) {
-        let placeholders = (0..chunk.len()).map(|_| "?").collect::<Vec<_>>().join(",");
-        let sql = format!(
-            "
-            SELECT name, MIN(tax_id) AS tax_id
-            FROM name
-            WHERE name_class IN ('scientific name', 'synonym', 'genbank synonym')
-            AND name IN ({placeholders})
-            GROUP BY name
-            "
-        );
+    let mut name_to_ids: HashMap<String, Vec<(i64, bool)>> = HashMap::new();
 
-        let mut stmt = conn.prepare(&sql)?;
-        let mut rows = stmt.query(rusqlite::params_from_iter(chunk.iter()))?;
-        while let Some(row) = rows.next()? {
-            let name: String = row.get(0)?;
-            let tax_id: i64 = row.get(1)?;
-            name_to_id.insert(name, tax_id);
-        }
-    }
+    // 1. Exact match
+    name_candidates(conn, &bare_names, &mut name_to_ids)?;
 
     // 2. Fallback for unresolved names: try common NCBI naming variants
     let mut fallback_queries: Vec<(String, String)> = Vec::new(); // (original, candidate)
-    for name in names {
-        if !name_to_id.contains_key(name) {
+    for name in &bare_names {
+        if !name_to_ids.contains_key(name) {
             for candidate in fallback_candidates(name) {
-                if !name_to_id.contains_key(&candidate) {
+                if !name_to_ids.contains_key(&candidate) {
                     fallback_queries.push((name.clone(), candidate));
                 }
             }
@@ -257,81 +629,315 @@ pub fn get_tax_id(
     if !fallback_queries.is_empty() {
         let candidates: Vec<String> =
             fallback_queries.iter().map(|(_, c)| c.clone()).collect();
-        for chunk in candidates.chunks(CHUNK_SIZE) {
+        name_candidates(conn, &candidates, &mut name_to_ids)?;
+
+        // Map resolved candidates back to original names
+        for (original, candidate) in &fallback_queries {
+            if name_to_ids.contains_key(original) {
+                continue;
+            }
+            if let Some(ids) = name_to_ids.get(candidate) {
+                let ids = ids.clone();
+                name_to_ids.insert(original.clone(), ids);
+            }
+        }
+    }
+
+    // 3. Genome accessions of the GTDB taxonomy map to their species
+    let accessions: Vec<String> = bare_names
+        .iter()
+        .filter(|name| !name_to_ids.contains_key(*name))
+        .map(|name| gtdb_accession(name).to_string())
+        .collect();
+    if !accessions.is_empty() && table_exists(conn, "genome")? {
+        for chunk in accessions.chunks(CHUNK_SIZE) {
             let placeholders =
                 (0..chunk.len()).map(|_| "?").collect::<Vec<_>>().join(",");
             let sql = format!(
                 "
-                SELECT name, MIN(tax_id) AS tax_id
-                FROM name
-                WHERE name_class IN ('scientific name', 'synonym', 'genbank synonym')
-                AND name IN ({placeholders})
-                GROUP BY name
+                SELECT accession, tax_id
+                FROM genome
+                WHERE accession IN ({placeholders})
                 "
             );
             let mut stmt = conn.prepare(&sql)?;
             let mut rows = stmt.query(rusqlite::params_from_iter(chunk.iter()))?;
             while let Some(row) = rows.next()? {
-                let candidate: String = row.get(0)?;
+                let accession: String = row.get(0)?;
                 let tax_id: i64 = row.get(1)?;
-                name_to_id.insert(candidate, tax_id);
+                name_to_ids.insert(accession, vec![(tax_id, true)]);
             }
         }
-
-        // Map resolved candidates back to original names
-        for (original, candidate) in &fallback_queries {
-            if let Some(tax_id) = name_to_id.get(candidate) {
-                name_to_id.insert(original.clone(), *tax_id);
+        for name in &bare_names {
+            let accession = gtdb_accession(name);
+            if accession != name {
+                if let Some(ids) = name_to_ids.get(accession).cloned() {
+                    name_to_ids.entry(name.clone()).or_insert(ids);
+                }
             }
         }
     }
 
-    // 3. Build result vector
+    // 4. Build result vector
     let mut tax_ids = Vec::with_capacity(names.len());
-    for name in names {
-        let tax_id = name_to_id
-            .get(name)
-            .ok_or_else(|| anyhow::anyhow!("No such name: {name}"))?;
-        tax_ids.push(*tax_id);
+    for (name, bare) in names.iter().zip(bare_names.iter()) {
+        let mut ids = name_to_ids
+            .get(bare)
+            .cloned()
+            .ok_or_else(|| no_such_name_error(conn, bare))?;
+
+        if let Some(ancestor) = split_ancestor(name).1 {
+            let ancestor_id = term_to_tax_id(conn, ancestor)?;
+            let mut within = Vec::with_capacity(ids.len());
+            for (tax_id, is_scientific) in ids {
+                if is_descendant(conn, tax_id, ancestor_id)? {
+                    within.push((tax_id, is_scientific));
+                }
+            }
+            if within.is_empty() {
+                anyhow::bail!("No such name: {bare} within {ancestor}");
+            }
+            ids = within;
+        }
+
+        let tax_id = match ids.as_slice() {
+            [(tax_id, _)] => *tax_id,
+            _ => {
+                let scientific: Vec<i64> = ids
+                    .iter()
+                    .filter(|(_, is_scientific)| *is_scientific)
+                    .map(|(tax_id, _)| *tax_id)
+                    .collect();
+                if scientific.len() == 1 {
+                    scientific[0]
+                } else {
+                    let all: Vec<i64> = ids.iter().map(|(tax_id, _)| *tax_id).collect();
+                    return Err(ambiguous_name_error(conn, bare, &all));
+                }
+            }
+        };
+        tax_ids.push(tax_id);
     }
 
     Ok(tax_ids)
 }
 
-/// IDs to Nodes
+/// Error for a name without exact matches, with "did you mean" hints when the
+/// search index is available.
+fn no_such_name_error(conn: &rusqlite::Connection, name: &str) -> anyhow::Error {
+    // Hints are best effort; a failed search must not hide the original error
+    let suggestions = suggest_names(conn, name).unwrap_or_default();
+    if suggestions.is_empty() {
+        anyhow::anyhow!("No such name: {name}")
+    } else {
+        let quoted: Vec<String> = suggestions.iter().map(|s| format!("'{s}'")).collect();
+        anyhow::anyhow!("No such name: {name}. Did you mean {}?", quoted.join(", "))
+    }
+}
+
+/// Up to three close names that `get_tax_id` accepts.
+fn suggest_names(
+    conn: &rusqlite::Connection,
+    name: &str,
+) -> anyhow::Result<Vec<String>> {
+    if !table_exists(conn, "name_fts")? {
+        return Ok(vec![]);
+    }
+
+    let max_distance = (name.chars().count() / 5).max(2);
+    let mut suggestions: Vec<String> = Vec::new();
+    for m in search_names(conn, name, SearchMode::Fuzzy, 50)? {
+        if m.distance > max_distance || name_class_order(&m.name_class) > 1 {
+            continue;
+        }
+        if !suggestions.contains(&m.name) {
+            suggestions.push(m.name);
+        }
+        if suggestions.len() == 3 {
+            break;
+        }
+    }
+
+    Ok(suggestions)
+}
+
+/// How [`search_names`] matches names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Names sharing trigrams with the query, ranked by edit distance
+    Fuzzy,
+    /// Names starting with the query
+    Prefix,
+    /// Names containing the query
+    Substring,
+}
+
+/// A name found by [`search_names`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameMatch {
+    pub tax_id: i64,
+    pub name: String,
+    pub name_class: String,
+    /// Case-insensitive edit distance to the query
+    pub distance: usize,
+}
+
+/// Number of trigram hits considered by fuzzy searches before ranking
+const FUZZY_CANDIDATES: usize = 1000;
+
+/// Rank of name classes in search results; scientific names come first.
+fn name_class_order(name_class: &str) -> u8 {
+    match name_class {
+        "scientific name" => 0,
+        "synonym" | "genbank synonym" | "equivalent name" => 1,
+        "common name" | "genbank common name" => 2,
+        _ => 3,
+    }
+}
+
+/// Case-insensitive Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+
+    prev[b.len()]
+}
+
+/// Search names case-insensitively
+///
+/// Uses the trigram index `name_fts` built by `nwr txdb`. Matches are ranked
+/// by edit distance to the query, then by name class (scientific names
+/// first), and at most `limit` of them are returned. Queries shorter than
+/// three characters, which trigrams can't index, are matched by prefix.
 ///
 /// ```
 /// let path = std::path::PathBuf::from("tests/nwr/");
 /// let conn = nwr::connect_txdb(&path).unwrap();
 ///
-/// let ids = vec![12340, 12347];
-/// let taxa = nwr::get_taxon(&conn, &ids).unwrap();
+/// let matches = nwr::search_names(&conn, "enterobacteria phage 933", nwr::SearchMode::Fuzzy, 5).unwrap();
+/// assert_eq!(matches[0].name, "Enterobacteria phage 933J");
+/// assert_eq!(matches[0].tax_id, 12340);
+/// assert_eq!(matches[0].distance, 1);
 ///
-/// assert_eq!(taxa.get(0).unwrap().tax_id, 12340);
-/// assert_eq!(taxa.get(0).unwrap().parent_tax_id, 12333);
-/// assert_eq!(taxa.get(0).unwrap().rank, "species");
-/// assert_eq!(taxa.get(0).unwrap().division, "Phages");
-/// assert_eq!(taxa.get(1).unwrap().tax_id, 12347);
+/// let matches = nwr::search_names(&conn, "phage 933", nwr::SearchMode::Substring, 10).unwrap();
+/// assert!(matches.iter().all(|m| m.tax_id == 12340));
 /// ```
-pub fn get_taxon(
+pub fn search_names(
     conn: &rusqlite::Connection,
-    ids: &[i64],
-) -> anyhow::Result<Vec<Taxon>> {
-    if ids.is_empty() {
-        return Ok(vec![]);
+    query: &str,
+    mode: SearchMode,
+    limit: usize,
+) -> anyhow::Result<Vec<NameMatch>> {
+    if !table_exists(conn, "name_fts")? {
+        anyhow::bail!("No search index found; rebuild the database with `nwr txdb`");
     }
 
-    let mut taxa_map: HashMap<i64, Taxon> = HashMap::new();
+    let query = query.trim().replace('_', " ");
+    if query.is_empty() {
+        anyhow::bail!("Empty search query");
+    }
+    let lower = query.to_lowercase();
+    let chars: Vec<char> = lower.chars().collect();
+    let mode = if chars.len() < 3 {
+        SearchMode::Prefix
+    } else {
+        mode
+    };
 
-    // Deduplicate ids before querying so that the same tax_id is never fetched
-    // twice (which would push duplicate name entries across chunks). The
-    // original `ids` order is preserved when building the output vector below.
-    let unique_ids: Vec<i64> = {
-        let mut seen = std::collections::HashSet::new();
-        ids.iter().filter(|id| seen.insert(**id)).copied().collect()
+    let mut stmt;
+    let mut rows = match mode {
+        SearchMode::Fuzzy => {
+            // Any shared trigram makes a candidate; bm25 keeps the closest ones
+            let mut trigrams: Vec<String> = chars
+                .windows(3)
+                .map(|w| {
+                    format!("\"{}\"", w.iter().collect::<String>().replace('"', "\"\""))
+                })
+                .collect();
+            trigrams.sort();
+            trigrams.dedup();
+            stmt = conn.prepare(
+                "
+                SELECT name.tax_id, name.name, name.name_class
+                FROM (
+                    SELECT rowid
+                    FROM name_fts
+                    WHERE name_fts MATCH ?1
+                    ORDER BY rank
+                    LIMIT ?2
+                ) hit
+                JOIN name ON name.id = hit.rowid
+                ",
+            )?;
+            stmt.query(rusqlite::params![trigrams.join(" OR "), FUZZY_CANDIDATES])?
+        }
+        SearchMode::Prefix | SearchMode::Substring => {
+            let pattern = if mode == SearchMode::Prefix {
+                format!("{lower}%")
+            } else {
+                format!("%{lower}%")
+            };
+            // LIKE on the trigram table uses the index for patterns of three
+            // or more characters
+            let table = if chars.len() < 3 { "name" } else { "name_fts" };
+            stmt = conn.prepare(&format!(
+                "
+                SELECT name.tax_id, name.name, name.name_class
+                FROM name
+                WHERE name.id IN (
+                    SELECT rowid FROM {table} WHERE name LIKE ?1
+                )
+                "
+            ))?;
+            stmt.query([pattern])?
+        }
     };
 
-    for chunk in unique_ids.chunks(CHUNK_SIZE) {
+    let mut matches = Vec::new();
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(1)?;
+        matches.push(NameMatch {
+            tax_id: row.get(0)?,
+            distance: edit_distance(&query, &name),
+            name,
+            name_class: row.get(2)?,
+        });
+    }
+
+    matches.sort_by(|a, b| {
+        a.distance
+            .cmp(&b.distance)
+            .then_with(|| {
+                name_class_order(&a.name_class).cmp(&name_class_order(&b.name_class))
+            })
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.tax_id.cmp(&b.tax_id))
+    });
+    matches.truncate(limit);
+
+    Ok(matches)
+}
+
+/// Fetch the nodes of `ids` into `taxa_map`.
+///
+/// IDs without a matching node are silently left out of the map.
+fn fetch_taxa(
+    conn: &rusqlite::Connection,
+    ids: &[i64],
+    taxa_map: &mut HashMap<i64, Taxon>,
+) -> anyhow::Result<()> {
+    for chunk in ids.chunks(CHUNK_SIZE) {
         let placeholders = (0..chunk.len()).map(|_| "?").collect::<Vec<_>>().join(",");
         let sql = format!(
             "
@@ -342,10 +948,18 @@ pub fn get_taxon(
                 division.division,
                 name.name_class,
                 name.name,
-                node.comment
+                node.comment,
+                node.gc_id,
+                node.gc_inherited,
+                gc.name,
+                node.mgc_id,
+                node.mgc_inherited,
+                mgc.name
             FROM node
                 INNER JOIN name ON node.tax_id = name.tax_id
                 INNER JOIN division ON node.division_id = division.id
+                LEFT JOIN gencode gc ON node.gc_id = gc.id
+                LEFT JOIN gencode mgc ON node.mgc_id = mgc.id
             WHERE node.tax_id IN ({placeholders})
             "
         );
@@ -358,49 +972,448 @@ pub fn get_taxon(
             let name_class: String = row.get(4)?;
             let name: String = row.get(5)?;
 
-            if let std::collections::hash_map::Entry::Vacant(e) = taxa_map.entry(tax_id)
-            {
-                let mut taxon = Taxon {
-                    tax_id,
-                    parent_tax_id: row.get(1)?,
-                    rank: row.get(2)?,
-                    division: row.get(3)?,
-                    ..Default::default()
-                };
-                let comments: String = row.get(6)?;
-                if !comments.is_empty() {
-                    taxon.comments = Some(comments);
-                }
-                e.insert(taxon);
-            }
+            if let std::collections::hash_map::Entry::Vacant(e) = taxa_map.entry(tax_id)
+            {
+                let mut taxon = Taxon {
+                    tax_id,
+                    parent_tax_id: row.get(1)?,
+                    rank: row.get(2)?,
+                    division: row.get(3)?,
+                    gc_id: row.get(7)?,
+                    gc_inherited: row.get(8)?,
+                    gc_name: row.get(9)?,
+                    mgc_id: row.get(10)?,
+                    mgc_inherited: row.get(11)?,
+                    mgc_name: row.get(12)?,
+                    ..Default::default()
+                };
+                let comments: String = row.get(6)?;
+                if !comments.is_empty() {
+                    taxon.comments = Some(comments);
+                }
+                e.insert(taxon);
+            }
+
+            if let Some(taxon) = taxa_map.get_mut(&tax_id) {
+                taxon.names.entry(name_class).or_default().push(name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// IDs to Nodes
+///
+/// Merged IDs are followed to their current taxa, so the returned `tax_id` may
+/// differ from the requested one. Deleted IDs are reported as such.
+///
+/// ```
+/// let path = std::path::PathBuf::from("tests/nwr/");
+/// let conn = nwr::connect_txdb(&path).unwrap();
+///
+/// let ids = vec![12340, 12347];
+/// let taxa = nwr::get_taxon(&conn, &ids).unwrap();
+///
+/// assert_eq!(taxa.get(0).unwrap().tax_id, 12340);
+/// assert_eq!(taxa.get(0).unwrap().parent_tax_id, 12333);
+/// assert_eq!(taxa.get(0).unwrap().rank, "species");
+/// assert_eq!(taxa.get(0).unwrap().division, "Phages");
+/// assert_eq!(taxa.get(0).unwrap().gc_id, 11);
+/// assert_eq!(taxa.get(1).unwrap().tax_id, 12347);
+///
+/// // 12339 has been merged into 12340
+/// let taxa = nwr::get_taxon(&conn, &[12339]).unwrap();
+/// assert_eq!(taxa.get(0).unwrap().tax_id, 12340);
+/// ```
+pub fn get_taxon(
+    conn: &rusqlite::Connection,
+    ids: &[i64],
+) -> anyhow::Result<Vec<Taxon>> {
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut taxa_map: HashMap<i64, Taxon> = HashMap::new();
+
+    // Deduplicate ids before querying so that the same tax_id is never fetched
+    // twice (which would push duplicate name entries across chunks). The
+    // original `ids` order is preserved when building the output vector below.
+    let unique_ids: Vec<i64> = {
+        let mut seen = std::collections::HashSet::new();
+        ids.iter().filter(|id| seen.insert(**id)).copied().collect()
+    };
+
+    fetch_taxa(conn, &unique_ids, &mut taxa_map)?;
+
+    // Follow merged IDs to their surviving taxa.
+    let missing: Vec<i64> = unique_ids
+        .iter()
+        .filter(|id| !taxa_map.contains_key(id))
+        .copied()
+        .collect();
+    let merged = get_merged(conn, &missing)?;
+    if !merged.is_empty() {
+        let mut targets: Vec<i64> = Vec::new();
+        for (old_id, new_id) in &merged {
+            warn!("Tax ID {old_id} has been merged into {new_id}");
+            if !taxa_map.contains_key(new_id) && !targets.contains(new_id) {
+                targets.push(*new_id);
+            }
+        }
+        fetch_taxa(conn, &targets, &mut taxa_map)?;
+    }
+
+    // When the caller passes duplicate IDs, or several IDs resolve to the same
+    // merged taxon, we must keep the map intact so every occurrence can be
+    // resolved. In the common case we drain the map via `remove` to avoid
+    // cloning each `Taxon` (which owns a `HashMap` of names) — a significant
+    // win for large clade queries.
+    let keep_map = ids.len() != unique_ids.len() || !merged.is_empty();
+    let mut taxa = Vec::with_capacity(ids.len());
+    for id in ids {
+        let key = merged.get(id).unwrap_or(id);
+        let taxon = if keep_map {
+            taxa_map
+                .get(key)
+                .ok_or_else(|| missing_id_error(conn, *id))?
+                .clone()
+        } else {
+            taxa_map
+                .remove(key)
+                .ok_or_else(|| missing_id_error(conn, *id))?
+        };
+        taxa.push(taxon);
+    }
+
+    Ok(taxa)
+}
+
+/// An NCBI genetic code (translation table) from gencode.dmp.
+#[derive(Debug, Clone, Default)]
+pub struct GeneticCode {
+    /// Genetic code ID, as used by `transl_table`.
+    pub id: i64,
+    /// Abbreviation, often empty.
+    pub abbreviation: String,
+    /// Descriptive name.
+    pub name: String,
+    /// Amino acids of the 64 codons, in TCAG order.
+    pub cde: String,
+    /// Start codons of the 64 codons, in TCAG order.
+    pub starts: String,
+}
+
+/// Genetic code by ID
+///
+/// ```
+/// let path = std::path::PathBuf::from("tests/nwr/");
+/// let conn = nwr::connect_txdb(&path).unwrap();
+///
+/// let code = nwr::get_genetic_code(&conn, 11).unwrap();
+///
+/// assert_eq!(code.name, "Bacterial, Archaeal and Plant Plastid");
+/// assert_eq!(code.cde.len(), 64);
+/// ```
+pub fn get_genetic_code(
+    conn: &rusqlite::Connection,
+    id: i64,
+) -> anyhow::Result<GeneticCode> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT id, abbreviation, name, cde, starts
+        FROM gencode
+        WHERE id = ?1
+        ",
+    )?;
+    let mut rows = stmt.query([id])?;
+    let row = rows
+        .next()?
+        .ok_or_else(|| anyhow::anyhow!("No such genetic code: {id}"))?;
+
+    Ok(GeneticCode {
+        id: row.get(0)?,
+        abbreviation: row.get(1)?,
+        name: row.get(2)?,
+        cde: row.get(3)?,
+        starts: row.get(4)?,
+    })
+}
+
+/// A type material record from typematerial.dmp.
+#[derive(Debug, Clone, Default)]
+pub struct TypeMaterial {
+    /// Type material type, e.g. "type strain" or "holotype".
+    pub material_type: String,
+    /// Identifier in the type material collection.
+    pub identifier: String,
+}
+
+/// Ranks stored in rankedlineage.dmp, in column order.
+pub const RANKED_LINEAGE_RANKS: &[&str] = &[
+    "species",
+    "genus",
+    "family",
+    "order",
+    "class",
+    "phylum",
+    "kingdom",
+    "superkingdom",
+];
+
+/// Names of the standard ranks of a taxon, read from `rankedlineage`
+///
+/// Returns `(rank, name)` pairs from species up to superkingdom, skipping ranks
+/// the taxon doesn't have. The taxon itself is included when its own rank is
+/// one of [`RANKED_LINEAGE_RANKS`]. Returns `None` when the table is empty or
+/// doesn't contain the taxon.
+///
+/// ```
+/// let path = std::path::PathBuf::from("tests/nwr/");
+/// let conn = nwr::connect_txdb(&path).unwrap();
+///
+/// let ranked = nwr::get_ranked_lineage(&conn, 12340).unwrap().unwrap();
+///
+/// assert_eq!(ranked.first().unwrap(), &("species".to_string(), "Enterobacteria phage 933J".to_string()));
+/// assert_eq!(ranked.last().unwrap(), &("superkingdom".to_string(), "Viruses".to_string()));
+/// ```
+pub fn get_ranked_lineage(
+    conn: &rusqlite::Connection,
+    id: i64,
+) -> anyhow::Result<Option<Vec<(String, String)>>> {
+    if !table_has_rows(conn, "rankedlineage")? {
+        return Ok(None);
+    }
+
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT
+            node.rank,
+            r.tax_name,
+            r.species,
+            r.genus,
+            r.family,
+            r."order",
+            r.class,
+            r.phylum,
+            r.kingdom,
+            r.superkingdom
+        FROM rankedlineage r
+            INNER JOIN node ON r.tax_id = node.tax_id
+        WHERE r.tax_id = ?1
+        "#,
+    )?;
+    let mut rows = stmt.query([id])?;
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+
+    let own_rank: String = row.get(0)?;
+    let tax_name: String = row.get(1)?;
+    let mut ranked = Vec::new();
+    for (i, rank) in RANKED_LINEAGE_RANKS.iter().enumerate() {
+        // rankedlineage.dmp lists ancestors only; fill in the taxon itself.
+        let name: Option<String> = if own_rank == *rank {
+            Some(tax_name.clone())
+        } else {
+            row.get(i + 2)?
+        };
+        if let Some(name) = name {
+            ranked.push(((*rank).to_string(), name));
+        }
+    }
+
+    Ok(Some(ranked))
+}
+
+/// Type material of a taxon, read from `typematerial`
+///
+/// ```
+/// let path = std::path::PathBuf::from("tests/nwr/");
+/// let conn = nwr::connect_txdb(&path).unwrap();
+///
+/// let materials = nwr::get_type_material(&conn, 12340).unwrap();
+///
+/// assert_eq!(materials.get(0).unwrap().material_type, "type strain");
+/// assert!(nwr::get_type_material(&conn, 12347).unwrap().is_empty());
+/// ```
+pub fn get_type_material(
+    conn: &rusqlite::Connection,
+    id: i64,
+) -> anyhow::Result<Vec<TypeMaterial>> {
+    if !table_exists(conn, "typematerial")? {
+        return Ok(vec![]);
+    }
+
+    let mut stmt = conn.prepare(
+        "
+        SELECT type, identifier
+        FROM typematerial
+        WHERE tax_id = ?1
+        ORDER BY id
+        ",
+    )?;
+    let mut rows = stmt.query([id])?;
+    let mut materials = Vec::new();
+    while let Some(row) = rows.next()? {
+        materials.push(TypeMaterial {
+            material_type: row.get(0)?,
+            identifier: row.get(1)?,
+        });
+    }
+
+    Ok(materials)
+}
+
+/// Join type material records as `type:identifier` pairs separated by `;`
+///
+/// ```
+/// let materials = vec![nwr::TypeMaterial {
+///     material_type: "type strain".to_string(),
+///     identifier: "ATCC 11775".to_string(),
+/// }];
+///
+/// assert_eq!(nwr::format_type_material(&materials), "type strain:ATCC 11775");
+/// ```
+pub fn format_type_material(materials: &[TypeMaterial]) -> String {
+    materials
+        .iter()
+        .map(|m| format!("{}:{}", m.material_type, m.identifier))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Potential hosts of a taxon, read from `host`
+///
+/// ```
+/// let path = std::path::PathBuf::from("tests/nwr/");
+/// let conn = nwr::connect_txdb(&path).unwrap();
+///
+/// let hosts = nwr::get_hosts(&conn, 12340).unwrap();
+///
+/// assert_eq!(hosts, vec!["bacteria".to_string()]);
+/// ```
+pub fn get_hosts(conn: &rusqlite::Connection, id: i64) -> anyhow::Result<Vec<String>> {
+    if !table_exists(conn, "host")? {
+        return Ok(vec![]);
+    }
+
+    let mut stmt = conn.prepare(
+        "
+        SELECT hosts
+        FROM host
+        WHERE tax_id = ?1
+        ",
+    )?;
+    let mut rows = stmt.query([id])?;
+    let mut hosts = Vec::new();
+    if let Some(row) = rows.next()? {
+        let list: String = row.get(0)?;
+        hosts.extend(
+            list.split(',')
+                .map(str::trim)
+                .filter(|h| !h.is_empty())
+                .map(std::string::ToString::to_string),
+        );
+    }
+
+    Ok(hosts)
+}
+
+/// Lineage IDs of a taxon, read from the precomputed `lineage` table
+///
+/// Returns the IDs from the root down to `id` itself, or `None` when the table
+/// is empty or doesn't contain the taxon.
+fn get_path(conn: &rusqlite::Connection, id: i64) -> anyhow::Result<Option<Vec<i64>>> {
+    if !table_has_rows(conn, "lineage")? {
+        return Ok(None);
+    }
+
+    let mut stmt = conn.prepare(
+        "
+        SELECT lineage
+        FROM lineage
+        WHERE tax_id = ?1
+        ",
+    )?;
+    let mut rows = stmt.query([id])?;
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+    let lineage: String = row.get(0)?;
+
+    let ids = lineage
+        .split_whitespace()
+        .map(|field| {
+            field.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid lineage of tax_id {id} in lineage: {e}")
+            })
+        })
+        .collect::<anyhow::Result<Vec<i64>>>()?;
+
+    Ok(Some(ids))
+}
+
+/// Nested-set interval of a taxon, read from the precomputed `lineage` table
+fn get_interval(
+    conn: &rusqlite::Connection,
+    id: i64,
+) -> anyhow::Result<Option<(i64, i64)>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT lft, rgt
+        FROM lineage
+        WHERE tax_id = ?1
+        ",
+    )?;
+    let mut rows = stmt.query([id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some((row.get(0)?, row.get(1)?))),
+        None => Ok(None),
+    }
+}
 
-            if let Some(taxon) = taxa_map.get_mut(&tax_id) {
-                taxon.names.entry(name_class).or_default().push(name);
-            }
-        }
+/// Lineage IDs of a taxon, read from `taxidlineage`
+///
+/// Returns the IDs from the root down to `id` itself, or `None` when the table
+/// is empty or doesn't contain the taxon.
+fn get_taxid_lineage(
+    conn: &rusqlite::Connection,
+    id: i64,
+) -> anyhow::Result<Option<Vec<i64>>> {
+    if !table_has_rows(conn, "taxidlineage")? {
+        return Ok(None);
     }
 
-    // When the caller passes duplicate IDs, we must keep the map intact so
-    // every occurrence can be resolved. In the common (no-duplicates) case we
-    // drain the map via `remove` to avoid cloning each `Taxon` (which owns a
-    // `HashMap` of names) — a significant win for large clade queries.
-    let has_duplicates = ids.len() != unique_ids.len();
-    let mut taxa = Vec::with_capacity(ids.len());
-    for id in ids {
-        let taxon = if has_duplicates {
-            taxa_map
-                .get(id)
-                .ok_or_else(|| anyhow::anyhow!("No such ID: {id}"))?
-                .clone()
-        } else {
-            taxa_map
-                .remove(id)
-                .ok_or_else(|| anyhow::anyhow!("No such ID: {id}"))?
-        };
-        taxa.push(taxon);
+    let mut stmt = conn.prepare(
+        "
+        SELECT lineage
+        FROM taxidlineage
+        WHERE tax_id = ?1
+        ",
+    )?;
+    let mut rows = stmt.query([id])?;
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+    let lineage: String = row.get(0)?;
+
+    let mut ids: Vec<i64> = Vec::new();
+    for field in lineage.split_whitespace() {
+        let tax_id: i64 = field.parse().map_err(|e| {
+            anyhow::anyhow!("Invalid lineage of tax_id {id} in taxidlineage: {e}")
+        })?;
+        ids.push(tax_id);
+    }
+    // Depending on the dump, the root may or may not head the lineage.
+    if ids.first() != Some(&1) {
+        ids.insert(0, 1);
+    }
+    if id != 1 {
+        ids.push(id);
     }
 
-    Ok(taxa)
+    Ok(Some(ids))
 }
 
 /// Retrieve the ancestor
@@ -451,6 +1464,9 @@ const MAX_TAXONOMY_DEPTH: i64 = 1000;
 
 /// All Nodes to the root (with ID 1)
 ///
+/// Reads the precomputed `lineage` table built by `nwr txdb`, or
+/// `taxidlineage` when `new_taxdump` was imported, otherwise walks the tree.
+///
 /// ```
 /// let path = std::path::PathBuf::from("tests/nwr/");
 /// let conn = nwr::connect_txdb(&path).unwrap();
@@ -462,6 +1478,16 @@ const MAX_TAXONOMY_DEPTH: i64 = 1000;
 /// assert_eq!(lineage.len(), 4);
 /// ```
 pub fn get_lineage(conn: &rusqlite::Connection, id: i64) -> anyhow::Result<Vec<Taxon>> {
+    let id = resolve_tax_id(conn, id)?;
+
+    // Prefer the precomputed lineages when they were built or imported.
+    if let Some(ids) = get_path(conn, id)? {
+        return get_taxon(conn, &ids);
+    }
+    if let Some(ids) = get_taxid_lineage(conn, id)? {
+        return get_taxon(conn, &ids);
+    }
+
     // Walk to the root in a single recursive CTE instead of issuing one query
     // per lineage level. The CTE returns rows from the starting taxon up to
     // (and including) the canonical root.
@@ -576,6 +1602,24 @@ pub fn get_all_descendent(
     conn: &rusqlite::Connection,
     id: i64,
 ) -> anyhow::Result<Vec<i64>> {
+    // The subtree is one range of the nested-set intervals
+    if table_has_rows(conn, "lineage")? {
+        if let Some((lft, rgt)) = get_interval(conn, id)? {
+            let mut stmt = conn.prepare(
+                "
+                SELECT tax_id
+                FROM lineage
+                WHERE lft BETWEEN ?1 AND ?2
+                ORDER BY depth, lft
+                ",
+            )?;
+            let ids = stmt
+                .query_map([lft, rgt], |row| row.get(0))?
+                .collect::<Result<Vec<i64>, _>>()?;
+            return Ok(ids);
+        }
+    }
+
     // Fetch the entire subtree in a single recursive CTE instead of issuing
     // one query per node. The CTE starts with the requested taxon and follows
     // parent->child edges, ignoring self-loops (only the root is its own
@@ -614,9 +1658,42 @@ pub fn get_all_descendent(
     Ok(ids)
 }
 
+/// Whether `id` is `ancestor_id` or one of its descendants
+///
+/// Compares nested-set intervals when the `lineage` table is present,
+/// otherwise looks through the lineage of `id`.
+///
+/// ```
+/// let path = std::path::PathBuf::from("tests/nwr/");
+/// let conn = nwr::connect_txdb(&path).unwrap();
+///
+/// assert!(nwr::is_descendant(&conn, 12340, 10239).unwrap());
+/// assert!(nwr::is_descendant(&conn, 12340, 12340).unwrap());
+/// assert!(!nwr::is_descendant(&conn, 10239, 12340).unwrap());
+/// ```
+pub fn is_descendant(
+    conn: &rusqlite::Connection,
+    id: i64,
+    ancestor_id: i64,
+) -> anyhow::Result<bool> {
+    if table_has_rows(conn, "lineage")? {
+        if let (Some((lft, _)), Some((anc_lft, anc_rgt))) =
+            (get_interval(conn, id)?, get_interval(conn, ancestor_id)?)
+        {
+            return Ok(anc_lft <= lft && lft <= anc_rgt);
+        }
+    }
+
+    Ok(get_lineage(conn, id)?
+        .iter()
+        .any(|n| n.tax_id == ancestor_id))
+}
+
 /// Convert terms to Taxonomy IDs
 /// Accepted forms: ID; "scientific name"; `scientific_name`
 ///
+/// Merged IDs are resolved to their current IDs via [`resolve_tax_id`].
+///
 /// ```
 /// let path = std::path::PathBuf::from("tests/nwr/");
 /// let conn = nwr::connect_txdb(&path).unwrap();
@@ -634,10 +1711,10 @@ pub fn get_all_descendent(
 /// assert_eq!(id, 12392);
 /// ```
 pub fn term_to_tax_id(conn: &rusqlite::Connection, term: &str) -> anyhow::Result<i64> {
-    let term = term.trim().replace('_', " ");
+    let term = term.trim().to_string();
 
     let id: i64 = if let Ok(n) = term.parse::<i64>() {
-        n
+        resolve_tax_id(conn, n)?
     } else {
         match get_tax_id(conn, std::slice::from_ref(&term))?
             .into_iter()
@@ -653,8 +1730,8 @@ pub fn term_to_tax_id(conn: &rusqlite::Connection, term: &str) -> anyhow::Result
 
 /// Batch-convert a list of terms to Taxonomy IDs.
 ///
-/// Numeric strings are parsed directly; other strings are resolved against the
-/// `name` table in a single batched query. The returned vector preserves the
+/// Numeric strings are parsed and passed through [`resolve_tax_id`]; other
+/// strings are resolved against the `name` table in a single batched query. The returned vector preserves the
 /// input order.
 ///
 /// ```
@@ -673,9 +1750,9 @@ pub fn terms_to_tax_ids<S: AsRef<str>>(
     let mut name_terms: Vec<(usize, String)> = Vec::new();
     for (i, term) in terms.iter().enumerate() {
         let term = term.as_ref();
-        let normalized = term.trim().replace('_', " ");
+        let normalized = term.trim().to_string();
         if let Ok(n) = normalized.parse::<i64>() {
-            ids[i] = n;
+            ids[i] = resolve_tax_id(conn, n)?;
         } else {
             name_terms.push((i, normalized));
         }
@@ -683,8 +1760,8 @@ pub fn terms_to_tax_ids<S: AsRef<str>>(
 
     if !name_terms.is_empty() {
         let names: Vec<String> = name_terms.iter().map(|(_, n)| n.clone()).collect();
-        let resolved = get_tax_id(conn, &names)
-            .map_err(|e| anyhow::anyhow!("Failed to resolve one or more terms: {e}"))?;
+        let resolved =
+            get_tax_id(conn, &names).context("Failed to resolve one or more terms")?;
         for ((i, _), tax_id) in name_terms.iter().zip(resolved.iter()) {
             ids[*i] = *tax_id;
         }
@@ -693,6 +1770,57 @@ pub fn terms_to_tax_ids<S: AsRef<str>>(
     Ok(ids)
 }
 
+/// Lowest common ancestor of the given taxa
+///
+/// Compares the lineages of all `ids` from the root down and returns the
+/// deepest taxon shared by all of them. A single ID is its own LCA.
+///
+/// ```
+/// let path = std::path::PathBuf::from("tests/nwr/");
+/// let conn = nwr::connect_txdb(&path).unwrap();
+///
+/// let lca = nwr::get_lca(&conn, &[12340, 12347]).unwrap();
+/// assert_eq!(lca.tax_id, 12333);
+///
+/// let lca = nwr::get_lca(&conn, &[12340, 10239]).unwrap();
+/// assert_eq!(lca.tax_id, 10239);
+/// ```
+pub fn get_lca(conn: &rusqlite::Connection, ids: &[i64]) -> anyhow::Result<Taxon> {
+    let mut seen: HashSet<i64> = HashSet::new();
+    let mut lineages = Vec::with_capacity(ids.len());
+    for id in ids {
+        if seen.insert(*id) {
+            lineages.push(get_lineage(conn, *id)?);
+        }
+    }
+
+    let refs: Vec<&[Taxon]> = lineages.iter().map(Vec::as_slice).collect();
+    find_lca(&refs)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("No common ancestor found"))
+}
+
+/// Find the lowest common ancestor in lineages
+///
+/// Each lineage runs from the root down, as returned by `get_lineage`.
+/// Returns `None` for an empty slice or lineages sharing no node.
+#[must_use]
+pub fn find_lca<'a>(lineages: &[&'a [Taxon]]) -> Option<&'a Taxon> {
+    let (first, rest) = lineages.split_first()?;
+
+    let mut shared = first.len();
+    for lineage in rest {
+        shared = first
+            .iter()
+            .zip(lineage.iter())
+            .take(shared)
+            .take_while(|(a, b)| a.tax_id == b.tax_id)
+            .count();
+    }
+
+    shared.checked_sub(1).map(|i| &first[i])
+}
+
 /// Find rank in lineage
 ///
 /// Returns `(tax_id, scientific_name)` for the first node whose `rank` matches.
@@ -718,6 +1846,168 @@ pub fn find_rank<'a>(lineage: &'a [Taxon], rank: &str) -> (i64, &'a str) {
     (0, "NA")
 }
 
+/// Rank of a GTDB prefix letter, e.g. `p` in `p__Bacillota`
+#[must_use]
+pub fn gtdb_rank(prefix: &str) -> Option<&'static str> {
+    match prefix {
+        "d" => Some("domain"),
+        "p" => Some("phylum"),
+        "c" => Some("class"),
+        "o" => Some("order"),
+        "f" => Some("family"),
+        "g" => Some("genus"),
+        "s" => Some("species"),
+        _ => None,
+    }
+}
+
+/// Synthetic tax ID of a taxon outside NCBI
+///
+/// The 64-bit FNV-1a hash of `key`, kept to 47 bits. IDs depend only on the
+/// key, so they are stable across rebuilds, and never clash with the root (1).
+/// NCBI tax IDs are far below 2^47, so clashes with grafted NCBI trees are
+/// unlikely; importers still check for them.
+///
+/// ```
+/// let id = nwr::synthetic_tax_id("genus:Bacillus");
+/// assert_eq!(id, nwr::synthetic_tax_id("genus:Bacillus"));
+/// assert!(id > 1 && id < 1 << 47);
+/// ```
+#[must_use]
+pub fn synthetic_tax_id(key: &str) -> i64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in key.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    let id = i64::try_from(hash & 0x7fff_ffff_ffff).unwrap_or_default();
+    if id <= 1 {
+        id + 2
+    } else {
+        id
+    }
+}
+
+/// Synthetic tax ID of a GTDB taxon
+///
+/// Hashed from the prefixed name (e.g. `g__Bacillus`) by [`synthetic_tax_id`],
+/// so IDs are stable across GTDB releases.
+///
+/// ```
+/// let id = nwr::gtdb_tax_id("g__Bacillus");
+/// assert_eq!(id, nwr::gtdb_tax_id("g__Bacillus"));
+/// assert_ne!(id, nwr::gtdb_tax_id("f__Bacillus"));
+/// ```
+#[must_use]
+pub fn gtdb_tax_id(prefixed_name: &str) -> i64 {
+    synthetic_tax_id(prefixed_name)
+}
+
+/// Strip the `RS_`/`GB_` source prefix of GTDB genome accessions
+///
+/// ```
+/// assert_eq!(nwr::gtdb_accession("RS_GCF_000005845.2"), "GCF_000005845.2");
+/// assert_eq!(nwr::gtdb_accession("GCA_000006155.2"), "GCA_000006155.2");
+/// ```
+#[must_use]
+pub fn gtdb_accession(accession: &str) -> &str {
+    accession
+        .strip_prefix("RS_")
+        .or_else(|| accession.strip_prefix("GB_"))
+        .unwrap_or(accession)
+}
+
+/// Placeholders in lineage templates, e.g. `{genus}`
+static RE_PLACEHOLDER: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\{([^{}]+)\}").unwrap());
+
+/// Default template of `format_lineage`
+pub const DEFAULT_LINEAGE_FORMAT: &str =
+    "{domain};{phylum};{class};{order};{family};{genus};{species}";
+
+/// QIIME/GTDB-style prefix of a rank, e.g. `p__` for phylum
+///
+/// `domain` and `superkingdom` both map to `d__`; other ranks use their
+/// first letter.
+#[must_use]
+pub fn rank_prefix(rank: &str) -> String {
+    match rank {
+        "domain" | "superkingdom" => "d__".to_string(),
+        _ => rank
+            .chars()
+            .next()
+            .map_or_else(String::new, |c| format!("{c}__")),
+    }
+}
+
+/// Format a lineage into a single string
+///
+/// Every `{rank}` placeholder in `template` is replaced by the scientific name
+/// of that rank in `lineage`. `{domain}` also matches `superkingdom`, and
+/// vice versa.
+///
+/// A missing rank is replaced by `fill`, in which `{rank}` stands for the
+/// missing rank and `{parent}` for the nearest higher rank found so far.
+/// Without `fill`, missing ranks are left empty. With `is_prefix`, values get
+/// prefixes like `p__`.
+///
+/// ```
+/// let path = std::path::PathBuf::from("tests/nwr/");
+/// let conn = nwr::connect_txdb(&path).unwrap();
+/// let lineage = nwr::get_lineage(&conn, 12340).unwrap();
+///
+/// let s = nwr::format_lineage(&lineage, "{superkingdom};{genus};{species}", None, false);
+/// assert_eq!(s, "Viruses;;Enterobacteria phage 933J");
+///
+/// let s = nwr::format_lineage(
+///     &lineage,
+///     "{domain};{genus};{species}",
+///     Some("Unclassified {parent} {rank}"),
+///     true,
+/// );
+/// assert_eq!(
+///     s,
+///     "d__Viruses;g__Unclassified Viruses genus;s__Enterobacteria phage 933J"
+/// );
+/// ```
+#[must_use]
+pub fn format_lineage(
+    lineage: &[Taxon],
+    template: &str,
+    fill: Option<&str>,
+    is_prefix: bool,
+) -> String {
+    let mut parent = String::new();
+
+    RE_PLACEHOLDER
+        .replace_all(template, |caps: &regex::Captures| {
+            let rank = &caps[1];
+            let found = lineage.iter().find(|node| {
+                node.rank == rank
+                    || matches!(
+                        (rank, node.rank.as_str()),
+                        ("domain", "superkingdom") | ("superkingdom", "domain")
+                    )
+            });
+
+            let value = if let Some(name) = found.and_then(Taxon::scientific_name) {
+                parent = name.to_string();
+                name.to_string()
+            } else {
+                fill.map_or_else(String::new, |f| {
+                    f.replace("{rank}", rank).replace("{parent}", &parent)
+                })
+            };
+
+            if is_prefix {
+                format!("{}{value}", rank_prefix(rank))
+            } else {
+                value
+            }
+        })
+        .into_owned()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -765,6 +2055,60 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("No such ID"));
     }
 
+    #[test]
+    fn test_get_taxon_deleted() {
+        let path = std::path::PathBuf::from("tests/nwr/");
+        let conn = connect_txdb(&path).unwrap();
+        let result = get_taxon(&conn, &[12341]);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("has been deleted"));
+    }
+
+    #[test]
+    fn test_get_taxon_merged_and_current() {
+        let path = std::path::PathBuf::from("tests/nwr/");
+        let conn = connect_txdb(&path).unwrap();
+
+        // 12339 has been merged into 12340
+        let taxa = get_taxon(&conn, &[12339, 12340, 12347]).unwrap();
+        assert_eq!(taxa.len(), 3);
+        assert_eq!(taxa[0].tax_id, 12340);
+        assert_eq!(taxa[1].tax_id, 12340);
+        assert_eq!(taxa[2].tax_id, 12347);
+    }
+
+    #[test]
+    fn test_term_to_tax_id_merged() {
+        let path = std::path::PathBuf::from("tests/nwr/");
+        let conn = connect_txdb(&path).unwrap();
+
+        let id = term_to_tax_id(&conn, "12345").unwrap();
+        assert_eq!(id, 12347);
+
+        let lineage = get_lineage(&conn, 12345).unwrap();
+        assert_eq!(lineage.last().unwrap().tax_id, 12347);
+    }
+
+    #[test]
+    fn test_resolve_tax_id_without_merged_table() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE node (
+                tax_id        INTEGER PRIMARY KEY,
+                parent_tax_id INTEGER,
+                rank          VARCHAR NOT NULL,
+                division_id   INTEGER NOT NULL,
+                comment       TEXT
+            );
+            ",
+        )
+        .unwrap();
+
+        // Databases built before merged.dmp support pass unknown IDs through.
+        assert_eq!(resolve_tax_id(&conn, 12339).unwrap(), 12339);
+    }
+
     #[test]
     fn test_get_taxon_duplicate_ids() {
         let path = std::path::PathBuf::from("tests/nwr/");
@@ -871,6 +2215,35 @@ mod tests {
         assert!(display.contains("This is a test comment"));
     }
 
+    #[test]
+    fn test_taxon_display_with_genetic_code() {
+        let taxon = Taxon {
+            tax_id: 12340,
+            rank: "species".to_string(),
+            division: "Phages".to_string(),
+            gc_id: 11,
+            gc_name: Some("Bacterial, Archaeal and Plant Plastid".to_string()),
+            ..Default::default()
+        };
+        let display = format!("{}", taxon);
+        assert!(
+            display.contains("Genetic code: 11 (Bacterial, Archaeal and Plant Plastid)")
+        );
+        assert!(!display.contains("Mitochondrial genetic code"));
+    }
+
+    #[test]
+    fn test_get_genetic_code_not_found() {
+        let path = std::path::PathBuf::from("tests/nwr/");
+        let conn = connect_txdb(&path).unwrap();
+        let result = get_genetic_code(&conn, 999);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("No such genetic code"));
+    }
+
     #[test]
     fn test_taxon_display_without_scientific_name() {
         let taxon = Taxon {
@@ -944,6 +2317,70 @@ mod tests {
         assert!(descendents.len() > 1); // Should have children
     }
 
+    #[test]
+    fn test_forget_table_rows() {
+        let tempdir = tempfile::TempDir::new().unwrap();
+        let conn =
+            rusqlite::Connection::open(tempdir.path().join("test.sqlite")).unwrap();
+        conn.execute_batch("CREATE TABLE lineage (tax_id INTEGER);")
+            .unwrap();
+        assert!(!table_has_rows(&conn, "lineage").unwrap());
+
+        // Filled in place, as by `nwr migrate`
+        conn.execute_batch("INSERT INTO lineage VALUES (1);")
+            .unwrap();
+        forget_table_rows();
+        assert!(table_has_rows(&conn, "lineage").unwrap());
+    }
+
+    #[test]
+    fn test_lineage_table_matches_tree() {
+        let path = std::path::PathBuf::from("tests/nwr/");
+        let conn = connect_txdb(&path).unwrap();
+        assert!(table_has_rows(&conn, "lineage").unwrap());
+
+        // The same tree without the precomputed lineages
+        let tree = rusqlite::Connection::open_in_memory().unwrap();
+        tree.execute_batch(
+            "
+            ATTACH DATABASE 'tests/nwr/taxonomy.sqlite' AS f;
+            CREATE TABLE node AS SELECT * FROM f.node;
+            CREATE TABLE name AS SELECT * FROM f.name;
+            CREATE TABLE division AS SELECT * FROM f.division;
+            CREATE TABLE gencode AS SELECT * FROM f.gencode;
+            DETACH DATABASE f;
+            ",
+        )
+        .unwrap();
+
+        for id in [1, 10239, 12333, 375032] {
+            let mut expected = get_all_descendent(&tree, id).unwrap();
+            let mut got = get_all_descendent(&conn, id).unwrap();
+            expected.sort_unstable();
+            got.sort_unstable();
+            assert_eq!(got, expected);
+        }
+
+        for id in [1, 12340, 375033] {
+            let expected: Vec<i64> = get_lineage(&tree, id)
+                .unwrap()
+                .iter()
+                .map(|n| n.tax_id)
+                .collect();
+            let got: Vec<i64> = get_lineage(&conn, id)
+                .unwrap()
+                .iter()
+                .map(|n| n.tax_id)
+                .collect();
+            assert_eq!(got, expected);
+        }
+
+        assert!(is_descendant(&conn, 375033, 375032).unwrap());
+        assert!(is_descendant(&tree, 375033, 375032).unwrap());
+        assert!(!is_descendant(&conn, 375032, 375033).unwrap());
+        assert!(!is_descendant(&tree, 375032, 375033).unwrap());
+    }
+
     #[test]
     fn test_get_all_descendent_cycle() {
         let conn = rusqlite::Connection::open_in_memory().unwrap();
@@ -1013,6 +2450,84 @@ mod tests {
         assert!(path.to_string_lossy().contains(".nwr"));
     }
 
+    #[test]
+    fn test_get_tax_id_homonyms() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE name (
+                id         INTEGER PRIMARY KEY,
+                tax_id     INTEGER NOT NULL,
+                name       VARCHAR NOT NULL,
+                name_class VARCHAR NOT NULL
+            );
+            INSERT INTO name (tax_id, name, name_class) VALUES
+                (10, 'Alpha', 'scientific name'),
+                (20, 'Alpha', 'synonym'),
+                (30, 'Beta', 'scientific name'),
+                (40, 'Beta', 'scientific name');
+            ",
+        )
+        .unwrap();
+
+        // The only scientific name wins over synonyms
+        let ids = get_tax_id(&conn, &["Alpha".to_string()]).unwrap();
+        assert_eq!(ids, vec![10]);
+
+        let err = get_tax_id(&conn, &["Beta".to_string()]).unwrap_err();
+        let err = err.downcast_ref::<AmbiguousNameError>().unwrap();
+        assert_eq!(err.name, "Beta");
+        let ids: Vec<i64> = err.candidates.iter().map(|c| c.0).collect();
+        assert_eq!(ids, vec![30, 40]);
+    }
+
+    #[test]
+    fn test_split_ancestor() {
+        assert_eq!(split_ancestor("Bacillus"), ("Bacillus", None));
+        assert_eq!(
+            split_ancestor("Bacillus @ Bacteria"),
+            ("Bacillus", Some("Bacteria"))
+        );
+        assert_eq!(
+            split_ancestor("Bacillus@Bacilli@Bacteria"),
+            ("Bacillus", Some("Bacilli@Bacteria"))
+        );
+        assert_eq!(split_ancestor("Bacillus@"), ("Bacillus@", None));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("Homo sapiens", "homo sapiens"), 0);
+        assert_eq!(edit_distance("Homo sapiens", "Homo sapeins"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_name_class_order() {
+        assert!(name_class_order("scientific name") < name_class_order("synonym"));
+        assert!(name_class_order("genbank synonym") < name_class_order("common name"));
+        assert!(name_class_order("common name") < name_class_order("includes"));
+    }
+
+    #[test]
+    fn test_find_lca() {
+        let taxon = |tax_id: i64| Taxon {
+            tax_id,
+            ..Default::default()
+        };
+        let a = vec![taxon(1), taxon(2), taxon(3)];
+        let b = vec![taxon(1), taxon(2), taxon(4)];
+        let c = vec![taxon(5)];
+
+        assert_eq!(find_lca(&[&a, &b]).unwrap().tax_id, 2);
+        assert_eq!(find_lca(&[&a]).unwrap().tax_id, 3);
+        assert_eq!(find_lca(&[&a, &a[..1]]).unwrap().tax_id, 1);
+        assert!(find_lca(&[&a, &c]).is_none());
+        assert!(find_lca(&[]).is_none());
+    }
+
     #[test]
     fn test_get_lineage_self_loop_cycle() {
         let conn = rusqlite::Connection::open_in_memory().unwrap();
@@ -1075,10 +2590,23 @@ mod tests {
     #[test]
     fn fallback_no_match() {
         // Names without sp/nom qualifiers produce no fallback candidates
-        let c = fallback_candidates("not_a_real_taxon_name");
+        let c = fallback_candidates("not a real taxon name");
         assert!(c.is_empty());
     }
 
+    #[test]
+    fn fallback_underscores() {
+        // Underscores for spaces come first, then the other variants
+        let c = fallback_candidates("Cladobotryum_sp");
+        assert_eq!(
+            c,
+            vec![
+                "Cladobotryum sp".to_string(),
+                "Cladobotryum sp.".to_string()
+            ]
+        );
+    }
+
     #[test]
     fn fallback_sp_in_middle_not_matched() {
         // "sp" in the middle should not trigger fallback
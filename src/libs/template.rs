@@ -12,6 +12,18 @@ pub const LEVEL_CONTIG: &str = "3"; // Same as SCAFFOLD - both are treated as le
 /// Assembly level code for other incomplete assemblies.
 pub const LEVEL_OTHER: &str = "5";
 
+/// The level code of an `assembly_level`; smaller codes are more contiguous.
+#[must_use]
+pub fn level_code(assembly_level: &str) -> &'static str {
+    match assembly_level {
+        "Complete Genome" => LEVEL_COMPLETE_GENOME,
+        "Chromosome" => LEVEL_CHROMOSOME,
+        "Scaffold" => LEVEL_SCAFFOLD,
+        "Contig" => LEVEL_CONTIG,
+        _ => LEVEL_OTHER,
+    }
+}
+
 /// Validate that a string is safe to embed into generated shell scripts and
 /// to use as a file or directory name.
 ///
@@ -16,15 +16,27 @@ fn main() -> anyhow::Result<()> {
         .subcommand(cmd_nwr::download::make_subcommand())
         .subcommand(cmd_nwr::txdb::make_subcommand())
         .subcommand(cmd_nwr::ardb::make_subcommand())
+        .subcommand(cmd_nwr::custom::make_subcommand())
+        .subcommand(cmd_nwr::diff::make_subcommand())
+        .subcommand(cmd_nwr::status::make_subcommand())
+        .subcommand(cmd_nwr::migrate::make_subcommand())
+        .subcommand(cmd_nwr::doctor::make_subcommand())
+        .subcommand(cmd_nwr::bundle::make_subcommand())
+        .subcommand(cmd_nwr::install::make_subcommand())
         // Taxonomy
         .subcommand(cmd_nwr::info::make_subcommand())
+        .subcommand(cmd_nwr::search::make_subcommand())
         .subcommand(cmd_nwr::lineage::make_subcommand())
         .subcommand(cmd_nwr::member::make_subcommand())
         .subcommand(cmd_nwr::append::make_subcommand())
+        .subcommand(cmd_nwr::reformat::make_subcommand())
         .subcommand(cmd_nwr::restrict::make_subcommand())
         .subcommand(cmd_nwr::common::make_subcommand())
+        .subcommand(cmd_nwr::lca::make_subcommand())
         // Assembly
+        .subcommand(cmd_nwr::assembly::make_subcommand())
         .subcommand(cmd_nwr::template::make_subcommand())
+        .subcommand(cmd_nwr::fetch::make_subcommand())
         .subcommand(cmd_nwr::abbr::make_subcommand())
         .subcommand(cmd_nwr::kb::make_subcommand())
         .subcommand(cmd_nwr::seqdb::make_subcommand())
@@ -32,11 +44,11 @@ fn main() -> anyhow::Result<()> {
             r"Subcommand groups:
 
 * Database
-    * download / txdb / ardb
+    * download / txdb / ardb / custom / diff / status / migrate / doctor / bundle / install
 * Taxonomy
-    * info / lineage / member / append / restrict / common
+    * info / search / lineage / member / append / reformat / restrict / common / lca
 * Assembly
-    * template / abbr / kb / seqdb
+    * assembly / template / fetch / abbr / kb / seqdb
 ",
         );
 
@@ -45,13 +57,25 @@ fn main() -> anyhow::Result<()> {
         Some(("download", sub_matches)) => cmd_nwr::download::execute(sub_matches),
         Some(("txdb", sub_matches)) => cmd_nwr::txdb::execute(sub_matches),
         Some(("ardb", sub_matches)) => cmd_nwr::ardb::execute(sub_matches),
+        Some(("custom", sub_matches)) => cmd_nwr::custom::execute(sub_matches),
+        Some(("diff", sub_matches)) => cmd_nwr::diff::execute(sub_matches),
+        Some(("status", sub_matches)) => cmd_nwr::status::execute(sub_matches),
+        Some(("migrate", sub_matches)) => cmd_nwr::migrate::execute(sub_matches),
+        Some(("doctor", sub_matches)) => cmd_nwr::doctor::execute(sub_matches),
+        Some(("bundle", sub_matches)) => cmd_nwr::bundle::execute(sub_matches),
+        Some(("install", sub_matches)) => cmd_nwr::install::execute(sub_matches),
         Some(("info", sub_matches)) => cmd_nwr::info::execute(sub_matches),
+        Some(("search", sub_matches)) => cmd_nwr::search::execute(sub_matches),
         Some(("lineage", sub_matches)) => cmd_nwr::lineage::execute(sub_matches),
         Some(("member", sub_matches)) => cmd_nwr::member::execute(sub_matches),
         Some(("append", sub_matches)) => cmd_nwr::append::execute(sub_matches),
+        Some(("reformat", sub_matches)) => cmd_nwr::reformat::execute(sub_matches),
         Some(("restrict", sub_matches)) => cmd_nwr::restrict::execute(sub_matches),
         Some(("common", sub_matches)) => cmd_nwr::common::execute(sub_matches),
+        Some(("lca", sub_matches)) => cmd_nwr::lca::execute(sub_matches),
+        Some(("assembly", sub_matches)) => cmd_nwr::assembly::execute(sub_matches),
         Some(("template", sub_matches)) => cmd_nwr::template::execute(sub_matches),
+        Some(("fetch", sub_matches)) => cmd_nwr::fetch::execute(sub_matches),
         Some(("abbr", sub_matches)) => cmd_nwr::abbr::execute(sub_matches),
         Some(("kb", sub_matches)) => cmd_nwr::kb::execute(sub_matches),
         Some(("seqdb", sub_matches)) => cmd_nwr::seqdb::execute(sub_matches),
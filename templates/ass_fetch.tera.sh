@@ -0,0 +1,69 @@
+{%- include "header" -%}
+{# Keep a blank line #}
+#----------------------------#
+# Usage
+#----------------------------#
+USAGE="
+Usage: $0 [STR_IN_FLD] ...
+
+Default values:
+    STR_IN_FLD  ''
+
+$ bash fetch.sh Klebsiella Stutzerimonas
+
+"
+
+if [ "$1" == "-h" ] || [ "$1" == "--help" ]; then
+    echo $USAGE
+    exit 0
+fi
+
+#----------------------------#
+# Run
+#----------------------------#
+log_warn fetch.sh
+
+touch check.lst
+
+# Assemblies not checked yet, of the given species
+# C_myc_ATHUM6906_GCA_036429605_1	https://ftp.ncbi.nlm.nih.gov/genomes/all/GCA/036/429/605/GCA_036429605.1_ASM3642960v1	Cladobotryum_mycophilum
+cat url.tsv |
+    tva join -f check.lst -k 1 -e |
+    if [ "$#" -gt 0 ]; then
+        result="tva filter --or"
+        for arg in "$@"; do
+            result+=" --str-in-fld '3:$arg'"
+        done
+        result=${result% }
+        eval "$result"
+    else
+        tva uniq
+    fi \
+    > fetch.tsv
+
+if [ ! -s fetch.tsv ]; then
+    log_info "Nothing to fetch"
+    rm fetch.tsv
+    exit 0
+fi
+
+# Downloads md5checksums.txt and the files, and checks their MD5 sums;
+# failed assemblies go to retry.tsv
+nwr fetch fetch.tsv --outdir . -p {{ parallel }} -o fetch.out
+STATUS=$?
+
+# Assemblies whose files all passed
+cut -f 1 fetch.out |
+    tva uniq \
+    >> check.lst
+sort -u check.lst -o check.lst
+rm fetch.tsv fetch.out
+
+if [ "$STATUS" -ne 0 ]; then
+    log_warn "$(wc -l < retry.tsv) assemblies failed, listed in retry.tsv; run fetch.sh again to retry them"
+    exit 1
+fi
+
+log_info Done.
+
+exit 0
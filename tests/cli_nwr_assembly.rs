@@ -1,9 +1,13 @@
 use assert_cmd::prelude::*;
 use predicates::prelude::*;
+use std::collections::HashMap;
 use std::io::Write;
 use std::process::Command;
 use tempfile::TempDir;
 
+mod common;
+use common::{Failure, Route};
+
 #[test]
 fn command_template_ass() -> anyhow::Result<()> {
     let mut cmd = Command::cargo_bin("nwr")?;
@@ -18,11 +22,14 @@ fn command_template_ass() -> anyhow::Result<()> {
     let stdout = String::from_utf8(output.stdout).unwrap();
     let stderr = String::from_utf8(output.stderr).unwrap();
 
-    assert_eq!(stderr.lines().count(), 8);
+    assert_eq!(stderr.lines().count(), 7);
     assert!(stderr.contains("Create ASSEMBLY/url.tsv"));
+    assert!(stderr.contains("Create ASSEMBLY/fetch.sh"));
 
     assert!(stdout.lines().count() > 100);
     assert!(stdout.contains("T_atrov"));
+    assert!(stdout.contains("nwr fetch fetch.tsv"));
+    assert!(!stdout.contains("aria2c"));
 
     Ok(())
 }
@@ -302,3 +309,535 @@ fn command_kb() -> anyhow::Result<()> {
     // TempDir is automatically cleaned up when it goes out of scope
     Ok(())
 }
+
+/// Routes of an assembly directory with its files and `md5checksums.txt`.
+fn assembly_routes(
+    dir: &str,
+    files: &[(&str, &[u8])],
+    md5s: &[(&str, &[u8])],
+) -> Vec<(String, Route)> {
+    let checksums: String = md5s
+        .iter()
+        .map(|(name, body)| format!("{:x}  ./{name}\n", md5::compute(body)))
+        .collect();
+    let mut routes = vec![(
+        format!("{dir}/md5checksums.txt"),
+        Route::new(checksums.as_bytes()),
+    )];
+    for (name, body) in files {
+        routes.push((format!("{dir}/{name}"), Route::new(body)));
+    }
+    routes
+}
+
+#[test]
+fn command_fetch() -> anyhow::Result<()> {
+    let genome = b">chr1\nACGTACGTACGTACGTACGTACGTACGTACGTACGTACGT\n".repeat(50);
+    let report = b"# Assembly name:  ASM1v1\n".to_vec();
+    let bad = b">chr1\nNNNN\n".to_vec();
+
+    let a = "/genomes/all/GCF_000000001.1_ASM1v1";
+    let b = "/genomes/all/GCF_000000002.1_ASM2v1";
+    let mut routes: HashMap<String, Route> = HashMap::new();
+    routes.extend(assembly_routes(
+        a,
+        &[
+            ("GCF_000000001.1_ASM1v1_genomic.fna.gz", &genome),
+            ("GCF_000000001.1_ASM1v1_assembly_report.txt", &report),
+        ],
+        &[
+            ("GCF_000000001.1_ASM1v1_genomic.fna.gz", &genome),
+            ("GCF_000000001.1_ASM1v1_assembly_report.txt", &report),
+        ],
+    ));
+    routes.extend(assembly_routes(
+        b,
+        &[("GCF_000000002.1_ASM2v1_genomic.fna.gz", &bad)],
+        &[("GCF_000000002.1_ASM2v1_genomic.fna.gz", &genome)],
+    ));
+    // The genome of A breaks off halfway once
+    let route = routes
+        .remove(&format!("{a}/GCF_000000001.1_ASM1v1_genomic.fna.gz"))
+        .unwrap();
+    routes.insert(
+        format!("{a}/GCF_000000001.1_ASM1v1_genomic.fna.gz"),
+        route.failing(Failure::Truncate(genome.len() / 2)),
+    );
+    let server = common::serve(routes);
+
+    let tempdir = TempDir::new()?;
+    let url_tsv = tempdir.path().join("url.tsv");
+    std::fs::write(
+        &url_tsv,
+        format!(
+            "Test_one_A\t{url}{a}\tTest_one\nTest_one_B\t{url}{b}\tTest_one\n",
+            url = server.url
+        ),
+    )?;
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("fetch")
+        .arg(&url_tsv)
+        .arg("-t")
+        .arg("fna")
+        .arg("-t")
+        .arg("faa")
+        .arg("-t")
+        .arg("report")
+        .arg("--retries")
+        .arg("1")
+        .arg("--outdir")
+        .arg(tempdir.path())
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+    let stderr = String::from_utf8(output.stderr)?;
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("1 of 2 assemblies failed"));
+    assert!(stderr.contains("MD5 check of GCF_000000002.1_ASM2v1_genomic.fna.gz failed"));
+    // A has no proteins
+    assert!(stderr.contains("no GCF_000000001.1_ASM1v1_protein.faa.gz"));
+    assert_eq!(stdout.lines().count(), 2);
+
+    let dir_a = tempdir.path().join("Test_one/Test_one_A");
+    assert_eq!(
+        std::fs::read(dir_a.join("GCF_000000001.1_ASM1v1_genomic.fna.gz"))?,
+        genome
+    );
+    assert!(!tempdir
+        .path()
+        .join("Test_one/Test_one_B/GCF_000000002.1_ASM2v1_genomic.fna.gz")
+        .exists());
+
+    let retry = std::fs::read_to_string(tempdir.path().join("retry.tsv"))?;
+    assert_eq!(retry.lines().count(), 1);
+    assert!(retry.starts_with("Test_one_B\t"));
+
+    // Files already there aren't downloaded again
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("fetch")
+        .arg(&url_tsv)
+        .arg("-t")
+        .arg("fna")
+        .arg("--retries")
+        .arg("0")
+        .arg("--outdir")
+        .arg(tempdir.path())
+        .assert()
+        .failure();
+    let requests = server.requests.lock().unwrap().clone();
+    let tries = requests
+        .iter()
+        .filter(|r| r.starts_with(&format!("{a}/GCF_000000001.1_ASM1v1_genomic.fna.gz")))
+        .count();
+    assert_eq!(tries, 2);
+
+    Ok(())
+}
+
+#[test]
+fn command_assembly() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    let dir = tempdir.path();
+    std::fs::write(
+        dir.join("lineages.tsv"),
+        "#family\tgenus\tspecies
+Enterobacteriaceae\tEscherichia\tEscherichia coli
+Enterobacteriaceae\tEscherichia\tEscherichia fergusonii
+Enterobacteriaceae\tSalmonella\tSalmonella enterica
+Bacillaceae\tBacillus\tBacillus subtilis
+",
+    )?;
+    Command::cargo_bin("nwr")?
+        .arg("custom")
+        .arg(dir.join("lineages.tsv"))
+        .arg("--outdir")
+        .arg(dir)
+        .assert()
+        .success();
+
+    let conn = rusqlite::Connection::open(dir.join("taxonomy.sqlite"))?;
+    let tax_id = |name: &str| -> rusqlite::Result<i64> {
+        conn.query_row(
+            "SELECT tax_id FROM name WHERE name = ?1 AND name_class = 'scientific name'",
+            [name],
+            |row| row.get(0),
+        )
+    };
+
+    // accession, species, infraspecific_name, category, level, date
+    let assemblies = [
+        (
+            "GCF_000005845.2",
+            "Escherichia coli",
+            "strain=K-12 substr. MG1655",
+            "reference genome",
+            "Complete Genome",
+            "2013/09/26",
+        ),
+        (
+            "GCF_000008865.2",
+            "Escherichia coli",
+            "strain=Sakai",
+            "na",
+            "Complete Genome",
+            "2018/06/08",
+        ),
+        (
+            "GCF_000026225.1",
+            "Escherichia fergusonii",
+            "strain=ATCC 35469",
+            "na",
+            "Contig",
+            "2009/01/06",
+        ),
+        (
+            "GCF_000006945.2",
+            "Salmonella enterica",
+            "strain=LT2",
+            "reference genome",
+            "Complete Genome",
+            "2016/01/13",
+        ),
+        (
+            "GCF_000009045.1",
+            "Bacillus subtilis",
+            "strain=168",
+            "reference genome",
+            "Complete Genome",
+            "2009/03/18",
+        ),
+    ];
+    let mut summary = String::from(
+        "#   See ftp://ftp.ncbi.nlm.nih.gov/genomes/README_assembly_summary.txt\n",
+    );
+    // A newer header, with the quality columns and ftp_path moved last
+    let header = [
+        "assembly_accession",
+        "bioproject",
+        "biosample",
+        "refseq_category",
+        "taxid",
+        "species_taxid",
+        "organism_name",
+        "infraspecific_name",
+        "assembly_level",
+        "genome_rep",
+        "seq_rel_date",
+        "asm_name",
+        "gbrs_paired_asm",
+        "relation_to_type_material",
+        "group",
+        "gc_percent",
+        "contig_count",
+        "contig_n50",
+        "ftp_path",
+    ];
+    summary.push_str(&format!("#{}\n", header.join("\t")));
+    for (i, (accession, species, strain, category, level, date)) in
+        assemblies.into_iter().enumerate()
+    {
+        let id = tax_id(species)?.to_string();
+        let organism_name =
+            format!("{species} {}", strain.trim_start_matches("strain="));
+        let biosample = format!("SAMN{}", &accession[4..13]);
+        let contig_n50 = if level == "Contig" {
+            "20000".to_string()
+        } else {
+            (4_600_000 + i).to_string()
+        };
+        let type_material = if strain.ends_with("ATCC 35469") || strain.ends_with("LT2")
+        {
+            "assembly from type material"
+        } else {
+            "na"
+        };
+        let ftp_path =
+            format!("https://ftp.ncbi.nlm.nih.gov/genomes/all/{accession}_ASM1v1");
+        let fields: Vec<&str> = header
+            .iter()
+            .map(|name| match *name {
+                "assembly_accession" => accession,
+                "biosample" => &biosample,
+                "refseq_category" => category,
+                "taxid" | "species_taxid" => &id,
+                "organism_name" => &organism_name,
+                "infraspecific_name" => strain,
+                "assembly_level" => level,
+                "genome_rep" => "Full",
+                "seq_rel_date" => date,
+                "asm_name" => "ASM1v1",
+                "gbrs_paired_asm" if accession == "GCF_000005845.2" => "GCA_000005845.2",
+                "relation_to_type_material" => type_material,
+                "group" => "bacteria",
+                "gc_percent" => "50.5",
+                "contig_count" => "na",
+                "contig_n50" => &contig_n50,
+                "ftp_path" => &ftp_path,
+                _ => "na",
+            })
+            .collect();
+        summary.push_str(&format!("{}\n", fields.join("\t")));
+    }
+    std::fs::write(dir.join("assembly_summary_refseq.txt"), summary)?;
+    Command::cargo_bin("nwr")?
+        .arg("ardb")
+        .arg("--dir")
+        .arg(dir)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("assembly")
+        .arg("Escherichia")
+        .arg("--level")
+        .arg("Complete Genome")
+        .arg("--dir")
+        .arg(dir)
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+
+    assert!(output.status.success());
+    assert_eq!(stdout.lines().count(), 3);
+    assert!(stdout.starts_with("#name\tftp_path\tbiosample\tspecies\tassembly_level\n"));
+    assert!(stdout.contains("E_coli_K_12_MG1655_GCF_000005845_2\thttps://"));
+    assert!(stdout.contains("E_coli_Sakai_GCF_000008865_2\t"));
+    assert!(stdout.contains("\tSAMN000005845\tEscherichia coli\tComplete Genome\n"));
+
+    // Categories and dates, under several taxa
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("assembly")
+        .arg("Enterobacteriaceae")
+        .arg("Bacillus")
+        .arg("--category")
+        .arg("reference genome")
+        .arg("--from")
+        .arg("2010/01/01")
+        .arg("--dir")
+        .arg(dir)
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+
+    assert!(output.status.success());
+    assert_eq!(stdout.lines().count(), 3);
+    assert!(stdout.contains("GCF_000005845_2"));
+    assert!(stdout.contains("S_ent_LT2_GCF_000006945_2"));
+
+    // Filters and columns on the other summary columns
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("assembly")
+        .arg("Enterobacteriaceae")
+        .arg("--filter")
+        .arg("contig_n50>=50000")
+        .arg("--filter")
+        .arg("relation_to_type_material = assembly from type material")
+        .arg("--column")
+        .arg("contig_n50")
+        .arg("--column")
+        .arg("group")
+        .arg("--column")
+        .arg("contig_count")
+        .arg("--dir")
+        .arg(dir)
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+
+    assert!(output.status.success());
+    assert_eq!(stdout.lines().count(), 2);
+    assert!(stdout.starts_with(
+        "#name\tftp_path\tbiosample\tspecies\tassembly_level\tcontig_n50\tgroup\tcontig_count\n"
+    ));
+    assert!(stdout.contains("S_ent_LT2_GCF_000006945_2\t"));
+    assert!(stdout.contains("\tComplete Genome\t4600003\tbacteria\tna\n"));
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("assembly")
+        .arg("Bacillus")
+        .arg("--filter")
+        .arg("checkm_completeness>=90")
+        .arg("--dir")
+        .arg(dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "has no column checkm_completeness",
+        ));
+
+    // The best assembly of each genus, by the default keys
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("assembly")
+        .arg("Enterobacteriaceae")
+        .arg("--per")
+        .arg("genus")
+        .arg("--dir")
+        .arg(dir)
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+
+    assert!(output.status.success());
+    assert_eq!(stdout.lines().count(), 3);
+    assert!(stdout
+        .starts_with("#name\tftp_path\tbiosample\tspecies\tassembly_level\treason\n"));
+    assert!(stdout.contains("E_coli_K_12_MG1655_GCF_000005845_2\t"));
+    assert!(stdout.contains(
+        "\t1 of 3 in genus Escherichia: reference genome, Complete Genome, contig_n50 4600000, released 2013-09-26\n"
+    ));
+    assert!(stdout.contains("\t1 of 1 in genus Salmonella: "));
+
+    // The two largest contig N50s of each species
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("assembly")
+        .arg("Escherichia")
+        .arg("--per")
+        .arg("species")
+        .arg("--top")
+        .arg("2")
+        .arg("--by")
+        .arg("contig_n50")
+        .arg("--by")
+        .arg("type")
+        .arg("--dir")
+        .arg(dir)
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+
+    assert!(output.status.success());
+    assert_eq!(stdout.lines().count(), 4);
+    assert!(stdout.contains(
+        "\t1 of 2 in species Escherichia coli: contig_n50 4600001, not type material\n"
+    ));
+    assert!(
+        stdout.contains("\t2 of 2 in species Escherichia coli: contig_n50 4600000, ")
+    );
+    assert!(stdout.contains(
+        "\t1 of 1 in species Escherichia fergusonii: contig_n50 20000, assembly from type material\n"
+    ));
+
+    // The GenBank database hasn't been built
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("assembly")
+        .arg("Bacillus")
+        .arg("--source")
+        .arg("genbank")
+        .arg("--dir")
+        .arg(dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("nwr ardb --genbank"));
+
+    // RefSeq and GenBank in one database; this summary has the legacy columns
+    let mut summary = String::from("# assembly_accession\tbioproject\tbiosample\twgs_master\trefseq_category\ttaxid\tspecies_taxid\torganism_name\tinfraspecific_name\tisolate\tversion_status\tassembly_level\trelease_type\tgenome_rep\tseq_rel_date\tasm_name\tsubmitter\tgbrs_paired_asm\tpaired_asm_comp\tftp_path\texcluded_from_refseq\trelation_to_type_material\tasm_not_live_date\n");
+    let id = tax_id("Escherichia coli")?;
+    for (accession, strain, paired, comp) in [
+        (
+            "GCA_000005845.2",
+            "K-12 substr. MG1655",
+            "GCF_000005845.2",
+            "identical",
+        ),
+        ("GCA_900000001.1", "XYZ", "na", "na"),
+    ] {
+        summary.push_str(&format!(
+            "{accession}\tPRJNA1\tSAMN{}\t\tna\t{id}\t{id}\tEscherichia coli {strain}\tstrain={strain}\t\tlatest\tComplete Genome\tMajor\tFull\t2020/01/01\tASM1v1\tNCBI\t{paired}\t{comp}\thttps://ftp.ncbi.nlm.nih.gov/genomes/all/{accession}_ASM1v1\t\t\tna\n",
+            &accession[4..13]
+        ));
+    }
+    std::fs::write(dir.join("assembly_summary_genbank.txt"), summary)?;
+    Command::cargo_bin("nwr")?
+        .arg("ardb")
+        .arg("--all")
+        .arg("--dir")
+        .arg(dir)
+        .assert()
+        .success();
+
+    let all_conn = rusqlite::Connection::open(dir.join("ar_all.sqlite"))?;
+    let pair: (String, String, String) = all_conn.query_row(
+        "SELECT refseq_accession, genbank_accession, paired_asm_comp FROM pair",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    assert_eq!(
+        pair,
+        (
+            "GCF_000005845.2".to_string(),
+            "GCA_000005845.2".to_string(),
+            "identical".to_string()
+        )
+    );
+    let count: i64 = all_conn.query_row(
+        "SELECT COUNT(*) FROM ar WHERE source = 'genbank'",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(count, 2);
+
+    for (prefer, kept, dropped) in [
+        ("refseq", "GCF_000005845_2", "GCA_000005845_2"),
+        ("genbank", "GCA_000005845_2", "GCF_000005845_2"),
+    ] {
+        let mut cmd = Command::cargo_bin("nwr")?;
+        let output = cmd
+            .arg("assembly")
+            .arg("Escherichia")
+            .arg("--level")
+            .arg("Complete Genome")
+            .arg("--source")
+            .arg("all")
+            .arg("--prefer")
+            .arg(prefer)
+            .arg("--column")
+            .arg("source")
+            .arg("--dir")
+            .arg(dir)
+            .output()?;
+        let stdout = String::from_utf8(output.stdout)?;
+
+        assert!(output.status.success());
+        assert_eq!(stdout.lines().count(), 4);
+        assert!(stdout.contains(kept));
+        assert!(!stdout.contains(dropped));
+        assert!(stdout.contains("E_coli_XYZ_GCA_900000001_1\t"));
+        assert!(stdout.contains("E_coli_Sakai_GCF_000008865_2\t"));
+    }
+
+    // Pairs come from `pair`, not `gbrs_paired_asm`
+    all_conn.execute("DELETE FROM pair", [])?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("assembly")
+        .arg("Escherichia")
+        .arg("--level")
+        .arg("Complete Genome")
+        .arg("--source")
+        .arg("all")
+        .arg("--dir")
+        .arg(dir)
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(output.status.success());
+    assert_eq!(stdout.lines().count(), 5);
+    assert!(stdout.contains("GCA_000005845_2"));
+    assert!(stdout.contains("GCF_000005845_2"));
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("assembly")
+        .arg("Escherichia")
+        .arg("--source")
+        .arg("all")
+        .arg("--source")
+        .arg("refseq")
+        .arg("--dir")
+        .arg(dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--source all already holds"));
+
+    Ok(())
+}
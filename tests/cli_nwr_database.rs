@@ -1,9 +1,13 @@
 use assert_cmd::prelude::*;
 use predicates::prelude::*;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use std::process::Command;
 
+mod common;
+use common::{Failure, Route};
+
 #[test]
 fn command_download_help() -> anyhow::Result<()> {
     let mut cmd = Command::cargo_bin("nwr")?;
@@ -35,10 +39,277 @@ fn command_invalid() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn command_download_http() -> anyhow::Result<()> {
+    let tarball = fs::read("tests/nwr/taxdump_new.tar.gz")?;
+    let md5 = format!("{:x}  taxdump.tar.gz\n", md5::compute(&tarball));
+    let summary = fs::read("tests/nwr/assembly_summary_refseq.txt")?;
+
+    // The tarball fails once, then breaks off halfway
+    let server = common::serve(HashMap::from([
+        (
+            "/pub/taxonomy/taxdump.tar.gz".to_string(),
+            Route::new(&tarball)
+                .failing(Failure::Status(503))
+                .failing(Failure::Truncate(tarball.len() / 2)),
+        ),
+        (
+            "/pub/taxonomy/taxdump.tar.gz.md5".to_string(),
+            Route::new(md5.as_bytes()),
+        ),
+        (
+            "/genomes/ASSEMBLY_REPORTS/assembly_summary_refseq.txt".to_string(),
+            Route::new(&summary),
+        ),
+        (
+            "/genomes/ASSEMBLY_REPORTS/assembly_summary_genbank.txt".to_string(),
+            Route::new(&summary),
+        ),
+    ]));
+
+    let tempdir = tempfile::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("download")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("--host")
+        .arg(&server.url)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("MD5 sum passed"));
+
+    assert_eq!(fs::read(tempdir.path().join("taxdump.tar.gz"))?, tarball);
+    assert_eq!(
+        fs::read(tempdir.path().join("assembly_summary_genbank.txt"))?,
+        summary
+    );
+    assert!(!tempdir.path().join("taxdump.tar.gz.part").exists());
+
+    // Retried, and resumed where the truncated response stopped
+    let requests = server.requests.lock().unwrap().clone();
+    let tries: Vec<&String> = requests
+        .iter()
+        .filter(|r| r.starts_with("/pub/taxonomy/taxdump.tar.gz") && !r.contains(".md5"))
+        .collect();
+    assert_eq!(tries.len(), 3);
+    assert_eq!(
+        tries[2],
+        &format!("/pub/taxonomy/taxdump.tar.gz bytes={}-", tarball.len() / 2)
+    );
+
+    // Files that aren't there fail without retries
+    let tempdir = tempfile::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("download")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("--host")
+        .arg(&server.url)
+        .arg("--tx")
+        .arg("/missing")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("HTTP 404"));
+
+    Ok(())
+}
+
+#[test]
+fn command_download_http_part() -> anyhow::Result<()> {
+    let tarball = fs::read("tests/nwr/taxdump_new.tar.gz")?;
+    let md5 = format!("{:x}  taxdump.tar.gz\n", md5::compute(&tarball));
+    let summary = fs::read("tests/nwr/assembly_summary_refseq.txt")?;
+    let routes = HashMap::from([
+        (
+            "/pub/taxonomy/taxdump.tar.gz".to_string(),
+            Route::new(&tarball),
+        ),
+        (
+            "/pub/taxonomy/taxdump.tar.gz.md5".to_string(),
+            Route::new(md5.as_bytes()),
+        ),
+        (
+            "/genomes/ASSEMBLY_REPORTS/assembly_summary_refseq.txt".to_string(),
+            Route::new(&summary),
+        ),
+        (
+            "/genomes/ASSEMBLY_REPORTS/assembly_summary_genbank.txt".to_string(),
+            Route::new(&summary),
+        ),
+    ]);
+    let tries = |server: &common::TestServer| -> Vec<String> {
+        server
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| {
+                r.starts_with("/pub/taxonomy/taxdump.tar.gz") && !r.contains(".md5")
+            })
+            .cloned()
+            .collect()
+    };
+
+    // A complete .part, as after a crash before the rename, is kept
+    let server = common::serve(routes.clone());
+    let tempdir = tempfile::TempDir::new()?;
+    fs::write(tempdir.path().join("taxdump.tar.gz.part"), &tarball)?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("download")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("--host")
+        .arg(&server.url)
+        .assert()
+        .success();
+    assert_eq!(fs::read(tempdir.path().join("taxdump.tar.gz"))?, tarball);
+    assert_eq!(
+        tries(&server),
+        vec![format!(
+            "/pub/taxonomy/taxdump.tar.gz bytes={}-",
+            tarball.len()
+        )]
+    );
+
+    // One longer than the remote file is downloaded again
+    let server = common::serve(routes);
+    let tempdir = tempfile::TempDir::new()?;
+    let mut longer = tarball.clone();
+    longer.extend_from_slice(b"stale");
+    fs::write(tempdir.path().join("taxdump.tar.gz.part"), &longer)?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("download")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("--host")
+        .arg(&server.url)
+        .assert()
+        .success();
+    assert_eq!(fs::read(tempdir.path().join("taxdump.tar.gz"))?, tarball);
+    assert_eq!(tries(&server).len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn command_bundle_install() -> anyhow::Result<()> {
+    let tarball = fs::read("tests/nwr/taxdump_new.tar.gz")?;
+    let source = tempfile::TempDir::new()?;
+    fs::write(source.path().join("taxdump.tar.gz"), &tarball)?;
+    fs::write(
+        source.path().join("taxdump.tar.gz.md5"),
+        format!("{:x}  taxdump.tar.gz\n", md5::compute(&tarball)),
+    )?;
+    fs::copy(
+        "tests/nwr/assembly_summary_refseq.txt",
+        source.path().join("assembly_summary_refseq.txt"),
+    )?;
+
+    let bundle = source.path().join("ncbi.tar.gz");
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("bundle")
+        .arg(&bundle)
+        .arg("--dir")
+        .arg(source.path())
+        .assert()
+        .success();
+
+    let target = tempfile::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("install")
+        .arg(&bundle)
+        .arg("--ardb")
+        .arg("--dir")
+        .arg(target.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("MD5 sum passed"));
+
+    assert_eq!(fs::read(target.path().join("taxdump.tar.gz"))?, tarball);
+    assert!(target.path().join("taxonomy.sqlite").exists());
+    assert!(target.path().join("ar_refseq.sqlite").exists());
+    assert!(!target.path().join("ar_genbank.sqlite").exists());
+    assert!(!target.path().join(".bundle").exists());
+
+    // A corrupted download leaves the target untouched
+    fs::write(
+        source.path().join("taxdump.tar.gz.md5"),
+        "00000000000000000000000000000000  taxdump.tar.gz\n",
+    )?;
+    let target = tempfile::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("install")
+        .arg(source.path())
+        .arg("--dir")
+        .arg(target.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("MD5 check failed"));
+
+    assert!(!target.path().join("taxdump.tar.gz").exists());
+    assert!(!target.path().join(".bundle").exists());
+
+    Ok(())
+}
+
+#[test]
+fn command_lock() -> anyhow::Result<()> {
+    let tempdir = tempfile::TempDir::new()?;
+    fs::copy(
+        "tests/nwr/taxonomy.sqlite",
+        tempdir.path().join("taxonomy.sqlite"),
+    )?;
+
+    // The test holds the lock as a running rebuild would
+    let lock = nwr::libs::lock::lock_dir(tempdir.path())?;
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.env("NWR_LOCK_TIMEOUT", "0")
+        .arg("info")
+        .arg("12340")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "is being rebuilt by another nwr command",
+        ));
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.env("NWR_LOCK_TIMEOUT", "0")
+        .arg("ardb")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is in use by another nwr command"));
+
+    // Readers wait for it to finish
+    let holder = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        drop(lock);
+    });
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.env("NWR_LOCK_TIMEOUT", "30")
+        .arg("info")
+        .arg("12340")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Waiting for another nwr command"))
+        .stdout(predicate::str::contains("Enterobacteria phage 933J"));
+    holder.join().unwrap();
+
+    Ok(())
+}
+
 #[test]
 fn command_txdb() -> anyhow::Result<()> {
+    // Fixed times keep the tracked fixture reproducible
     let mut cmd = Command::cargo_bin("nwr")?;
     let output = cmd
+        .env("SOURCE_DATE_EPOCH", "0")
         .arg("txdb")
         .arg("--dir")
         .arg("tests/nwr/")
@@ -50,13 +321,80 @@ fn command_txdb() -> anyhow::Result<()> {
     // Progress log lines go to stderr; their count varies based on data size.
     assert!(stderr.lines().count() >= 5);
 
+    // Every node has a precomputed lineage
+    let conn = rusqlite::Connection::open("tests/nwr/taxonomy.sqlite")?;
+    let missing: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM node WHERE tax_id NOT IN (SELECT tax_id FROM lineage)",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(missing, 0);
+    let lineage: String = conn.query_row(
+        "SELECT lineage FROM lineage WHERE tax_id = 12340",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(lineage, "1 10239 12333 12340");
+
+    // Sources are recorded
+    let built: String =
+        conn.query_row("SELECT value FROM meta WHERE key = 'built'", [], |row| {
+            row.get(0)
+        })?;
+    assert_eq!(built, "1970-01-01T00:00:00Z");
+    let md5: String = conn.query_row(
+        "SELECT value FROM meta WHERE key = 'source:nodes.dmp:md5'",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(md5.len(), 32);
+
+    Ok(())
+}
+
+#[test]
+fn command_txdb_gtdb() -> anyhow::Result<()> {
+    // Fixed times keep the tracked fixture reproducible
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .env("SOURCE_DATE_EPOCH", "0")
+        .arg("txdb")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--gtdb")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let conn = rusqlite::Connection::open("tests/nwr/gtdb.sqlite")?;
+    // root + 2 domains, 5 phyla, ..., 6 species
+    let nodes: i64 =
+        conn.query_row("SELECT COUNT(*) FROM node", [], |row| row.get(0))?;
+    assert_eq!(nodes, 36);
+    let genomes: i64 =
+        conn.query_row("SELECT COUNT(*) FROM genome", [], |row| row.get(0))?;
+    assert_eq!(genomes, 7);
+
+    // No taxonomy files
+    let tempdir = tempfile::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("txdb")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("--gtdb")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No GTDB taxonomy files found"));
+
     Ok(())
 }
 
 #[test]
 fn command_ardb() -> anyhow::Result<()> {
+    // Fixed times keep the tracked fixture reproducible
     let mut cmd = Command::cargo_bin("nwr")?;
     let output = cmd
+        .env("SOURCE_DATE_EPOCH", "0")
         .arg("ardb")
         .arg("--dir")
         .arg("tests/nwr/")
@@ -70,6 +408,68 @@ fn command_ardb() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn command_ardb_filters() -> anyhow::Result<()> {
+    let tempdir = tempfile::TempDir::new()?;
+    fs::copy(
+        "tests/nwr/taxonomy.sqlite",
+        tempdir.path().join("taxonomy.sqlite"),
+    )?;
+    let mut summary = String::from("# assembly_accession\tbioproject\tbiosample\twgs_master\trefseq_category\ttaxid\tspecies_taxid\torganism_name\tinfraspecific_name\tisolate\tversion_status\tassembly_level\trelease_type\tgenome_rep\tseq_rel_date\tasm_name\tsubmitter\tgbrs_paired_asm\tpaired_asm_comp\tftp_path\texcluded_from_refseq\trelation_to_type_material\tasm_not_live_date\n");
+    for (i, (tax_id, name)) in [
+        (12340, "Enterobacteria phage 933J"),
+        (12347, "Actinophage JHJ-1"),
+        (38018, "unidentified phage"),
+        (562, "Escherichia coli"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        summary.push_str(&format!(
+            "GCF_00000000{i}.1\tPRJNA1\tSAMN1\t\tna\t{tax_id}\t{tax_id}\t{name}\tna\t\tlatest\tComplete Genome\tMajor\tFull\t2020/01/01\tASM1v1\tNCBI\tna\tna\thttps://ftp.ncbi.nlm.nih.gov/genomes/all/GCF_00000000{i}.1_ASM1v1\t\t\tna\n"
+        ));
+    }
+    fs::write(tempdir.path().join("assembly_summary_refseq.txt"), summary)?;
+
+    let ardb = |args: &[&str]| -> anyhow::Result<(i64, Vec<(String, String)>)> {
+        Command::cargo_bin("nwr")?
+            .arg("ardb")
+            .args(args)
+            .arg("--dir")
+            .arg(tempdir.path())
+            .assert()
+            .success();
+        let conn = rusqlite::Connection::open(tempdir.path().join("ar_refseq.sqlite"))?;
+        let count = conn.query_row("SELECT COUNT(*) FROM ar", [], |row| row.get(0))?;
+        let skipped = conn
+            .prepare("SELECT assembly_accession, reason FROM skipped ORDER BY line")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        Ok((count, skipped))
+    };
+
+    // The built-in filters
+    let (count, skipped) = ardb(&[])?;
+    assert_eq!(count, 1);
+    let reasons: Vec<&str> = skipped.iter().map(|(_, reason)| reason.as_str()).collect();
+    assert_eq!(reasons, vec!["virus", "virus", "incompetent"]);
+    assert_eq!(skipped[0].0, "GCF_000000000.1");
+
+    // Phages only; taxa missing from the taxonomy have no division
+    let (count, skipped) = ardb(&["--keep", "virus", "--division", "Phages"])?;
+    assert_eq!(count, 2);
+    assert_eq!(skipped[1].1, "division: NA");
+
+    let (count, skipped) = ardb(&["--keep", "all", "--exclude", "^actino"])?;
+    assert_eq!(count, 3);
+    assert_eq!(skipped[0].1, "exclude: ^actino");
+
+    let (count, _) = ardb(&["--keep", "all", "--include", "phage"])?;
+    assert_eq!(count, 3);
+
+    Ok(())
+}
+
 /// Copy a fixture file and insert a single blank line after the specified
 /// 0-based line index. This lets us exercise blank-line skipping without
 /// modifying the original test fixtures.
@@ -95,8 +495,11 @@ fn setup_blank_line_fixtures() -> anyhow::Result<()> {
     let dst = Path::new("tests/nwr/blank_line");
     fs::create_dir_all(dst)?;
     copy_with_blank_line(src, dst, "division.dmp", 0)?;
+    copy_with_blank_line(src, dst, "gencode.dmp", 0)?;
     copy_with_blank_line(src, dst, "names.dmp", 0)?;
     copy_with_blank_line(src, dst, "nodes.dmp", 0)?;
+    copy_with_blank_line(src, dst, "merged.dmp", 0)?;
+    copy_with_blank_line(src, dst, "delnodes.dmp", 0)?;
     // The first two lines of the assembly summary are header comments; insert
     // a blank line after the first data row.
     copy_with_blank_line(src, dst, "assembly_summary_refseq.txt", 2)?;
@@ -148,3 +551,378 @@ fn command_ardb_blank_lines() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn command_custom() -> anyhow::Result<()> {
+    let tempdir = tempfile::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("custom")
+        .arg("tests/nwr/custom.tsv")
+        .arg("--outdir")
+        .arg(tempdir.path())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("lineage")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("Alphatestvirus two")
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+
+    // root, family, genus, species
+    assert_eq!(stdout.lines().count(), 4);
+    assert!(stdout.contains("family\tTestviridae"));
+    assert!(stdout.contains("species\tAlphatestvirus two"));
+
+    // Lineage strings
+    let tempdir = tempfile::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("custom")
+        .arg("tests/nwr/custom_lineage.tsv")
+        .arg("--lineage")
+        .arg("2")
+        .arg("--outdir")
+        .arg(tempdir.path())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("lineage")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("Gammatestvirus")
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+
+    assert_eq!(stdout.lines().count(), 3);
+    assert!(stdout.contains("no rank\tGammatestvirus"));
+
+    // One "environmental samples" under each family
+    let conn = rusqlite::Connection::open(tempdir.path().join("taxonomy.sqlite"))?;
+    let parents: Vec<String> = conn
+        .prepare(
+            "SELECT p.name FROM name n
+             INNER JOIN node ON node.tax_id = n.tax_id
+             INNER JOIN name p ON p.tax_id = node.parent_tax_id
+             WHERE n.name = 'environmental samples'
+             ORDER BY p.name",
+        )?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    assert_eq!(parents, vec!["Othertestviridae", "Testviridae"]);
+
+    // The same rows in reverse order give the same tax IDs
+    let taxa = |conn: &rusqlite::Connection| -> anyhow::Result<Vec<(i64, String)>> {
+        Ok(conn
+            .prepare("SELECT tax_id, name FROM name ORDER BY tax_id")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?)
+    };
+    let reversed = tempfile::TempDir::new()?;
+    let text = fs::read_to_string("tests/nwr/custom_lineage.tsv")?;
+    let mut lines: Vec<&str> = text.lines().collect();
+    lines[1..].reverse();
+    let infile = reversed.path().join("custom_lineage.tsv");
+    fs::write(&infile, lines.join("\n") + "\n")?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("custom")
+        .arg(&infile)
+        .arg("--lineage")
+        .arg("2")
+        .arg("--outdir")
+        .arg(reversed.path())
+        .assert()
+        .success();
+    let conn_reversed =
+        rusqlite::Connection::open(reversed.path().join("taxonomy.sqlite"))?;
+    assert_eq!(taxa(&conn)?, taxa(&conn_reversed)?);
+
+    Ok(())
+}
+
+#[test]
+fn command_custom_graft() -> anyhow::Result<()> {
+    let tempdir = tempfile::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("custom")
+        .arg("tests/nwr/custom.tsv")
+        .arg("--graft")
+        .arg("unclassified bacterial viruses")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--outdir")
+        .arg(tempdir.path())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("lineage")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("Alphatestvirus one")
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+
+    assert!(stdout.contains("superkingdom\tViruses\t10239"));
+    assert!(stdout.contains("\tunclassified bacterial viruses\t12333"));
+    assert!(stdout.contains("species\tAlphatestvirus one"));
+
+    // NCBI taxa are still there
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("info")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("--tsv")
+        .arg("Phage h30")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("12371"));
+
+    // Grafting into the source database
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("custom")
+        .arg("tests/nwr/custom.tsv")
+        .arg("--graft")
+        .arg("12333")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--outdir")
+        .arg("tests/nwr/")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--outdir must differ"));
+
+    Ok(())
+}
+
+#[test]
+fn command_diff() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("diff")
+        .arg("tests/nwr/taxonomy.sqlite")
+        .arg("tests/nwr/taxdump_new.tar.gz")
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+
+    assert_eq!(stdout.lines().count(), 9);
+    assert!(stdout.contains("renamed\t12371\tPhage H30\tPhage h30\tPhage H30"));
+    assert!(stdout.contains("rank\t12374\tLactococcus phage\tspecies\tno rank"));
+    assert!(stdout.contains("reparented\t12375\t"));
+    assert!(stdout.contains("merged\t12388\tMycobacterium phage FRAT1\t12388\t12392"));
+    assert!(stdout.contains("deleted\t12403\t"));
+    assert!(stdout.contains("added\t99999\tNovel phage 1\tNA\tspecies"));
+
+    // Subtree
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("diff")
+        .arg("tests/nwr/")
+        .arg("tests/nwr/taxdump_new.tar.gz")
+        .arg("--subtree")
+        .arg("environmental samples")
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+
+    assert_eq!(stdout.lines().count(), 3);
+    assert!(stdout.contains("reparented\t156614\t"));
+    assert!(stdout.contains("renamed\t156615\t"));
+
+    Ok(())
+}
+
+#[test]
+fn command_diff_file() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("diff")
+        .arg("tests/nwr/taxonomy.sqlite")
+        .arg("tests/nwr/taxdump_new.tar.gz")
+        .arg("-f")
+        .arg("tests/nwr/diff.tsv")
+        .arg("-c")
+        .arg("2")
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+
+    assert_eq!(stdout.lines().count(), 5);
+    assert!(stdout.contains("#name\ttax_id\tchange\tnew_tax_id\tnew_name"));
+    assert!(stdout.contains("12371\trenamed\t12371\tPhage H30"));
+    assert!(stdout.contains("12388\tmerged\t12392\tLactobacillus phage mv4"));
+    assert!(stdout.contains("12403\tdeleted\tNA\tNA"));
+    assert!(stdout.contains("156617\tlineage\t156617\t"));
+    // Unchanged rows are left out
+    assert!(!stdout.contains("12347"));
+
+    Ok(())
+}
+
+#[test]
+fn command_txdb_tarball() -> anyhow::Result<()> {
+    // Explicit tarball
+    let tempdir = tempfile::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("txdb")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("--tarball")
+        .arg("tests/nwr/taxdump_new.tar.gz")
+        .assert()
+        .success();
+
+    let conn = rusqlite::Connection::open(tempdir.path().join("taxonomy.sqlite"))?;
+    let name: String =
+        conn.query_row("SELECT name FROM name WHERE tax_id = 99999", [], |row| {
+            row.get(0)
+        })?;
+    assert_eq!(name, "Novel phage 1");
+    let merged: i64 =
+        conn.query_row("SELECT COUNT(*) FROM merged", [], |row| row.get(0))?;
+    assert_eq!(merged, 3);
+
+    // taxdump.tar.gz in the directory is picked up without --tarball
+    let tempdir = tempfile::TempDir::new()?;
+    fs::copy(
+        "tests/nwr/taxdump_new.tar.gz",
+        tempdir.path().join("taxdump.tar.gz"),
+    )?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("txdb")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .assert()
+        .success();
+    assert!(tempdir.path().join("taxonomy.sqlite").exists());
+    assert!(!tempdir.path().join("nodes.dmp").exists());
+
+    // A stale extracted file isn't mixed into a tarball import
+    let tempdir = tempfile::TempDir::new()?;
+    fs::copy("tests/nwr/host.dmp", tempdir.path().join("host.dmp"))?;
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("txdb")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("--tarball")
+        .arg("tests/nwr/taxdump_new.tar.gz")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("host.dmp: not in the tarballs"));
+
+    let conn = rusqlite::Connection::open(tempdir.path().join("taxonomy.sqlite"))?;
+    let hosts: i64 =
+        conn.query_row("SELECT COUNT(*) FROM host", [], |row| row.get(0))?;
+    assert_eq!(hosts, 0);
+
+    Ok(())
+}
+
+#[test]
+fn command_txdb_tarball_missing_entries() -> anyhow::Result<()> {
+    let tempdir = tempfile::TempDir::new()?;
+    let tarball = tempdir.path().join("partial.tar.gz");
+    {
+        let gz = flate2::write::GzEncoder::new(
+            fs::File::create(&tarball)?,
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(gz);
+        builder.append_path_with_name("tests/nwr/nodes.dmp", "nodes.dmp")?;
+        builder.append_path_with_name("tests/nwr/names.dmp", "names.dmp")?;
+        builder.into_inner()?.finish()?;
+    }
+
+    // The existing database is left alone
+    let db = tempdir.path().join("taxonomy.sqlite");
+    fs::write(&db, "previous")?;
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("txdb")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("--tarball")
+        .arg(&tarball)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Missing division.dmp, gencode.dmp, merged.dmp, delnodes.dmp",
+        ));
+    assert_eq!(fs::read_to_string(&db)?, "previous");
+
+    Ok(())
+}
+
+#[test]
+fn command_txdb_validation() -> anyhow::Result<()> {
+    let tempdir = tempfile::TempDir::new()?;
+    let tarball = tempdir.path().join("rootless.tar.gz");
+    {
+        let gz = flate2::write::GzEncoder::new(
+            fs::File::create(&tarball)?,
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(gz);
+        for name in [
+            "division.dmp",
+            "gencode.dmp",
+            "names.dmp",
+            "merged.dmp",
+            "delnodes.dmp",
+        ] {
+            builder.append_path_with_name(format!("tests/nwr/{name}"), name)?;
+        }
+        // nodes.dmp without the root
+        let nodes: String = fs::read_to_string("tests/nwr/nodes.dmp")?
+            .lines()
+            .filter(|line| !line.starts_with("1\t"))
+            .map(|line| format!("{line}\n"))
+            .collect();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(nodes.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "nodes.dmp", nodes.as_bytes())?;
+        builder.into_inner()?.finish()?;
+    }
+
+    // A database that fails the checks doesn't replace the existing one
+    let db = tempdir.path().join("taxonomy.sqlite");
+    fs::write(&db, "previous")?;
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("txdb")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("--tarball")
+        .arg(&tarball)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Root node 1 is missing"));
+    assert_eq!(fs::read_to_string(&db)?, "previous");
+    assert!(!tempdir.path().join("taxonomy.sqlite.tmp").exists());
+
+    // A good one does, keeping the previous one as the backup
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("txdb")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("--tarball")
+        .arg("tests/nwr/taxdump_new.tar.gz")
+        .assert()
+        .success();
+    let conn = rusqlite::Connection::open(&db)?;
+    let count: i64 =
+        conn.query_row("SELECT COUNT(*) FROM node", [], |row| row.get(0))?;
+    assert!(count > 0);
+    assert!(!tempdir.path().join("taxonomy.sqlite.tmp").exists());
+    assert_eq!(
+        fs::read_to_string(tempdir.path().join("taxonomy.sqlite.bak"))?,
+        "previous"
+    );
+
+    Ok(())
+}
@@ -52,6 +52,17 @@ fn command_seqdb_load_strain() -> anyhow::Result<()> {
 
     assert!(output.status.success());
 
+    // The loaded file is recorded
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("status")
+        .arg("--dir")
+        .arg(temp_dir.path())
+        .arg(temp_dir.path().join("seq.sqlite"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("source:seqdb_strains.tsv:md5"))
+        .stdout(predicate::str::contains("rows:rank\t"));
+
     Ok(())
 }
 
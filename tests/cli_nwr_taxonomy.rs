@@ -1,4 +1,5 @@
 use assert_cmd::prelude::*;
+use predicates::prelude::*;
 use std::io::Write;
 use std::process::Command;
 
@@ -30,8 +31,12 @@ fn command_info() -> anyhow::Result<()> {
         .unwrap();
     let stdout = String::from_utf8(output.stdout).unwrap();
 
-    assert_eq!(stdout.lines().count(), 12);
+    assert_eq!(stdout.lines().count(), 14);
     assert!(stdout.contains("ID: 10239"), "first record");
+    assert!(
+        stdout.contains("Genetic code: 11 (Bacterial, Archaeal and Plant Plastid)"),
+        "genetic code"
+    );
 
     Ok(())
 }
@@ -646,3 +651,744 @@ fn command_restrict_strict_fails() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn command_info_merged() -> anyhow::Result<()> {
+    // 12339 has been merged into 12340
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("info")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--tsv")
+        .arg("--resolved")
+        .arg("12339")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("#term\ttax_id"));
+    assert!(stdout.contains("12339\t12340\tEnterobacteria phage 933J"));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("merged into 12340"));
+
+    Ok(())
+}
+
+#[test]
+fn command_info_deleted() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("info")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("12341")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("has been deleted"));
+
+    Ok(())
+}
+
+#[test]
+fn command_append_resolved() -> anyhow::Result<()> {
+    let mut temp = tempfile::NamedTempFile::new()?;
+    writeln!(temp, "#name\ttax_id")?;
+    writeln!(temp, "old\t12345")?;
+    let path = temp.into_temp_path();
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("append")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("-c")
+        .arg("2")
+        .arg("-r")
+        .arg("species")
+        .arg("--resolved")
+        .arg(path.to_str().unwrap())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().next().unwrap(),
+        "#name\ttax_id\tresolved_id\tspecies"
+    );
+    assert!(stdout.contains("old\t12345\t12347\tActinophage JHJ-1"));
+
+    Ok(())
+}
+
+#[test]
+fn command_restrict_resolved() -> anyhow::Result<()> {
+    let mut temp = tempfile::NamedTempFile::new()?;
+    writeln!(temp, "#tax_id")?;
+    writeln!(temp, "12345")?;
+    let path = temp.into_temp_path();
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("restrict")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("Viruses")
+        .arg("--resolved")
+        .arg("-f")
+        .arg(path.to_str().unwrap())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "#tax_id\tresolved_id\n12345\t12347\n");
+
+    Ok(())
+}
+
+#[test]
+fn command_info_gc() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("info")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--tsv")
+        .arg("--gc")
+        .arg("Bacillus phage bg1")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().next().unwrap(),
+        "#tax_id\tsci_name\trank\tdivision\tgc_id\tgc_name\tmgc_id\tmgc_name"
+    );
+    assert!(stdout.contains("\tPhages\t11\tBacterial, Archaeal and Plant Plastid\t0\t"));
+
+    Ok(())
+}
+
+#[test]
+fn command_append_gc() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("append")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("-c")
+        .arg("2")
+        .arg("-r")
+        .arg("species")
+        .arg("--gc")
+        .arg("tests/nwr/taxon-valid.tsv")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().next().unwrap(),
+        "#sci_name\ttax_id\tspecies\tgc_id\tmgc_id"
+    );
+    assert!(stdout.contains("\t12347\tActinophage JHJ-1\t11\t0"));
+
+    Ok(())
+}
+
+#[test]
+fn command_lineage_ranked() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("lineage")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--ranked")
+        .arg("12340")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 2);
+    assert_eq!(stdout.lines().next().unwrap(), "superkingdom\tViruses");
+    assert_eq!(
+        stdout.lines().last().unwrap(),
+        "species\tEnterobacteria phage 933J"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn command_info_host_type_material() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("info")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--tsv")
+        .arg("--host")
+        .arg("--type-material")
+        .arg("12340")
+        .arg("12347")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().next().unwrap(),
+        "#tax_id\tsci_name\trank\tdivision\thost\ttype_material"
+    );
+    assert!(stdout.contains("\tbacteria\ttype strain:ATCC 23631-B1\n"));
+    assert!(stdout.contains("12347\tActinophage JHJ-1\tspecies\t"));
+
+    // Text mode shows these sections automatically
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("info")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("12340")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Type material:\n* type strain ATCC 23631-B1\n"));
+    assert!(stdout.contains("Potential hosts: bacteria."));
+
+    Ok(())
+}
+
+#[test]
+fn command_append_host() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("append")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("-c")
+        .arg("2")
+        .arg("--host")
+        .arg("tests/nwr/taxon-valid.tsv")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().next().unwrap(),
+        "#sci_name\ttax_id\tsci_name\thost"
+    );
+    assert!(stdout.contains("\t12347\tActinophage JHJ-1\tbacteria"));
+
+    Ok(())
+}
+
+#[test]
+fn command_lca() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("lca")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("370566")
+        .arg("Streptococcus phage 10750.2")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "387086\tStreptococcus pyogenes MGAS10750 phage\tspecies\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn command_lca_file() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("lca")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("-f")
+        .arg("tests/nwr/lca.tsv")
+        .arg("-c")
+        .arg("2")
+        .output()
+        .unwrap();
+
+    // Invalid IDs are errors by default
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("lca.tsv:5:"));
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("lca")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("-f")
+        .arg("tests/nwr/lca.tsv")
+        .arg("-c")
+        .arg("2")
+        .arg("--ignore-invalid")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "#query\thits\tlca_id\tlca_name\tlca_rank");
+    assert!(
+        lines[1].ends_with("\t387086\tStreptococcus pyogenes MGAS10750 phage\tspecies")
+    );
+    assert!(lines[2].ends_with("\t12333\tunclassified bacterial viruses\tno rank"));
+    assert!(lines[3].ends_with("\t12340\tEnterobacteria phage 933J\tspecies"));
+    assert_eq!(lines[4], "q4\t999999999\t0\tNA\tNA");
+
+    Ok(())
+}
+
+#[test]
+fn command_reformat() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("reformat")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("-c")
+        .arg("2")
+        .arg("tests/nwr/taxon-valid.tsv")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().next().unwrap(), "#sci_name\ttax_id\tlineage");
+    assert!(stdout.contains("\t12347\tViruses;;;;;;Actinophage JHJ-1\n"));
+
+    Ok(())
+}
+
+#[test]
+fn command_reformat_prefix_fill() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("reformat")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("-c")
+        .arg("2")
+        .arg("-f")
+        .arg("{domain};{family};{species}")
+        .arg("--fill")
+        .arg("Unclassified {parent} {rank}")
+        .arg("--prefix")
+        .arg("tests/nwr/taxon-valid.tsv")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(
+        "\t12347\td__Viruses;f__Unclassified Viruses family;s__Actinophage JHJ-1\n"
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn command_search() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("search")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("actinophage JHJ1")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines[0],
+        "#term\tname\tname_class\ttax_id\tsci_name\trank\tdistance"
+    );
+    assert_eq!(
+        lines[1],
+        "actinophage JHJ1\tActinophage JHJ-1\tscientific name\t12347\tActinophage JHJ-1\tspecies\t1"
+    );
+    assert!(lines.len() <= 11);
+
+    Ok(())
+}
+
+#[test]
+fn command_search_prefix() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("search")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--prefix")
+        .arg("-n")
+        .arg("100")
+        .arg("BACTERIOPHAGE")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\tBacteriophage JHJ-1\tsynonym\t12347\t"));
+    assert!(stdout.lines().skip(1).all(|l| l
+        .split('\t')
+        .nth(1)
+        .unwrap()
+        .to_lowercase()
+        .starts_with("bacteriophage")));
+
+    Ok(())
+}
+
+#[test]
+fn command_info_did_you_mean() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("info")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("Actinophage JHJ1")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr
+        .contains("No such name: Actinophage JHJ1. Did you mean 'Actinophage JHJ-1'"));
+
+    Ok(())
+}
+
+#[test]
+fn command_info_ambiguous() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("info")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("Streptococcus pyogenes prophage")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr
+        .contains("Ambiguous name: Streptococcus pyogenes prophage matches 2 taxa"));
+    assert!(stderr.contains("370559 (no rank): Viruses;"));
+    assert!(stderr.contains("370566 (no rank): Viruses;"));
+
+    // Name@Ancestor
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("info")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--tsv")
+        .arg("Streptococcus pyogenes prophage@Streptococcus pyogenes MGAS10750 phage")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("370566\tStreptococcus phage 10750.1\t"));
+
+    Ok(())
+}
+
+#[test]
+fn command_append_within() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("append")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--within")
+        .arg("387087")
+        .arg("tests/nwr/homonym.tsv")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "Streptococcus pyogenes prophage\tStreptococcus phage 10270.1\n12340\tEnterobacteria phage 933J\n"
+    );
+
+    // Ambiguous names are not attached silently
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("append")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("tests/nwr/homonym.tsv")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "12340\tEnterobacteria phage 933J\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Ambiguous name"));
+
+    Ok(())
+}
+
+#[test]
+fn command_info_gtdb() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("info")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--taxonomy")
+        .arg("gtdb")
+        .arg("--tsv")
+        .arg("RS_GCF_000005845.2")
+        .arg("GCA_000008445.1")
+        .arg("Bacillota")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[1].ends_with("\tEscherichia coli\tspecies\tBacteria"));
+    assert!(lines[2].ends_with("\tBacillus_A anthracis\tspecies\tBacteria"));
+    assert!(lines[3].ends_with("\tBacillota\tphylum\tBacteria"));
+
+    Ok(())
+}
+
+#[test]
+fn command_lineage_gtdb() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("lineage")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--taxonomy")
+        .arg("gtdb")
+        .arg("Pyrococcus furiosus")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 8);
+    assert!(stdout.contains("domain\tArchaea\t"));
+    assert!(stdout.contains("phylum\tMethanobacteriota_B\t"));
+
+    Ok(())
+}
+
+#[test]
+fn command_member_common_gtdb() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("member")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--taxonomy")
+        .arg("gtdb")
+        .arg("Bacillota")
+        .arg("-r")
+        .arg("species")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 3);
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("common")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .arg("--taxonomy")
+        .arg("gtdb")
+        .arg("Escherichia coli")
+        .arg("Bacillus subtilis")
+        .arg("Bacillus_A anthracis")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("(Bacillus subtilis,Bacillus_A anthracis)Bacillales"));
+    assert!(stdout.contains("Escherichia coli"));
+
+    Ok(())
+}
+
+#[test]
+fn command_status() -> anyhow::Result<()> {
+    let mut cmd = Command::cargo_bin("nwr")?;
+    let output = cmd
+        .arg("status")
+        .arg("--dir")
+        .arg("tests/nwr/")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.starts_with("#database\tkey\tvalue\n"));
+    assert!(stdout.contains("taxonomy.sqlite\tbuilt\t1970-01-01T00:00:00Z"));
+    assert!(stdout.contains("taxonomy.sqlite\tsource:nodes.dmp:md5\t"));
+    assert!(
+        stdout.contains("ar_refseq.sqlite\tsource:assembly_summary_refseq.txt:md5\t")
+    );
+    assert!(stdout.contains("gtdb.sqlite\trows:genome\t7"));
+
+    Ok(())
+}
+
+#[test]
+fn command_migrate() -> anyhow::Result<()> {
+    // A database from before versioning, lineages and the search index
+    let tempdir = tempfile::TempDir::new()?;
+    let db = tempdir.path().join("taxonomy.sqlite");
+    std::fs::copy("tests/nwr/taxonomy.sqlite", &db)?;
+    {
+        let conn = rusqlite::Connection::open(&db)?;
+        conn.execute_batch(
+            "DROP TABLE lineage; DROP TABLE name_fts; DROP TABLE meta; PRAGMA user_version = 0;",
+        )?;
+    }
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("lineage")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("12340")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("has schema version 0"))
+        .stderr(predicate::str::contains("nwr migrate"));
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("migrate")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("lineage")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .arg("12340")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("12333"));
+    let conn = rusqlite::Connection::open(&db)?;
+    let lineages: i64 =
+        conn.query_row("SELECT COUNT(*) FROM lineage", [], |row| row.get(0))?;
+    assert!(lineages > 0);
+    drop(conn);
+
+    // Imported data can't be recreated
+    {
+        let conn = rusqlite::Connection::open(&db)?;
+        conn.execute_batch("DROP TABLE merged; PRAGMA user_version = 0;")?;
+    }
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("migrate")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "missing table merged; re-run `nwr txdb`",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn command_doctor() -> anyhow::Result<()> {
+    let tempdir = tempfile::TempDir::new()?;
+    let db = tempdir.path().join("taxonomy.sqlite");
+    std::fs::copy("tests/nwr/taxonomy.sqlite", &db)?;
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("doctor")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "[OK] taxonomy.sqlite: 502 nodes, all connected to the root",
+        ))
+        .stdout(predicate::str::contains("0 problem(s), 0 warning(s)"));
+
+    // An orphan, a cycle, a name without a node, a stale download and a leftover
+    {
+        let conn = rusqlite::Connection::open(&db)?;
+        conn.execute_batch(
+            "
+            INSERT INTO node (tax_id, parent_tax_id, rank, division_id, gc_id, gc_inherited, mgc_id, mgc_inherited)
+            VALUES (900001, 800000, 'species', 0, 0, 0, 0, 0),
+                   (900002, 900003, 'genus', 0, 0, 0, 0, 0),
+                   (900003, 900002, 'family', 0, 0, 0, 0, 0);
+            INSERT INTO name (tax_id, name, name_class) VALUES (900004, 'Nowhere', 'scientific name');
+            ",
+        )?;
+    }
+    std::fs::write(tempdir.path().join("taxdump.tar.gz"), "not a tarball")?;
+    std::fs::write(
+        tempdir.path().join("taxdump.tar.gz.md5"),
+        "00000000000000000000000000000000  taxdump.tar.gz",
+    )?;
+    std::fs::write(tempdir.path().join("taxonomy.sqlite.tmp"), "")?;
+
+    let mut cmd = Command::cargo_bin("nwr")?;
+    cmd.arg("doctor")
+        .arg("--dir")
+        .arg(tempdir.path())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "[FAIL] taxdump.tar.gz: MD5 check failed",
+        ))
+        .stdout(predicate::str::contains(
+            "1 orphan node(s) whose parent is missing: 900001",
+        ))
+        .stdout(predicate::str::contains(
+            "2 node(s) in parent cycles: 900002, 900003",
+        ))
+        .stdout(predicate::str::contains(
+            "1 tax ID(s) in name without a node: 900004",
+        ))
+        .stdout(predicate::str::contains(
+            "[WARN] taxonomy.sqlite.tmp: left by an interrupted build",
+        ))
+        .stdout(predicate::str::contains("4 problem(s), 1 warning(s)"));
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+//! A local HTTP server for the download tests.
+
+// Each test crate uses a different part of it
+#![allow(dead_code)]
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+/// How the server misbehaves on a request.
+#[derive(Debug, Clone, Copy)]
+pub enum Failure {
+    /// Answer with this status code
+    Status(u16),
+    /// Announce the whole body but close the connection after this many bytes
+    Truncate(usize),
+}
+
+/// A file served at a path, with the failures of its first requests.
+#[derive(Debug, Clone, Default)]
+pub struct Route {
+    pub body: Vec<u8>,
+    pub failures: VecDeque<Failure>,
+}
+
+impl Route {
+    pub fn new(body: &[u8]) -> Self {
+        Self {
+            body: body.to_vec(),
+            failures: VecDeque::new(),
+        }
+    }
+
+    pub fn failing(mut self, failure: Failure) -> Self {
+        self.failures.push_back(failure);
+        self
+    }
+}
+
+/// A running server; requests are logged as "path range".
+pub struct TestServer {
+    pub url: String,
+    pub requests: Arc<Mutex<Vec<String>>>,
+}
+
+/// Serve `routes` on a free local port until the test exits.
+pub fn serve(routes: HashMap<String, Route>) -> TestServer {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(vec![]));
+    let routes = Arc::new(Mutex::new(routes));
+
+    let log = Arc::clone(&requests);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().map_while(Result::ok) {
+            let _ = handle(stream, &routes, &log);
+        }
+    });
+
+    TestServer { url, requests }
+}
+
+fn handle(
+    mut stream: TcpStream,
+    routes: &Mutex<HashMap<String, Route>>,
+    log: &Mutex<Vec<String>>,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_string();
+    let mut offset: Option<usize> = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("range") {
+                offset = value
+                    .trim()
+                    .strip_prefix("bytes=")
+                    .and_then(|v| v.trim_end_matches('-').parse().ok());
+            }
+        }
+    }
+    log.lock().unwrap().push(match offset {
+        Some(offset) => format!("{path} bytes={offset}-"),
+        None => path.clone(),
+    });
+
+    let (body, failure) = {
+        let mut routes = routes.lock().unwrap();
+        match routes.get_mut(&path) {
+            Some(route) => (route.body.clone(), route.failures.pop_front()),
+            None => return respond(&mut stream, "404 Not Found", &[], b""),
+        }
+    };
+
+    if let Some(Failure::Status(code)) = failure {
+        return respond(&mut stream, &format!("{code} Failure"), &[], b"");
+    }
+    let offset = offset.unwrap_or(0);
+    if offset > 0 && offset >= body.len() {
+        return respond(
+            &mut stream,
+            "416 Range Not Satisfiable",
+            &[format!("Content-Range: bytes */{}", body.len())],
+            b"",
+        );
+    }
+    let (status, headers) = if offset > 0 {
+        (
+            "206 Partial Content",
+            vec![format!(
+                "Content-Range: bytes {offset}-{}/{}",
+                body.len() - 1,
+                body.len()
+            )],
+        )
+    } else {
+        ("200 OK", vec![])
+    };
+    let rest = &body[offset..];
+    match failure {
+        Some(Failure::Truncate(n)) => {
+            write_head(&mut stream, status, &headers, rest.len())?;
+            stream.write_all(&rest[..n.min(rest.len())])?;
+            stream.flush()
+        }
+        _ => respond(&mut stream, status, &headers, rest),
+    }
+}
+
+fn write_head(
+    stream: &mut TcpStream,
+    status: &str,
+    headers: &[String],
+    len: usize,
+) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Length: {len}\r\nConnection: close\r\n"
+    )?;
+    for header in headers {
+        write!(stream, "{header}\r\n")?;
+    }
+    write!(stream, "\r\n")
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    headers: &[String],
+    body: &[u8],
+) -> std::io::Result<()> {
+    write_head(stream, status, headers, body.len())?;
+    stream.write_all(body)?;
+    stream.flush()
+}